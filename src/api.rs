@@ -1,75 +1,472 @@
-use std::{env, str::FromStr, sync::Arc};
+use std::{
+    collections::{BTreeSet, HashMap},
+    convert::Infallible,
+    env,
+    str::FromStr,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, RwLock,
+    },
+    time::Duration,
+};
 
 use axum::{
+    body::Body,
     debug_handler,
-    extract::{Path, State},
+    extract::{
+        ws::{Message, WebSocket, WebSocketUpgrade},
+        Path, Query, State,
+    },
+    http::{header, StatusCode},
     response::IntoResponse,
-    Json,
 };
-use serde::Deserialize;
-use serde_json::json;
+use futures_util::{stream, StreamExt};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
 use solana_client::nonblocking::rpc_client::RpcClient;
-use solana_sdk::{pubkey::Pubkey, signature::Keypair, signer::Signer};
-use tracing::{error, info, warn};
+use solana_sdk::{
+    commitment_config::CommitmentConfig,
+    native_token::{lamports_to_sol, sol_to_lamports},
+    pubkey::Pubkey,
+    signature::Keypair,
+    signer::Signer,
+};
+use tokio::sync::broadcast::error::RecvError;
+use tracing::{debug, error, info, warn};
 
 use crate::{
-    constants::Symbol,
-    get_rpc_client, get_rpc_client_blocking,
-    helper::{api_error, api_ok},
-    pump::{get_pump_info, Pump, PumpInfo},
-    raydium::{get_pool_info, Raydium},
-    swap::{self, SwapDirection, SwapInType},
-    token,
+    breaker::CircuitBreaker,
+    constants::{get_network, Symbol},
+    copy::{CopyConfig, CopyTasks},
+    dca::{DcaConfig, DcaSchedules},
+    error::{ApiError, ErrorCode},
+    extract::{ApiJson, JsonBody},
+    filter::MintFilters,
+    get_rpc_client, get_rpc_client_blocking, get_rpc_client_blocking_with_commitment,
+    get_rpc_client_with_commitment,
+    helper::{api_error, api_ok, get_solana_price, parse_commitment, redact_url},
+    idempotency::IdempotencyKeys,
+    jito,
+    ledger::{self, now_millis, Ledger, TradeFilter},
+    meteora::Meteora,
+    price::{self, PriceCache, PriceStreams, PriceVenue},
+    pump::{get_pump_info, upload_metadata, Pump, PumpInfo, TokenMetadata},
+    raydium::{self, get_pool_info, Raydium},
+    read_wallet_file,
+    strategy::{Strategies, StrategyConfig},
+    swap::{
+        self, PoolSelectionPolicy, Slippage, SlippageMode, SwapDirection, SwapInType, SwapParams,
+    },
+    telemetry,
+    token::{self, DecimalsCache, DustFilter, MetadataCache},
+    tx::{self, ConfirmMode, SentTransactions, TxStatuses},
 };
 
 #[derive(Clone)]
 pub struct AppState {
     pub client: Arc<RpcClient>,
     pub client_blocking: Arc<solana_client::rpc_client::RpcClient>,
-    pub wallet: Arc<Keypair>,
+    // signing keypair, replaced by `load_wallet`, read it through `wallet()`
+    wallet: Arc<RwLock<Arc<Keypair>>>,
+    // reject buys while set, see `swap::swap`
+    pub sell_only: Arc<AtomicBool>,
+    pub ledger: Arc<Ledger>,
+    pub price_streams: Arc<PriceStreams>,
+    pub price_cache: Arc<PriceCache>,
+    pub copy_tasks: Arc<CopyTasks>,
+    pub strategies: Arc<Strategies>,
+    pub dca: Arc<DcaSchedules>,
+    // pauses swaps after repeated failures, see `swap::swap`
+    pub breaker: Arc<CircuitBreaker>,
+    pub mint_filters: Arc<MintFilters>,
+    pub decimals_cache: Arc<DecimalsCache>,
+    pub metadata_cache: Arc<MetadataCache>,
+    // recent swap transactions, see `tx::bump`
+    pub sent_transactions: Arc<SentTransactions>,
+    pub tx_statuses: Arc<TxStatuses>,
+    // responses of swaps sent with an `Idempotency-Key`, see `idempotency::idempotent`
+    pub idempotency_keys: Arc<IdempotencyKeys>,
+}
+
+impl AppState {
+    pub fn new(
+        client: Arc<RpcClient>,
+        client_blocking: Arc<solana_client::rpc_client::RpcClient>,
+        wallet: Arc<Keypair>,
+    ) -> Self {
+        let sell_only = env::var("SELL_ONLY").ok() == Some("true".to_string());
+        match tx::get_nonce_account() {
+            Some(nonce_account) => info!("nonce mode is active, nonce account: {}", nonce_account),
+            None => info!("nonce mode is off, transactions use recent blockhashes"),
+        }
+        Self {
+            client,
+            client_blocking,
+            wallet: Arc::new(RwLock::new(wallet)),
+            sell_only: Arc::new(AtomicBool::new(sell_only)),
+            ledger: Arc::new(Ledger::default()),
+            price_streams: Arc::new(PriceStreams::default()),
+            price_cache: Arc::new(PriceCache::default()),
+            copy_tasks: Arc::new(CopyTasks::default()),
+            strategies: Arc::new(Strategies::default()),
+            dca: Arc::new(DcaSchedules::default()),
+            breaker: Arc::new(CircuitBreaker::default()),
+            decimals_cache: Arc::new(DecimalsCache::default()),
+            metadata_cache: Arc::new(MetadataCache::default()),
+            sent_transactions: Arc::new(SentTransactions::default()),
+            tx_statuses: Arc::new(TxStatuses::default()),
+            idempotency_keys: Arc::new(IdempotencyKeys::default()),
+            mint_filters: Arc::new(MintFilters::from_env().expect("failed to load mint filters")),
+        }
+    }
+
+    /// Same state with rpc clients reading at `commitment`
+    pub fn with_commitment(&self, commitment: CommitmentConfig) -> anyhow::Result<Self> {
+        Ok(Self {
+            client: get_rpc_client_with_commitment(commitment)?,
+            client_blocking: get_rpc_client_blocking_with_commitment(commitment)?,
+            ..self.clone()
+        })
+    }
+
+    pub fn is_sell_only(&self) -> bool {
+        self.sell_only.load(Ordering::Relaxed)
+    }
+
+    /// The current signing keypair, swaps already running keep the one they started with
+    pub fn wallet(&self) -> Arc<Keypair> {
+        self.wallet.read().unwrap().clone()
+    }
+
+    /// Sign with `wallet` from now on, returns the previous keypair
+    pub fn set_wallet(&self, wallet: Arc<Keypair>) -> Arc<Keypair> {
+        std::mem::replace(&mut *self.wallet.write().unwrap(), wallet)
+    }
 }
 
 #[derive(Debug, Deserialize)]
 pub struct CreateSwap {
     mint: String,
     direction: SwapDirection,
-    amount_in: f64,
+    amount_in: Option<f64>,
+    /// buys only, converted to sol at the current sol price, excludes amount_in
+    amount_in_usd: Option<f64>,
     in_type: Option<SwapInType>,
     slippage: Option<u64>,
+    slippage_mode: Option<SlippageMode>,
     jito: Option<bool>,
+    pool_selection: Option<PoolSelectionPolicy>,
+    confirm_large: Option<bool>,
+    webhook_url: Option<String>,
+    /// sol floor for pump sells, excludes slippage and slippage_mode
+    min_sol_out: Option<f64>,
+    /// raydium pool to trade on, skips pool discovery
+    pool_id: Option<String>,
+    /// processed, confirmed or finalized
+    commitment: Option<String>,
+    /// `sync` (default) waits for confirmation, `async` returns once submitted
+    mode: Option<ConfirmMode>,
+    /// retry slippage failures with more slippage, up to SLIPPAGE_BUMP_MAX
+    auto_bump_slippage: Option<bool>,
+}
+
+impl JsonBody for CreateSwap {
+    fn example() -> Option<Value> {
+        Some(json!({
+            "mint": "EKpQGSJtjMFqKZ9KQanSqYXRcF8fBopzLHYxdM65zcjm",
+            "direction": "buy",
+            "amount_in": 0.001,
+            "in_type": "qty",
+            "slippage": 10,
+        }))
+    }
+
+    fn allowed_values(field: &str) -> Option<&'static [&'static str]> {
+        match field {
+            "direction" => Some(&["buy", "sell"]),
+            "in_type" => Some(&["qty", "pct"]),
+            "slippage_mode" => Some(&["fixed", "depth"]),
+            "pool_selection" => Some(&["liquidity", "fee", "program"]),
+            "mode" => Some(&["sync", "async"]),
+            _ => None,
+        }
+    }
+}
+
+#[debug_handler]
+pub async fn health(State(state): State<AppState>) -> impl IntoResponse {
+    api_ok(json!({
+        "alive": true,
+        "sell_only": state.is_sell_only(),
+    }))
+}
+
+#[debug_handler]
+pub async fn ready(State(state): State<AppState>) -> impl IntoResponse {
+    let rpc_ok = match tokio::time::timeout(Duration::from_secs(2), state.client.get_health()).await
+    {
+        Ok(Ok(())) => true,
+        Ok(Err(err)) => {
+            warn!("ready: rpc get_health err: {:#?}", err);
+            false
+        }
+        Err(_) => {
+            warn!("ready: rpc get_health timeout");
+            false
+        }
+    };
+    let network = get_network();
+    let tip_stream_ok = jito::ws::TIP_STREAM_CONNECTED.load(Ordering::Relaxed);
+    let data = json!({
+        "network": network.as_str(),
+        "wallet": state.wallet().pubkey().to_string(),
+        "rpc": {
+            "url": redact_url(&state.client.url()),
+            "healthy": rpc_ok,
+        },
+        "tip_stream": {
+            "connected": tip_stream_ok,
+        },
+        "breaker": state.breaker.status(),
+    });
+
+    // the tip stream is not started off mainnet
+    if rpc_ok && (tip_stream_ok || !network.supports_jito()) {
+        api_ok(data)
+    } else {
+        api_error(
+            ApiError::new(ErrorCode::ServiceUnavailable, "dependencies unavailable")
+                .with_data(data),
+        )
+    }
+}
+
+#[debug_handler]
+pub async fn metrics(State(state): State<AppState>) -> impl IntoResponse {
+    metrics::gauge!(telemetry::SELL_ONLY).set(if state.is_sell_only() { 1.0 } else { 0.0 });
+    match telemetry::render() {
+        Some(body) => (
+            StatusCode::OK,
+            [(header::CONTENT_TYPE, "text/plain; version=0.0.4")],
+            body,
+        ),
+        None => (
+            StatusCode::SERVICE_UNAVAILABLE,
+            [(header::CONTENT_TYPE, "text/plain; version=0.0.4")],
+            "metrics recorder not installed".to_string(),
+        ),
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct SetSellOnly {
+    enabled: bool,
+}
+
+impl JsonBody for SetSellOnly {}
+
+#[debug_handler]
+pub async fn filters(State(state): State<AppState>) -> impl IntoResponse {
+    api_ok(state.mint_filters.as_ref())
+}
+
+#[debug_handler]
+pub async fn get_sell_only(State(state): State<AppState>) -> impl IntoResponse {
+    api_ok(json!({ "sell_only": state.is_sell_only() }))
+}
+
+#[debug_handler]
+pub async fn set_sell_only(
+    State(state): State<AppState>,
+    ApiJson(input): ApiJson<SetSellOnly>,
+) -> impl IntoResponse {
+    let previous = state.sell_only.swap(input.enabled, Ordering::Relaxed);
+    if previous != input.enabled {
+        warn!("sell only mode changed: {} -> {}", previous, input.enabled);
+    }
+    api_ok(json!({ "sell_only": input.enabled }))
+}
+
+#[debug_handler]
+pub async fn resume(State(state): State<AppState>) -> impl IntoResponse {
+    let was_tripped = state.breaker.resume();
+    if was_tripped {
+        warn!("trading resumed by an admin request");
+    }
+    api_ok(json!({
+        "was_tripped": was_tripped,
+        "breaker": state.breaker.status(),
+    }))
+}
+
+impl TryFrom<CreateSwap> for SwapParams {
+    type Error = anyhow::Error;
+
+    fn try_from(input: CreateSwap) -> anyhow::Result<Self> {
+        let min_sol_out = match input.min_sol_out {
+            Some(_) if input.slippage.is_some() || input.slippage_mode.is_some() => {
+                return Err(ApiError::new(
+                    ErrorCode::InvalidInput,
+                    "min_sol_out and slippage are mutually exclusive",
+                )
+                .into());
+            }
+            Some(min_sol_out) if !min_sol_out.is_finite() || min_sol_out <= 0.0 => {
+                return Err(ApiError::new(
+                    ErrorCode::InvalidInput,
+                    "min_sol_out must be greater than 0",
+                )
+                .into());
+            }
+            Some(min_sol_out) => Some(sol_to_lamports(min_sol_out)),
+            None => None,
+        };
+        let amount_in = match (input.amount_in, input.amount_in_usd) {
+            (Some(_), Some(_)) => {
+                return Err(ApiError::new(
+                    ErrorCode::InvalidInput,
+                    "amount_in and amount_in_usd are mutually exclusive",
+                )
+                .into());
+            }
+            (None, None) => {
+                return Err(ApiError::new(
+                    ErrorCode::InvalidInput,
+                    "amount_in or amount_in_usd is required",
+                )
+                .into());
+            }
+            (Some(amount_in), None) => amount_in,
+            (None, Some(amount_in_usd)) => {
+                if input.direction != SwapDirection::Buy
+                    || matches!(input.in_type, Some(SwapInType::Pct))
+                {
+                    return Err(ApiError::new(
+                        ErrorCode::InvalidInput,
+                        "amount_in_usd is only supported for qty buys",
+                    )
+                    .into());
+                }
+                if !amount_in_usd.is_finite() || amount_in_usd <= 0.0 {
+                    return Err(ApiError::new(
+                        ErrorCode::InvalidInput,
+                        "amount_in_usd must be greater than 0",
+                    )
+                    .into());
+                }
+                // set from the sol price right before the swap
+                0.0
+            }
+        };
+        let commitment = parse_commitment(input.commitment.as_deref())?;
+        let slippage = input.slippage.unwrap_or_else(swap::get_default_slippage);
+        Ok(SwapParams {
+            mint: input.mint,
+            direction: input.direction,
+            amount_in,
+            amount_in_usd: input.amount_in_usd,
+            in_type: input.in_type.unwrap_or(SwapInType::Qty),
+            slippage: Slippage::new(input.slippage_mode.unwrap_or_default(), slippage),
+            use_jito: input.jito.unwrap_or(false),
+            confirm_large: input.confirm_large.unwrap_or(false),
+            pool_selection: input
+                .pool_selection
+                .unwrap_or_else(swap::get_pool_selection_policy),
+            webhook_url: input.webhook_url,
+            min_sol_out,
+            pool_id: input.pool_id,
+            commitment,
+            confirm_mode: input.mode.unwrap_or_default(),
+            auto_bump_slippage: input.auto_bump_slippage.unwrap_or(false),
+        })
+    }
 }
 
 #[debug_handler]
 pub async fn swap(
     State(state): State<AppState>,
-    Json(input): Json<CreateSwap>,
+    ApiJson(input): ApiJson<CreateSwap>,
 ) -> impl IntoResponse {
-    let slippage = match input.slippage {
-        Some(v) => v,
-        None => {
-            let slippage = env::var("SLIPPAGE").unwrap_or("5".to_string());
-            let slippage = slippage.parse::<u64>().unwrap_or(5);
-            slippage
-        }
+    let params = match SwapParams::try_from(input) {
+        Ok(params) => params,
+        Err(err) => return api_error(err),
     };
+    info!("{:?}", params);
 
-    info!("{:?}, slippage: {}", input, slippage);
-
-    let result = swap::swap(
-        state,
-        input.mint.as_str(),
-        input.amount_in,
-        input.direction.clone(),
-        input.in_type.unwrap_or(SwapInType::Qty),
-        slippage,
-        input.jito.unwrap_or(false),
-    )
-    .await;
+    let result = swap::swap_with_timeout(state, params).await;
     match result {
-        Ok(txs) => api_ok(txs),
+        Ok(result) => api_ok(result),
         Err(err) => {
             warn!("swap err: {:#?}", err);
-            api_error(&err.to_string())
+            api_error(err)
+        }
+    }
+}
+
+#[debug_handler]
+pub async fn validate_swap(
+    State(state): State<AppState>,
+    ApiJson(input): ApiJson<CreateSwap>,
+) -> impl IntoResponse {
+    let params = match SwapParams::try_from(input) {
+        Ok(params) => params,
+        Err(err) => return api_error(err),
+    };
+    match swap::resolve(&state, &params).await {
+        Ok(resolved) => api_ok(resolved),
+        Err(err) => {
+            warn!("validate swap err: {:#?}", err);
+            api_error(err)
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct SimulateBundle {
+    swaps: Vec<CreateSwap>,
+    /// send the swaps after a clean simulation
+    #[serde(default)]
+    submit: bool,
+}
+
+impl JsonBody for SimulateBundle {
+    fn example() -> Option<Value> {
+        Some(json!({ "swaps": [CreateSwap::example()], "submit": false }))
+    }
+
+    fn allowed_values(field: &str) -> Option<&'static [&'static str]> {
+        CreateSwap::allowed_values(field)
+    }
+}
+
+#[debug_handler]
+pub async fn simulate_bundle(
+    State(state): State<AppState>,
+    ApiJson(input): ApiJson<SimulateBundle>,
+) -> impl IntoResponse {
+    let swaps = match input
+        .swaps
+        .into_iter()
+        .map(SwapParams::try_from)
+        .collect::<Result<Vec<_>, _>>()
+    {
+        Ok(swaps) => swaps,
+        Err(err) => return api_error(err),
+    };
+    if !input.submit {
+        return match swap::simulate_bundle(&state, &swaps).await {
+            Ok(simulation) => api_ok(simulation),
+            Err(err) => {
+                warn!("simulate bundle err: {:#?}", err);
+                api_error(err)
+            }
+        };
+    }
+    match swap::submit_bundle(state, swaps).await {
+        Ok(submission) => api_ok(submission),
+        Err(err) => {
+            warn!("submit bundle err: {:#?}", err);
+            api_error(err)
         }
     }
 }
@@ -82,16 +479,22 @@ pub async fn get_pool(
     let client = match get_rpc_client() {
         Ok(client) => client,
         Err(err) => {
-            return api_error(&format!("failed to get rpc client: {err}"));
+            return api_error(ApiError::new(
+                ErrorCode::RpcError,
+                format!("failed to get rpc client: {err}"),
+            ));
         }
     };
     let client_blocking = match get_rpc_client_blocking() {
         Ok(client) => client,
         Err(err) => {
-            return api_error(&format!("failed to get rpc client: {err}"));
+            return api_error(ApiError::new(
+                ErrorCode::RpcError,
+                format!("failed to get rpc client: {err}"),
+            ));
         }
     };
-    let wallet = state.wallet;
+    let wallet = state.wallet();
     let mut swapx = Raydium::new(client, wallet);
     swapx.with_blocking_client(client_blocking);
     match swapx.get_pool(pool_id.as_str()).await {
@@ -104,7 +507,7 @@ pub async fn get_pool(
         })),
         Err(err) => {
             warn!("get pool err: {:#?}", err);
-            api_error(&err.to_string())
+            api_error(err)
         }
     }
 }
@@ -114,49 +517,148 @@ pub async fn get_pool_by_token_address(
     State(_state): State<AppState>,
     Path(token_address): Path<String>,
 ) -> impl IntoResponse {
-    let pool_data = get_pool_info(&token_address, Symbol::WSOL_TOKEN).await;
+    let pool_data = get_pool_info(&token_address, Symbol::WSOL_TOKEN)
+        .await
+        .and_then(|data| {
+            if data.data.is_empty() {
+                Err(raydium::pool_not_found(&token_address))
+            } else {
+                Ok(data)
+            }
+        });
     info!("get_pool_by_token_address: {:#?}", pool_data);
     match pool_data {
         Ok(data) => api_ok(json!(data)),
         Err(err) => {
             warn!("get swap pool by token address err: {:#?}", err);
-            api_error(&err.to_string())
+            api_error(err)
         }
     }
 }
 
+#[derive(Debug, Deserialize)]
+pub struct PriceQuery {
+    fresh: Option<bool>,
+    /// processed, confirmed or finalized, defaults to the shared clients
+    commitment: Option<String>,
+}
+
+// price reads at the requested commitment
+fn price_state(state: AppState, query: &PriceQuery) -> anyhow::Result<AppState> {
+    match &query.commitment {
+        Some(commitment) => state.with_commitment(parse_commitment(Some(commitment))?),
+        None => Ok(state),
+    }
+}
+
 #[debug_handler]
 pub async fn get_raydium_token_price(
     State(state): State<AppState>,
     Path(token_address): Path<String>,
+    Query(query): Query<PriceQuery>,
 ) -> impl IntoResponse {
-    let pool_data = get_pool_info(&token_address, Symbol::WSOL_TOKEN).await;
-    info!("get_pool_by_token_address: {:#?}", pool_data);
-    match pool_data {
-        Ok(data) => {
-            match data.get_pool() {
-                Some(pool) => {
-                    let mut swapx = Raydium::new(state.client.clone(), state.wallet.clone());
-                    swapx.with_blocking_client(state.client_blocking.clone());
-                    let price = swapx.get_pool_price(Some(&pool.id), None).await;
-                    match price {
-                        Ok(raydium_info) => api_ok(json!(raydium_info)),
-                        Err(err) => {
-                            error!("get pool price err: {:#?}", err);
-                            api_error(&err.to_string())
-                        }
-                    }
-                }
-                None => {
-                    // warn!("get pool err: {:#?}", err);
-                    // api_error(&err.to_string())
-                    api_error("pool not found")
-                }
-            }
+    let state = match price_state(state, &query) {
+        Ok(state) => state,
+        Err(err) => return api_error(err),
+    };
+    let fetch = || async {
+        let pool_data = get_pool_info(&token_address, Symbol::WSOL_TOKEN).await;
+        info!("get_pool_by_token_address: {:#?}", pool_data);
+        let pool = pool_data
+            .inspect_err(|err| warn!("get swap pool by token address err: {:#?}", err))?
+            .get_pool()
+            .ok_or_else(|| raydium::pool_not_found(&token_address))?;
+        let mut swapx = Raydium::new(state.client.clone(), state.wallet());
+        swapx.with_blocking_client(state.client_blocking.clone());
+        let raydium_info = swapx
+            .get_pool_price(Some(&pool.id), None)
+            .await
+            .inspect_err(|err| error!("get pool price err: {:#?}", err))?;
+        Ok::<Value, anyhow::Error>(json!(raydium_info))
+    };
+    match state
+        .price_cache
+        .get_or_fetch(
+            &token_address,
+            &price::cache_venue(&state, "raydium"),
+            query.fresh.unwrap_or(false),
+            fetch,
+        )
+        .await
+    {
+        Ok(data) => api_ok(data),
+        Err(err) => api_error(err),
+    }
+}
+
+#[debug_handler]
+pub async fn get_meteora_token_price(
+    State(state): State<AppState>,
+    Path(token_address): Path<String>,
+    Query(query): Query<PriceQuery>,
+) -> impl IntoResponse {
+    let state = match price_state(state, &query) {
+        Ok(state) => state,
+        Err(err) => return api_error(err),
+    };
+    let fetch = || async {
+        let mut swapx = Meteora::new(state.client.clone(), state.wallet());
+        swapx
+            .with_blocking_client(state.client_blocking.clone())
+            .with_decimals_cache(state.decimals_cache.clone());
+        let meteora_info = swapx.get_pool_price(None, Some(&token_address)).await?;
+        Ok::<Value, anyhow::Error>(json!(meteora_info))
+    };
+    match state
+        .price_cache
+        .get_or_fetch(
+            &token_address,
+            &price::cache_venue(&state, "meteora"),
+            query.fresh.unwrap_or(false),
+            fetch,
+        )
+        .await
+    {
+        Ok(data) => api_ok(data),
+        Err(err) => {
+            warn!("get meteora token {token_address} price err: {:#?}", err);
+            api_error(err)
         }
+    }
+}
+
+#[debug_handler]
+pub async fn get_pumpswap_token_price(
+    State(state): State<AppState>,
+    Path(token_address): Path<String>,
+    Query(query): Query<PriceQuery>,
+) -> impl IntoResponse {
+    let state = match price_state(state, &query) {
+        Ok(state) => state,
+        Err(err) => return api_error(err),
+    };
+    let fetch = || async {
+        let mut swapx = Pump::new(state.client.clone(), state.wallet());
+        swapx
+            .with_blocking_client(state.client_blocking.clone())
+            .with_decimals_cache(state.decimals_cache.clone());
+        let pump_swap_info = swapx.get_pump_swap_price(&token_address).await?;
+        Ok::<Value, anyhow::Error>(json!(pump_swap_info))
+    };
+    match state
+        .price_cache
+        .get_or_fetch(
+            &token_address,
+            &price::cache_venue(&state, "pumpswap"),
+            query.fresh.unwrap_or(false),
+            fetch,
+        )
+        .await
+    {
+        Ok(data) => api_ok(data),
         Err(err) => {
-            warn!("get swap pool by token address err: {:#?}", err);
-            api_error(&err.to_string())
+            warn!("get pumpswap token {token_address} price err: {:#?}", err);
+            api_error(err)
         }
     }
 }
@@ -165,18 +667,108 @@ pub async fn get_raydium_token_price(
 pub async fn get_pump_token_price(
     State(state): State<AppState>,
     Path(token_address): Path<String>,
+    Query(query): Query<PriceQuery>,
 ) -> impl IntoResponse {
-    let mut swapx = Pump::new(state.client.clone(), state.wallet.clone());
-    swapx.with_blocking_client(state.client_blocking.clone());
-    match swapx.get_pump_price(&token_address).await {
-        Ok(data) => api_ok(json!({
+    let state = match price_state(state, &query) {
+        Ok(state) => state,
+        Err(err) => return api_error(err),
+    };
+    let fetch = || async {
+        let mut swapx = Pump::new(state.client.clone(), state.wallet());
+        swapx.with_blocking_client(state.client_blocking.clone());
+        let data = swapx.get_pump_price(&token_address).await?;
+        Ok::<Value, anyhow::Error>(json!({
             "base_amount": data.0,
             "quote_amount": data.1,
             "price": data.2,
-        })),
+        }))
+    };
+    match state
+        .price_cache
+        .get_or_fetch(
+            &token_address,
+            &price::cache_venue(&state, "pump"),
+            query.fresh.unwrap_or(false),
+            fetch,
+        )
+        .await
+    {
+        Ok(data) => api_ok(data),
         Err(err) => {
             warn!("get pump token {token_address} price err: {:#?}", err);
-            api_error(&err.to_string())
+            api_error(err)
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct BatchPrice {
+    tokens: Vec<String>,
+    venue: Option<PriceVenue>,
+}
+
+impl JsonBody for BatchPrice {}
+
+#[debug_handler]
+pub async fn batch_price(
+    State(state): State<AppState>,
+    Query(query): Query<PriceQuery>,
+    ApiJson(input): ApiJson<BatchPrice>,
+) -> impl IntoResponse {
+    let state = match price_state(state, &query) {
+        Ok(state) => state,
+        Err(err) => return api_error(err),
+    };
+    match price::get_token_prices(
+        &state,
+        input.tokens,
+        input.venue.unwrap_or_default(),
+        query.fresh.unwrap_or(false),
+    )
+    .await
+    {
+        Ok(prices) => api_ok(json!({ "prices": prices })),
+        Err(err) => {
+            warn!("batch price err: {:#?}", err);
+            api_error(err)
+        }
+    }
+}
+
+#[debug_handler]
+pub async fn price_stream(
+    ws: WebSocketUpgrade,
+    State(state): State<AppState>,
+    Path(token_address): Path<String>,
+) -> impl IntoResponse {
+    if Pubkey::from_str(&token_address).is_err() {
+        return api_error(ApiError::new(
+            ErrorCode::InvalidInput,
+            format!("invalid mint pubkey: {token_address}"),
+        ));
+    }
+    ws.on_upgrade(move |socket| stream_prices(socket, state, token_address))
+}
+
+async fn stream_prices(mut socket: WebSocket, state: AppState, mint: String) {
+    let mut ticks = state.price_streams.subscribe(&state, &mint).await;
+    loop {
+        tokio::select! {
+            tick = ticks.recv() => match tick {
+                Ok(tick) => {
+                    if socket.send(Message::Text(tick)).await.is_err() {
+                        break;
+                    }
+                }
+                Err(RecvError::Lagged(skipped)) => {
+                    debug!("price subscriber of {} lagged by {} ticks", mint, skipped);
+                }
+                Err(RecvError::Closed) => break,
+            },
+            incoming = socket.recv() => match incoming {
+                Some(Ok(Message::Close(_))) | Some(Err(_)) | None => break,
+                _ => {}
+            },
         }
     }
 }
@@ -201,49 +793,323 @@ pub async fn get_coin_info(wallet: Arc<Keypair>, mint: &String) -> Result<PumpIn
             return Err(err.to_string());
         }
     };
-    if pump_info.complete {
-        let mut swapx = Raydium::new(client, wallet);
-        swapx.with_blocking_client(client_blocking);
-        match swapx.get_pool_price(None, Some(mint.as_str())).await {
-            Ok(raydium_info) => {
-                pump_info.raydium_info = Some(raydium_info);
-            }
-            Err(err) => {
-                warn!("get raydium pool price err: {:#?}", err);
-            }
+    if !pump_info.complete {
+        return Ok(pump_info);
+    }
+    // graduated coins migrate to pumpswap, older ones went to raydium
+    let mut swapx = Pump::new(client.clone(), wallet.clone());
+    swapx.with_blocking_client(client_blocking.clone());
+    match swapx.get_pump_swap_price(mint).await {
+        Ok(pump_swap_info) => {
+            pump_info.pump_swap_pool = Some(pump_swap_info.pool_id.clone());
+            pump_info.pump_swap_info = Some(pump_swap_info);
+            return Ok(pump_info);
+        }
+        Err(err) => {
+            debug!("no pumpswap pool for {}: {:#?}", mint, err);
+        }
+    }
+    let mut swapx = Raydium::new(client, wallet);
+    swapx.with_blocking_client(client_blocking);
+    match swapx.get_pool_price(None, Some(mint.as_str())).await {
+        Ok(raydium_info) => {
+            pump_info.raydium_pool = Some(raydium_info.amm_pool_id.clone());
+            pump_info.raydium_info = Some(raydium_info);
+        }
+        Err(err) => {
+            warn!("get raydium pool price err: {:#?}", err);
         }
     }
     Ok(pump_info)
 }
 
 pub async fn coins(State(state): State<AppState>, Path(mint): Path<String>) -> impl IntoResponse {
-    match get_coin_info(state.wallet, &mint).await {
+    match get_coin_info(state.wallet(), &mint).await {
         Ok(pump_info) => {
             return api_ok(pump_info);
         }
         Err(err_msg) => {
-            return api_error(&err_msg);
+            return api_error(err_msg);
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct TokenAccountsQuery {
+    min_amount: Option<f64>,
+    min_value_usd: Option<f64>,
+    /// price every holding in usd, implied by min_value_usd
+    priced: Option<bool>,
+    offset: Option<usize>,
+    limit: Option<usize>,
+}
+
+fn check_dust_filter(filter: &DustFilter) -> anyhow::Result<()> {
+    for (name, value) in [
+        ("min_amount", filter.min_amount),
+        ("min_value_usd", filter.min_value_usd),
+    ] {
+        if value.is_some_and(|value| !value.is_finite() || value < 0.0) {
+            return Err(ApiError::new(
+                ErrorCode::InvalidInput,
+                format!("{} must not be negative", name),
+            )
+            .into());
         }
     }
+    Ok(())
+}
+
+// the accounts left by the dust filter, and the usd prices used when `priced`
+async fn holdings_page(
+    state: &AppState,
+    token_accounts: token::TokenAccounts,
+    filter: DustFilter,
+    priced: bool,
+    offset: usize,
+    limit: usize,
+) -> anyhow::Result<(token::HoldingsPage, Option<HashMap<String, f64>>)> {
+    let prices = if priced || filter.min_value_usd.is_some() {
+        // dust below min_amount is never priced
+        let mints = token_accounts
+            .iter()
+            .filter(|account| {
+                account.ui_amount > 0.0
+                    && filter
+                        .min_amount
+                        .map_or(true, |min| account.ui_amount >= min)
+            })
+            .map(|account| account.mint.clone())
+            .collect::<BTreeSet<_>>();
+        let prices = price::get_usd_prices(state, mints.into_iter().collect())
+            .await
+            .inspect_err(|err| warn!("price token_accounts err: {:#?}", err))?;
+        Some(prices)
+    } else {
+        None
+    };
+    let page = token::filter_holdings(
+        token_accounts,
+        prices.as_ref(),
+        filter,
+        offset,
+        limit.min(token::TOKEN_ACCOUNTS_MAX_LIMIT),
+    );
+    Ok((page, prices))
 }
 
 #[debug_handler]
-pub async fn token_accounts(State(state): State<AppState>) -> impl IntoResponse {
+pub async fn token_accounts(
+    State(state): State<AppState>,
+    Query(query): Query<TokenAccountsQuery>,
+) -> impl IntoResponse {
+    let filter = DustFilter {
+        min_amount: query.min_amount,
+        min_value_usd: query.min_value_usd,
+    };
+    if let Err(err) = check_dust_filter(&filter) {
+        return api_error(err);
+    }
     let client = match get_rpc_client() {
         Ok(client) => client,
         Err(err) => {
-            return api_error(&format!("failed to get rpc client: {err}"));
+            return api_error(ApiError::new(
+                ErrorCode::RpcError,
+                format!("failed to get rpc client: {err}"),
+            ));
         }
     };
-    let wallet = state.wallet;
+    let wallet = state.wallet();
 
-    let token_accounts = token::token_accounts(&client, &wallet.pubkey()).await;
-
-    match token_accounts {
-        Ok(token_accounts) => api_ok(token_accounts),
+    let token_accounts = match token::token_accounts(&client, &wallet.pubkey()).await {
+        Ok(token_accounts) => token_accounts,
         Err(err) => {
             warn!("get token_accounts err: {:#?}", err);
-            api_error(&err.to_string())
+            return api_error(err);
+        }
+    };
+    match holdings_page(
+        &state,
+        token_accounts,
+        filter,
+        query.priced.unwrap_or(false),
+        query.offset.unwrap_or(0),
+        query.limit.unwrap_or(token::TOKEN_ACCOUNTS_LIMIT),
+    )
+    .await
+    {
+        Ok((page, _)) => api_ok(page),
+        Err(err) => api_error(err),
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct WalletBalanceQuery {
+    /// any wallet pubkey, defaults to the configured wallet
+    wallet: Option<String>,
+    min_amount: Option<f64>,
+    min_value_usd: Option<f64>,
+    /// defaults to true
+    priced: Option<bool>,
+    offset: Option<usize>,
+    limit: Option<usize>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct WalletBalance {
+    pub wallet: String,
+    pub lamports: u64,
+    pub sol_balance: f64,
+    /// ui amount of the wallet WSOL accounts, also listed in holdings
+    pub wsol_balance: f64,
+    pub sol_price_usd: Option<f64>,
+    pub holdings: token::HoldingsPage,
+    /// native sol plus every priced holding
+    pub total_value_usd: Option<f64>,
+}
+
+#[debug_handler]
+pub async fn wallet_balance(
+    State(state): State<AppState>,
+    Query(query): Query<WalletBalanceQuery>,
+) -> impl IntoResponse {
+    let owner = match &query.wallet {
+        Some(wallet) => match Pubkey::from_str(wallet) {
+            Ok(owner) => owner,
+            Err(_) => {
+                return api_error(ApiError::new(
+                    ErrorCode::InvalidInput,
+                    "invalid wallet pubkey",
+                ))
+            }
+        },
+        None => state.wallet().pubkey(),
+    };
+    let filter = DustFilter {
+        min_amount: query.min_amount,
+        min_value_usd: query.min_value_usd,
+    };
+    if let Err(err) = check_dust_filter(&filter) {
+        return api_error(err);
+    }
+    let lamports = match state.client.get_balance(&owner).await {
+        Ok(lamports) => lamports,
+        Err(err) => {
+            return api_error(ApiError::new(
+                ErrorCode::RpcError,
+                format!("failed to get balance: {}", err),
+            ))
+        }
+    };
+    let token_accounts = match token::token_accounts(&state.client, &owner).await {
+        Ok(token_accounts) => token_accounts,
+        Err(err) => {
+            warn!("get token_accounts of {} err: {:#?}", owner, err);
+            return api_error(err);
+        }
+    };
+    let native_mint = spl_token::native_mint::ID.to_string();
+    let wsol_balance = token_accounts
+        .iter()
+        .filter(|account| account.mint == native_mint)
+        .map(|account| account.ui_amount)
+        .sum::<f64>();
+    let priced = query.priced.unwrap_or(true);
+    let (holdings, prices) = match holdings_page(
+        &state,
+        token_accounts,
+        filter,
+        priced,
+        query.offset.unwrap_or(0),
+        query.limit.unwrap_or(token::TOKEN_ACCOUNTS_LIMIT),
+    )
+    .await
+    {
+        Ok(page) => page,
+        Err(err) => return api_error(err),
+    };
+    let sol_balance = lamports_to_sol(lamports);
+    let sol_price_usd = match prices {
+        Some(prices) => match prices.get(&native_mint) {
+            Some(sol_price) => Some(*sol_price),
+            None => match get_solana_price().await {
+                Ok(sol_price) => Some(sol_price),
+                Err(err) => {
+                    return api_error(ApiError::new(
+                        ErrorCode::RpcError,
+                        format!("failed to get the sol price: {}", err),
+                    ))
+                }
+            },
+        },
+        None => None,
+    };
+    let total_value_usd = sol_price_usd.map(|sol_price| {
+        sol_balance * sol_price + holdings.summary.total_value_usd.unwrap_or_default()
+    });
+    api_ok(WalletBalance {
+        wallet: owner.to_string(),
+        lamports,
+        sol_balance,
+        wsol_balance,
+        sol_price_usd,
+        holdings,
+        total_value_usd,
+    })
+}
+
+#[derive(Debug, Deserialize)]
+pub struct LoadWallet {
+    /// keypair file on the daemon host, a json byte array or a base58 string
+    path: String,
+}
+
+impl JsonBody for LoadWallet {}
+
+#[debug_handler]
+pub async fn load_wallet(
+    State(state): State<AppState>,
+    ApiJson(input): ApiJson<LoadWallet>,
+) -> impl IntoResponse {
+    let wallet = match read_wallet_file(&input.path) {
+        Ok(wallet) => wallet,
+        Err(err) => {
+            warn!("load wallet {} err: {:#?}", input.path, err);
+            return api_error(err);
+        }
+    };
+    let pubkey = wallet.pubkey();
+    let previous = state.set_wallet(Arc::new(wallet)).pubkey();
+    info!("wallet rotated from {} to {}", previous, pubkey);
+    api_ok(json!({
+        "wallet": pubkey.to_string(),
+        "previous": previous.to_string(),
+    }))
+}
+
+#[debug_handler]
+pub async fn token_metadata(
+    State(state): State<AppState>,
+    Path(mint): Path<String>,
+) -> impl IntoResponse {
+    let mint = match Pubkey::from_str(&mint) {
+        Ok(mint) => mint,
+        Err(_) => {
+            return api_error(ApiError::new(
+                ErrorCode::InvalidInput,
+                "invalid mint pubkey",
+            ))
+        }
+    };
+    match state
+        .metadata_cache
+        .get(&state.client, &state.decimals_cache, &mint)
+        .await
+    {
+        Ok(info) => api_ok(info),
+        Err(err) => {
+            warn!("get token {} metadata err: {:#?}", mint, err);
+            api_error(err)
         }
     }
 }
@@ -256,15 +1122,21 @@ pub async fn token_account(
     let client = match get_rpc_client() {
         Ok(client) => client,
         Err(err) => {
-            return api_error(&format!("failed to get rpc client: {err}"));
+            return api_error(ApiError::new(
+                ErrorCode::RpcError,
+                format!("failed to get rpc client: {err}"),
+            ));
         }
     };
-    let wallet = state.wallet;
+    let wallet = state.wallet();
 
     let mint = if let Ok(mint) = Pubkey::from_str(mint.as_str()) {
         mint
     } else {
-        return api_error("invalid mint pubkey");
+        return api_error(ApiError::new(
+            ErrorCode::InvalidInput,
+            "invalid mint pubkey",
+        ));
     };
 
     let token_account = token::token_account(&client, &wallet.pubkey(), mint).await;
@@ -273,7 +1145,457 @@ pub async fn token_account(
         Ok(token_account) => api_ok(token_account),
         Err(err) => {
             warn!("get token_account err: {:#?}", err);
-            api_error(&err.to_string())
+            api_error(err)
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CloseTokenAccounts {
+    /// every empty account when absent
+    mints: Option<Vec<String>>,
+}
+
+impl JsonBody for CloseTokenAccounts {
+    fn example() -> Option<Value> {
+        Some(json!({ "mints": ["Fof1DyVSYiQGCnT3uTbmq8kQMPdwL35x1bD82NaTs9mM"] }))
+    }
+}
+
+#[debug_handler]
+pub async fn close_token_accounts(
+    State(state): State<AppState>,
+    ApiJson(input): ApiJson<CloseTokenAccounts>,
+) -> impl IntoResponse {
+    let mints = match input.mints {
+        Some(mints) => {
+            let mut pubkeys = vec![];
+            for mint in mints.iter() {
+                match Pubkey::from_str(mint) {
+                    Ok(pubkey) => pubkeys.push(pubkey),
+                    Err(_) => {
+                        return api_error(ApiError::new(
+                            ErrorCode::InvalidInput,
+                            format!("invalid mint pubkey: {mint}"),
+                        ))
+                    }
+                }
+            }
+            Some(pubkeys)
+        }
+        None => None,
+    };
+
+    match token::close_empty_accounts(
+        &state.client,
+        &state.client_blocking,
+        &state.wallet(),
+        mints,
+    )
+    .await
+    {
+        Ok(result) => api_ok(result),
+        Err(err) => {
+            warn!("close token accounts err: {:#?}", err);
+            api_error(err)
+        }
+    }
+}
+
+fn get_stats_window_secs() -> u64 {
+    env::var("STATS_WINDOW_SECS")
+        .ok()
+        .and_then(|v| v.parse::<u64>().ok())
+        .unwrap_or(86400)
+}
+
+#[derive(Debug, Deserialize)]
+pub struct RouteStatsQuery {
+    window_secs: Option<u64>,
+}
+
+#[debug_handler]
+pub async fn route_stats(
+    State(state): State<AppState>,
+    Query(query): Query<RouteStatsQuery>,
+) -> impl IntoResponse {
+    let window_secs = query.window_secs.unwrap_or_else(get_stats_window_secs);
+    let stats = state
+        .ledger
+        .route_stats(window_secs.saturating_mul(1000))
+        .await;
+    api_ok(json!({
+        "window_secs": window_secs,
+        "routes": stats,
+    }))
+}
+
+// records read from the ledger per chunk of the csv export
+const TRADES_EXPORT_BATCH: usize = 500;
+
+#[debug_handler]
+pub async fn pnl(
+    State(state): State<AppState>,
+    Query(filter): Query<TradeFilter>,
+) -> impl IntoResponse {
+    if let Err(err) = filter.validate() {
+        return api_error(err);
+    }
+    api_ok(state.ledger.pnl(&filter).await)
+}
+
+#[debug_handler]
+pub async fn export_trades(
+    State(state): State<AppState>,
+    Query(filter): Query<TradeFilter>,
+) -> impl IntoResponse {
+    if let Err(err) = filter.validate() {
+        return api_error(err);
+    }
+    // one chunk per page of the ledger, the cursor is the last exported id, `None` once done
+    let rows = stream::unfold(Some(0), move |cursor| {
+        let state = state.clone();
+        let filter = filter.clone();
+        async move {
+            let after = cursor?;
+            let records = state
+                .ledger
+                .page(after, TRADES_EXPORT_BATCH, |r| filter.matches(r))
+                .await;
+            let last = records.last()?.id;
+            let mut chunk = String::new();
+            for record in &records {
+                let decimals = match Pubkey::from_str(&record.mint) {
+                    Ok(mint) => state.decimals_cache.get(&state.client, &mint).await.ok(),
+                    Err(_) => None,
+                };
+                chunk.push_str(&ledger::trade_csv_row(record, decimals));
+            }
+            let next = (records.len() == TRADES_EXPORT_BATCH).then_some(last);
+            Some((Ok::<_, Infallible>(chunk), next))
+        }
+    });
+    let csv_header =
+        stream::once(async { Ok::<_, Infallible>(ledger::TRADES_CSV_HEADER.to_string()) });
+    (
+        StatusCode::OK,
+        [
+            (header::CONTENT_TYPE, "text/csv; charset=utf-8".to_string()),
+            (
+                header::CONTENT_DISPOSITION,
+                format!("attachment; filename=\"trades-{}.csv\"", now_millis()),
+            ),
+        ],
+        Body::from_stream(csv_header.chain(rows)),
+    )
+        .into_response()
+}
+
+#[debug_handler]
+pub async fn tx_status(
+    State(state): State<AppState>,
+    Path(signature): Path<String>,
+) -> impl IntoResponse {
+    match tx::get_tx_status(&state.client_blocking, &state.tx_statuses, &signature).await {
+        Ok(status) => api_ok(status),
+        Err(err) => {
+            warn!("tx status {} err: {:#?}", signature, err);
+            api_error(err)
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct BumpTx {
+    /// micro-lamports, twice the previous price if unset
+    unit_price: Option<u64>,
+    /// send as a jito bundle with a tip, as the original if unset
+    jito: Option<bool>,
+}
+
+impl JsonBody for BumpTx {}
+
+#[debug_handler]
+pub async fn bump_tx(
+    State(state): State<AppState>,
+    Path(signature): Path<String>,
+    ApiJson(input): ApiJson<BumpTx>,
+) -> impl IntoResponse {
+    match tx::bump(
+        &state.client_blocking,
+        &state.wallet(),
+        &state.sent_transactions,
+        &signature,
+        input.unit_price,
+        input.jito,
+    )
+    .await
+    {
+        Ok(outcome) => api_ok(outcome),
+        Err(err) => {
+            warn!("bump tx {} err: {:#?}", signature, err);
+            api_error(err)
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct WrapSol {
+    lamports: u64,
+}
+
+impl JsonBody for WrapSol {}
+
+#[debug_handler]
+pub async fn wrap_sol(
+    State(state): State<AppState>,
+    ApiJson(input): ApiJson<WrapSol>,
+) -> impl IntoResponse {
+    match token::wrap_sol(
+        &state.client,
+        &state.client_blocking,
+        &state.wallet(),
+        input.lamports,
+    )
+    .await
+    {
+        Ok(result) => api_ok(result),
+        Err(err) => {
+            warn!("wrap sol err: {:#?}", err);
+            api_error(err)
         }
     }
 }
+
+#[debug_handler]
+pub async fn unwrap_sol(State(state): State<AppState>) -> impl IntoResponse {
+    match token::unwrap_sol(&state.client, &state.client_blocking, &state.wallet()).await {
+        Ok(result) => api_ok(result),
+        Err(err) => {
+            warn!("unwrap sol err: {:#?}", err);
+            api_error(err)
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CreateToken {
+    name: String,
+    symbol: String,
+    #[serde(default)]
+    description: String,
+    image_uri: Option<String>,
+    /// skip the upload and use an already hosted metadata json
+    metadata_uri: Option<String>,
+    twitter: Option<String>,
+    telegram: Option<String>,
+    website: Option<String>,
+    /// sol spent on the initial dev buy
+    dev_buy: Option<f64>,
+    slippage: Option<u64>,
+    slippage_mode: Option<SlippageMode>,
+    jito: Option<bool>,
+    confirm_large: Option<bool>,
+}
+
+impl JsonBody for CreateToken {}
+
+#[debug_handler]
+pub async fn create_pump_token(
+    State(state): State<AppState>,
+    ApiJson(input): ApiJson<CreateToken>,
+) -> impl IntoResponse {
+    info!("{:?}", input);
+    let dev_buy = input.dev_buy.unwrap_or(0.0);
+    let result = async {
+        if input.name.is_empty() || input.symbol.is_empty() {
+            return Err(
+                ApiError::new(ErrorCode::InvalidInput, "name and symbol are required").into(),
+            );
+        }
+        if !dev_buy.is_finite() || dev_buy < 0.0 {
+            return Err(
+                ApiError::new(ErrorCode::InvalidInput, "dev_buy must not be negative").into(),
+            );
+        }
+        if dev_buy > 0.0 {
+            state.breaker.check()?;
+            if state.is_sell_only() {
+                return Err(ApiError::new(
+                    ErrorCode::BuysDisabled,
+                    "sell only mode is enabled, buys are rejected",
+                )
+                .into());
+            }
+            swap::check_spend_limit(dev_buy, input.confirm_large.unwrap_or(false))?;
+        }
+        let slippage = Slippage::new(
+            input.slippage_mode.unwrap_or_default(),
+            input.slippage.unwrap_or_else(swap::get_default_slippage),
+        );
+        if let Slippage::Fixed(slippage) = slippage {
+            if slippage >= 100 {
+                return Err(
+                    ApiError::new(ErrorCode::InvalidInput, "slippage must be below 100%").into(),
+                );
+            }
+        }
+        let use_jito = input.jito.unwrap_or(false);
+        if use_jito {
+            jito::check_network()?;
+        }
+        let metadata_uri = match (input.metadata_uri, input.image_uri) {
+            (Some(metadata_uri), _) => metadata_uri,
+            (None, Some(image_uri)) => {
+                upload_metadata(&TokenMetadata {
+                    name: input.name.clone(),
+                    symbol: input.symbol.clone(),
+                    description: input.description,
+                    image_uri,
+                    twitter: input.twitter,
+                    telegram: input.telegram,
+                    website: input.website,
+                })
+                .await?
+            }
+            (None, None) => {
+                return Err(ApiError::new(
+                    ErrorCode::InvalidInput,
+                    "one of image_uri or metadata_uri is required",
+                )
+                .into())
+            }
+        };
+        let mut swapx = Pump::new(state.client.clone(), state.wallet());
+        swapx.with_blocking_client(state.client_blocking.clone());
+        let result = swapx
+            .create(
+                &input.name,
+                &input.symbol,
+                &metadata_uri,
+                dev_buy,
+                slippage,
+                use_jito,
+            )
+            .await;
+        // only a dev buy is a trade
+        if dev_buy > 0.0 {
+            state.breaker.record(&result);
+        }
+        result
+    }
+    .await;
+    match result {
+        Ok(outcome) => api_ok(outcome),
+        Err(err) => {
+            warn!("create pump token err: {:#?}", err);
+            api_error(err)
+        }
+    }
+}
+
+#[debug_handler]
+pub async fn start_copy(
+    State(state): State<AppState>,
+    ApiJson(input): ApiJson<CopyConfig>,
+) -> impl IntoResponse {
+    match state.copy_tasks.start(&state, input).await {
+        Ok(task) => api_ok(task),
+        Err(err) => {
+            warn!("start copy err: {:#?}", err);
+            api_error(err)
+        }
+    }
+}
+
+#[debug_handler]
+pub async fn copy_tasks(State(state): State<AppState>) -> impl IntoResponse {
+    api_ok(state.copy_tasks.list().await)
+}
+
+#[debug_handler]
+pub async fn stop_copy(
+    State(state): State<AppState>,
+    Path(target): Path<String>,
+) -> impl IntoResponse {
+    match state.copy_tasks.stop(&target).await {
+        Ok(task) => api_ok(task),
+        Err(err) => api_error(err),
+    }
+}
+
+#[debug_handler]
+pub async fn start_strategy(
+    State(state): State<AppState>,
+    ApiJson(input): ApiJson<StrategyConfig>,
+) -> impl IntoResponse {
+    match state.strategies.start(&state, input).await {
+        Ok(strategy) => api_ok(strategy),
+        Err(err) => {
+            warn!("start strategy err: {:#?}", err);
+            api_error(err)
+        }
+    }
+}
+
+#[debug_handler]
+pub async fn strategies(State(state): State<AppState>) -> impl IntoResponse {
+    api_ok(state.strategies.list().await)
+}
+
+#[debug_handler]
+pub async fn get_strategy(
+    State(state): State<AppState>,
+    Path(mint): Path<String>,
+) -> impl IntoResponse {
+    match state.strategies.get(&mint).await {
+        Ok(strategy) => api_ok(strategy),
+        Err(err) => api_error(err),
+    }
+}
+
+#[debug_handler]
+pub async fn stop_strategy(
+    State(state): State<AppState>,
+    Path(mint): Path<String>,
+) -> impl IntoResponse {
+    match state.strategies.stop(&mint).await {
+        Ok(strategy) => api_ok(strategy),
+        Err(err) => api_error(err),
+    }
+}
+
+#[debug_handler]
+pub async fn start_dca(
+    State(state): State<AppState>,
+    ApiJson(input): ApiJson<DcaConfig>,
+) -> impl IntoResponse {
+    match state.dca.start(&state, input).await {
+        Ok(dca) => api_ok(dca),
+        Err(err) => {
+            warn!("start dca err: {:#?}", err);
+            api_error(err)
+        }
+    }
+}
+
+#[debug_handler]
+pub async fn dca_schedules(State(state): State<AppState>) -> impl IntoResponse {
+    api_ok(state.dca.list().await)
+}
+
+#[debug_handler]
+pub async fn get_dca(State(state): State<AppState>, Path(id): Path<u64>) -> impl IntoResponse {
+    match state.dca.get(id).await {
+        Ok(dca) => api_ok(dca),
+        Err(err) => api_error(err),
+    }
+}
+
+#[debug_handler]
+pub async fn stop_dca(State(state): State<AppState>, Path(id): Path<u64>) -> impl IntoResponse {
+    match state.dca.stop(id).await {
+        Ok(dca) => api_ok(dca),
+        Err(err) => api_error(err),
+    }
+}
@@ -0,0 +1,48 @@
+use std::env;
+
+use axum::{extract::Request, http::header, middleware::Next, response::Response};
+use tracing::warn;
+
+use crate::{
+    error::{ApiError, ErrorCode},
+    helper::api_error,
+};
+
+// admin endpoints are disabled unless ADMIN_TOKEN is configured
+fn get_admin_token() -> Option<String> {
+    env::var("ADMIN_TOKEN")
+        .ok()
+        .filter(|token| !token.is_empty())
+}
+
+// compare without short-circuiting on the first differing byte
+fn token_eq(a: &[u8], b: &[u8]) -> bool {
+    if a.len() != b.len() {
+        return false;
+    }
+    a.iter().zip(b).fold(0u8, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+/// Require `Authorization: Bearer <ADMIN_TOKEN>` on the wrapped routes
+pub async fn require_admin_token(req: Request, next: Next) -> Response {
+    let Some(admin_token) = get_admin_token() else {
+        return api_error(ApiError::new(
+            ErrorCode::Forbidden,
+            "admin api is disabled, set ADMIN_TOKEN to enable it",
+        ));
+    };
+
+    let provided = req
+        .headers()
+        .get(header::AUTHORIZATION)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.strip_prefix("Bearer "));
+
+    match provided {
+        Some(token) if token_eq(token.as_bytes(), admin_token.as_bytes()) => next.run(req).await,
+        _ => {
+            warn!("rejected unauthorized request to {}", req.uri());
+            api_error(ApiError::new(ErrorCode::Unauthorized, "unauthorized"))
+        }
+    }
+}
@@ -0,0 +1,250 @@
+use std::{collections::VecDeque, env, sync::Mutex};
+
+use anyhow::{anyhow, Result};
+use serde::Serialize;
+use tracing::{info, warn};
+
+use crate::{
+    error::{ApiError, ErrorCode},
+    ledger::now_millis,
+};
+
+// consecutive failed swaps that pause trading, 0 disables the breaker
+fn get_breaker_failures() -> usize {
+    env::var("BREAKER_FAILURES")
+        .ok()
+        .and_then(|v| v.parse::<usize>().ok())
+        .unwrap_or(5)
+}
+
+fn get_breaker_window_secs() -> u64 {
+    env::var("BREAKER_WINDOW_SECS")
+        .ok()
+        .and_then(|v| v.parse::<u64>().ok())
+        .unwrap_or(300)
+}
+
+fn get_breaker_cooldown_secs() -> u64 {
+    env::var("BREAKER_COOLDOWN_SECS")
+        .ok()
+        .and_then(|v| v.parse::<u64>().ok())
+        .unwrap_or(600)
+}
+
+/// Failures that reached the rpc or the chain, rejections of the request itself
+/// (bad input, balance, filters, pool checks) do not count
+pub fn counts_as_failure(code: ErrorCode) -> bool {
+    !matches!(
+        code,
+        ErrorCode::InvalidInput
+            | ErrorCode::Unauthorized
+            | ErrorCode::Forbidden
+            | ErrorCode::BuysDisabled
+            | ErrorCode::MintBlocked
+            | ErrorCode::InsufficientFunds
+            | ErrorCode::SpendLimitExceeded
+            | ErrorCode::InsufficientLiquidity
+            | ErrorCode::NoRoute
+            | ErrorCode::PoolPaused
+            | ErrorCode::PoolNotFound
+            | ErrorCode::NotFound
+            | ErrorCode::IdempotencyKeyInUse
+            | ErrorCode::TradingPaused
+    )
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct BreakerStatus {
+    pub tripped: bool,
+    /// unix timestamps in milliseconds
+    pub tripped_at: Option<u64>,
+    pub resumes_at: Option<u64>,
+    /// consecutive failures within the window
+    pub failures: usize,
+    pub last_error: Option<String>,
+    pub max_failures: usize,
+    pub window_secs: u64,
+    pub cooldown_secs: u64,
+}
+
+#[derive(Debug, Default)]
+struct BreakerState {
+    // timestamps of the consecutive failures, oldest first
+    failures: VecDeque<u64>,
+    tripped_at: Option<u64>,
+    last_error: Option<String>,
+}
+
+/// Pauses swaps after `max_failures` consecutive failures within `window_ms`, until
+/// `cooldown_ms` elapsed or `resume` is called, a successful swap resets the count
+pub struct CircuitBreaker {
+    state: Mutex<BreakerState>,
+    max_failures: usize,
+    window_ms: u64,
+    cooldown_ms: u64,
+}
+
+impl Default for CircuitBreaker {
+    fn default() -> Self {
+        Self::new(
+            get_breaker_failures(),
+            get_breaker_window_secs() * 1000,
+            get_breaker_cooldown_secs() * 1000,
+        )
+    }
+}
+
+impl CircuitBreaker {
+    pub fn new(max_failures: usize, window_ms: u64, cooldown_ms: u64) -> Self {
+        Self {
+            state: Mutex::new(BreakerState::default()),
+            max_failures,
+            window_ms,
+            cooldown_ms,
+        }
+    }
+
+    /// `TradingPaused` while tripped
+    pub fn check(&self) -> Result<()> {
+        self.check_at(now_millis())
+    }
+
+    fn check_at(&self, now: u64) -> Result<()> {
+        let mut state = self.state.lock().unwrap();
+        let Some(tripped_at) = state.tripped_at else {
+            return Ok(());
+        };
+        let resumes_at = tripped_at + self.cooldown_ms;
+        if now >= resumes_at {
+            info!("circuit breaker cooldown elapsed, trading resumes");
+            *state = BreakerState::default();
+            return Ok(());
+        }
+        Err(ApiError::new(
+            ErrorCode::TradingPaused,
+            format!(
+                "trading paused after {} consecutive failed swaps, resumes at {} or on POST /api/resume, last error: {}",
+                self.max_failures,
+                resumes_at,
+                state.last_error.as_deref().unwrap_or_default()
+            ),
+        )
+        .with_data(serde_json::json!({ "resumes_at": resumes_at }))
+        .into())
+    }
+
+    /// Count the result of a swap
+    pub fn record<T>(&self, result: &Result<T>) {
+        match result {
+            Ok(_) => self.record_success(),
+            Err(err) if counts_as_failure(ApiError::from(err).code) => {
+                self.record_failure_at(now_millis(), err.to_string())
+            }
+            Err(_) => {}
+        }
+    }
+
+    pub fn record_success(&self) {
+        let mut state = self.state.lock().unwrap();
+        state.failures.clear();
+    }
+
+    fn record_failure_at(&self, now: u64, error: String) {
+        if self.max_failures == 0 {
+            return;
+        }
+        let mut state = self.state.lock().unwrap();
+        state.failures.push_back(now);
+        while state
+            .failures
+            .front()
+            .is_some_and(|at| now.saturating_sub(*at) > self.window_ms)
+        {
+            state.failures.pop_front();
+        }
+        state.last_error = Some(error);
+        if state.tripped_at.is_none() && state.failures.len() >= self.max_failures {
+            warn!(
+                "circuit breaker tripped after {} failed swaps, last error: {}",
+                state.failures.len(),
+                state.last_error.as_deref().unwrap_or_default()
+            );
+            state.tripped_at = Some(now);
+        }
+    }
+
+    /// Clear a tripped breaker, returns whether it was tripped
+    pub fn resume(&self) -> bool {
+        let mut state = self.state.lock().unwrap();
+        let tripped = state.tripped_at.is_some();
+        *state = BreakerState::default();
+        if tripped {
+            info!("circuit breaker cleared, trading resumes");
+        }
+        tripped
+    }
+
+    pub fn status(&self) -> BreakerStatus {
+        // an elapsed cooldown clears the breaker
+        let _ = self.check();
+        let state = self.state.lock().unwrap();
+        BreakerStatus {
+            tripped: state.tripped_at.is_some(),
+            tripped_at: state.tripped_at,
+            resumes_at: state.tripped_at.map(|at| at + self.cooldown_ms),
+            failures: state.failures.len(),
+            last_error: state.last_error.clone(),
+            max_failures: self.max_failures,
+            window_secs: self.window_ms / 1000,
+            cooldown_secs: self.cooldown_ms / 1000,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_circuit_breaker() {
+        let breaker = CircuitBreaker::new(3, 60_000, 600_000);
+        breaker.record_failure_at(0, "rpc down".to_string());
+        breaker.record_failure_at(1_000, "rpc down".to_string());
+        // a success resets the count
+        breaker.record_success();
+        breaker.record_failure_at(2_000, "rpc down".to_string());
+        breaker.record_failure_at(3_000, "rpc down".to_string());
+        assert!(breaker.check_at(3_000).is_ok());
+
+        // failures outside the window do not add up
+        breaker.record_failure_at(100_000, "rpc down".to_string());
+        assert!(breaker.check_at(100_000).is_ok());
+        breaker.record_failure_at(101_000, "rpc down".to_string());
+        breaker.record_failure_at(102_000, "rpc down".to_string());
+        let err = breaker.check_at(102_000).unwrap_err();
+        assert_eq!(ApiError::from(&err).code, ErrorCode::TradingPaused);
+
+        // paused until the cooldown elapses
+        assert!(breaker.check_at(701_999).is_err());
+        assert!(breaker.check_at(702_000).is_ok());
+        assert_eq!(breaker.status().failures, 0);
+
+        for at in 0..3 {
+            breaker.record_failure_at(at, "rpc down".to_string());
+        }
+        assert!(breaker.check_at(10).is_err());
+        assert!(breaker.resume());
+        assert!(breaker.check_at(10).is_ok());
+        assert!(!breaker.resume());
+
+        let disabled = CircuitBreaker::new(0, 60_000, 600_000);
+        for at in 0..10 {
+            disabled.record_failure_at(at, "rpc down".to_string());
+        }
+        assert!(disabled.check_at(10).is_ok());
+
+        assert!(counts_as_failure(ErrorCode::RpcError));
+        assert!(counts_as_failure(ErrorCode::SlippageExceeded));
+        assert!(!counts_as_failure(ErrorCode::InsufficientFunds));
+    }
+}
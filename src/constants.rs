@@ -1,6 +1,85 @@
+use std::env;
+
+use anyhow::Result;
+use serde::Serialize;
+
+use crate::error::{ApiError, ErrorCode};
+
 pub struct Symbol;
 
 impl Symbol {
     pub const SOLANA: &'static str = "solana";
     pub const WSOL_TOKEN: &'static str = "So11111111111111111111111111111111111111112";
 }
+
+/// Cluster the service trades on, WSOL and the pump.fun, PumpSwap and Meteora programs
+/// share their addresses across clusters, raydium does not
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Network {
+    Mainnet,
+    Devnet,
+}
+
+impl Network {
+    pub fn parse(network: &str) -> Result<Self> {
+        match network {
+            "mainnet" | "mainnet-beta" => Ok(Network::Mainnet),
+            "devnet" => Ok(Network::Devnet),
+            other => Err(ApiError::new(
+                ErrorCode::InvalidInput,
+                format!("unknown NETWORK {}, expected mainnet or devnet", other),
+            )
+            .into()),
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Network::Mainnet => "mainnet",
+            Network::Devnet => "devnet",
+        }
+    }
+
+    /// used when RPC_ENDPOINTS is not set
+    pub fn default_rpc_url(&self) -> &'static str {
+        match self {
+            Network::Mainnet => "https://api.mainnet-beta.solana.com",
+            Network::Devnet => "https://api.devnet.solana.com",
+        }
+    }
+
+    /// used when RPC_WEBSOCKET_ENDPOINTS is not set
+    pub fn default_ws_url(&self) -> &'static str {
+        match self {
+            Network::Mainnet => "wss://api.mainnet-beta.solana.com",
+            Network::Devnet => "wss://api.devnet.solana.com",
+        }
+    }
+
+    /// jito block engines only run on mainnet
+    pub fn supports_jito(&self) -> bool {
+        *self == Network::Mainnet
+    }
+}
+
+/// NETWORK, `mainnet` when unset, panics on an unknown value so a typo never trades on mainnet
+pub fn get_network() -> Network {
+    let network = env::var("NETWORK").unwrap_or("mainnet".to_string());
+    Network::parse(&network).unwrap_or_else(|err| panic!("{}", err))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_network_parse() {
+        assert_eq!(Network::parse("mainnet").unwrap(), Network::Mainnet);
+        assert_eq!(Network::parse("mainnet-beta").unwrap(), Network::Mainnet);
+        assert_eq!(Network::parse("devnet").unwrap(), Network::Devnet);
+        let err = Network::parse("devent").unwrap_err();
+        assert_eq!(ApiError::from(&err).code, ErrorCode::InvalidInput);
+        assert!(!Network::Devnet.supports_jito());
+    }
+}
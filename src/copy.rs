@@ -0,0 +1,405 @@
+use std::{
+    collections::{BTreeSet, HashMap},
+    str::FromStr,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+use anyhow::Result;
+use futures_util::StreamExt;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use solana_client::{
+    nonblocking::pubsub_client::PubsubClient,
+    rpc_config::{RpcTransactionLogsConfig, RpcTransactionLogsFilter},
+};
+use solana_sdk::{
+    commitment_config::CommitmentConfig, native_token::lamports_to_sol, pubkey::Pubkey,
+    signer::Signer,
+};
+use solana_transaction_status::{option_serializer::OptionSerializer, UiTransactionTokenBalance};
+use tokio::{sync::Mutex, task::JoinHandle};
+use tracing::{debug, info, warn};
+
+use crate::{
+    api::AppState,
+    error::{ApiError, ErrorCode},
+    extract::JsonBody,
+    get_random_ws_url,
+    ledger::now_millis,
+    pump, raydium,
+    swap::{self, SwapDirection, SwapInType, SwapParams},
+    telemetry, token, tx,
+};
+
+const RECONNECT_DELAY: Duration = Duration::from_secs(3);
+
+fn default_scale() -> f64 {
+    1.0
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CopyConfig {
+    /// wallet whose swaps are mirrored
+    pub target: String,
+    /// multiplier on the sol the target spends on a buy
+    #[serde(default = "default_scale")]
+    pub scale: f64,
+    /// cap on the sol spent per mirrored buy
+    pub max_sol_per_trade: Option<f64>,
+    /// only copy these mints when not empty
+    #[serde(default)]
+    pub allowlist: BTreeSet<String>,
+    #[serde(default)]
+    pub blocklist: BTreeSet<String>,
+    pub slippage: Option<u64>,
+    #[serde(default)]
+    pub jito: bool,
+}
+
+impl JsonBody for CopyConfig {
+    fn example() -> Option<Value> {
+        Some(json!({
+            "target": "4ryBWkNzxqCVz2bYBQ6XQ9hdZtHjd9bJ5qPLeyYMwCBD",
+            "scale": 0.5,
+            "max_sol_per_trade": 0.1,
+        }))
+    }
+}
+
+#[derive(Debug, Default)]
+struct CopyStats {
+    copied: AtomicU64,
+    skipped: AtomicU64,
+    failed: AtomicU64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct CopyTaskInfo {
+    pub config: CopyConfig,
+    /// unix timestamp in milliseconds
+    pub started_at: u64,
+    pub copied: u64,
+    pub skipped: u64,
+    pub failed: u64,
+}
+
+struct CopyTask {
+    config: CopyConfig,
+    started_at: u64,
+    stats: Arc<CopyStats>,
+    handle: JoinHandle<()>,
+}
+
+impl CopyTask {
+    fn info(&self) -> CopyTaskInfo {
+        CopyTaskInfo {
+            config: self.config.clone(),
+            started_at: self.started_at,
+            copied: self.stats.copied.load(Ordering::Relaxed),
+            skipped: self.stats.skipped.load(Ordering::Relaxed),
+            failed: self.stats.failed.load(Ordering::Relaxed),
+        }
+    }
+}
+
+/// Running copy trading tasks keyed by target wallet
+#[derive(Default)]
+pub struct CopyTasks {
+    tasks: Mutex<HashMap<String, CopyTask>>,
+}
+
+impl CopyTasks {
+    pub async fn start(&self, state: &AppState, config: CopyConfig) -> Result<CopyTaskInfo> {
+        let target = Pubkey::from_str(&config.target).map_err(|e| {
+            ApiError::new(
+                ErrorCode::InvalidInput,
+                format!("failed to parse target pubkey: {}", e),
+            )
+        })?;
+        if target == state.wallet().pubkey() {
+            return Err(
+                ApiError::new(ErrorCode::InvalidInput, "target is the configured wallet").into(),
+            );
+        }
+        if !config.scale.is_finite() || config.scale <= 0.0 {
+            return Err(
+                ApiError::new(ErrorCode::InvalidInput, "scale must be greater than 0").into(),
+            );
+        }
+        let mut tasks = self.tasks.lock().await;
+        if tasks.contains_key(&config.target) {
+            return Err(ApiError::new(
+                ErrorCode::InvalidInput,
+                format!("already copying {}, stop it first", config.target),
+            )
+            .into());
+        }
+        let stats = Arc::new(CopyStats::default());
+        let handle = tokio::spawn(run(state.clone(), config.clone(), stats.clone()));
+        let task = CopyTask {
+            config: config.clone(),
+            started_at: now_millis(),
+            stats,
+            handle,
+        };
+        let info = task.info();
+        tasks.insert(config.target, task);
+        Ok(info)
+    }
+
+    pub async fn stop(&self, target: &str) -> Result<CopyTaskInfo> {
+        let task =
+            self.tasks.lock().await.remove(target).ok_or_else(|| {
+                ApiError::new(ErrorCode::NotFound, format!("not copying {}", target))
+            })?;
+        task.handle.abort();
+        info!("copy trading stopped: {}", target);
+        Ok(task.info())
+    }
+
+    pub async fn list(&self) -> Vec<CopyTaskInfo> {
+        self.tasks
+            .lock()
+            .await
+            .values()
+            .map(CopyTask::info)
+            .collect()
+    }
+}
+
+async fn run(state: AppState, config: CopyConfig, stats: Arc<CopyStats>) {
+    let _guard = telemetry::MonitorGuard::start();
+    info!("copy trading started: {:?}", config);
+    loop {
+        if let Err(err) = subscribe(&state, &config, &stats).await {
+            warn!("copy trading {} subscription err: {}", config.target, err);
+        }
+        tokio::time::sleep(RECONNECT_DELAY).await;
+    }
+}
+
+// returns when the websocket closes
+async fn subscribe(state: &AppState, config: &CopyConfig, stats: &Arc<CopyStats>) -> Result<()> {
+    let client = PubsubClient::new(&get_random_ws_url()?).await?;
+    let (mut logs, unsubscribe) = client
+        .logs_subscribe(
+            RpcTransactionLogsFilter::Mentions(vec![config.target.clone()]),
+            RpcTransactionLogsConfig {
+                commitment: Some(CommitmentConfig::confirmed()),
+            },
+        )
+        .await?;
+    info!("copy trading subscribed to {}", config.target);
+
+    let swap_programs = [
+        format!("Program {} invoke", pump::PUMP_PROGRAM),
+        format!("Program {} invoke", raydium::amm_program()),
+    ];
+    while let Some(response) = logs.next().await {
+        let notification = response.value;
+        if notification.err.is_some() {
+            continue;
+        }
+        let is_swap = notification
+            .logs
+            .iter()
+            .any(|log| swap_programs.iter().any(|program| log.starts_with(program)));
+        if !is_swap {
+            continue;
+        }
+        tokio::spawn(mirror_and_count(
+            state.clone(),
+            config.clone(),
+            stats.clone(),
+            notification.signature,
+        ));
+    }
+    unsubscribe().await;
+    Ok(())
+}
+
+async fn mirror_and_count(
+    state: AppState,
+    config: CopyConfig,
+    stats: Arc<CopyStats>,
+    signature: String,
+) {
+    match mirror(&state, &config, &signature).await {
+        Ok(true) => {
+            stats.copied.fetch_add(1, Ordering::Relaxed);
+        }
+        Ok(false) => {
+            stats.skipped.fetch_add(1, Ordering::Relaxed);
+        }
+        Err(err) => {
+            stats.failed.fetch_add(1, Ordering::Relaxed);
+            warn!(
+                "copy of {} from {} failed: {}",
+                signature, config.target, err
+            );
+        }
+    }
+}
+
+// Ok(false) when the transaction is skipped
+async fn mirror(state: &AppState, config: &CopyConfig, signature: &str) -> Result<bool> {
+    let target = Pubkey::from_str(&config.target)?;
+    let meta = tx::get_transaction_meta(&state.client_blocking, signature).await?;
+    let Some(mint) = traded_mint(
+        &meta.pre_token_balances,
+        &meta.post_token_balances,
+        &config.target,
+    ) else {
+        debug!(
+            "copy: no token traded by {} in {}",
+            config.target, signature
+        );
+        return Ok(false);
+    };
+    if (!config.allowlist.is_empty() && !config.allowlist.contains(&mint))
+        || config.blocklist.contains(&mint)
+    {
+        info!("copy: {} filtered out, skip {}", mint, signature);
+        return Ok(false);
+    }
+
+    let mint_pubkey = Pubkey::from_str(&mint)?;
+    let changes = tx::balance_changes(&meta, &target, &mint_pubkey);
+    let (direction, in_type, amount_in) = if changes.token_delta > 0 {
+        // wsol held in an account across the swap does not show in the native balance
+        let wsol = tx::balance_changes(&meta, &target, &spl_token::native_mint::ID);
+        let spent = -(changes.trade_sol_delta() + wsol.token_delta);
+        if spent <= 0 {
+            return Ok(false);
+        }
+        let mut amount = lamports_to_sol(spent as u64) * config.scale;
+        if let Some(max_sol) = config.max_sol_per_trade {
+            amount = amount.min(max_sol);
+        }
+        (SwapDirection::Buy, SwapInType::Qty, amount)
+    } else {
+        // sell the same share of our position as the target sold of theirs
+        let pre_amount = tx::owner_token_amount(&meta.pre_token_balances, &config.target, &mint)
+            .unwrap_or_default();
+        if pre_amount <= 0 {
+            return Ok(false);
+        }
+        let sold = (-changes.token_delta) as f64 / pre_amount as f64;
+        let held = token::token_account(&state.client, &state.wallet().pubkey(), mint_pubkey)
+            .await
+            .map_or(0.0, |account| account.ui_amount);
+        if held <= 0.0 {
+            debug!("copy: nothing to sell of {}, skip {}", mint, signature);
+            return Ok(false);
+        }
+        (SwapDirection::Sell, SwapInType::Pct, sold.min(1.0))
+    };
+    if amount_in <= 0.0 {
+        return Ok(false);
+    }
+    info!(
+        "copy {} {} of {} from {}, amount_in: {} ({:?})",
+        direction.as_str(),
+        mint,
+        config.target,
+        signature,
+        amount_in,
+        in_type
+    );
+    let params = SwapParams::market(
+        mint,
+        direction,
+        in_type,
+        amount_in,
+        config.slippage,
+        config.jito,
+    );
+    swap::swap_with_timeout(state.clone(), params).await?;
+    Ok(true)
+}
+
+fn owner_balances<'a>(
+    balances: &'a OptionSerializer<Vec<UiTransactionTokenBalance>>,
+    owner: &'a str,
+) -> impl Iterator<Item = &'a UiTransactionTokenBalance> {
+    let balances = match balances {
+        OptionSerializer::Some(balances) => balances.as_slice(),
+        _ => &[],
+    };
+    balances
+        .iter()
+        .filter(move |balance| matches!(&balance.owner, OptionSerializer::Some(o) if o == owner))
+}
+
+/// The non wsol mint whose balance of `owner` moved the most
+fn traded_mint(
+    pre_balances: &OptionSerializer<Vec<UiTransactionTokenBalance>>,
+    post_balances: &OptionSerializer<Vec<UiTransactionTokenBalance>>,
+    owner: &str,
+) -> Option<String> {
+    let wsol = spl_token::native_mint::ID.to_string();
+    let mut deltas: HashMap<&str, i128> = HashMap::new();
+    for (balances, sign) in [(pre_balances, -1), (post_balances, 1)] {
+        for balance in owner_balances(balances, owner) {
+            if balance.mint == wsol {
+                continue;
+            }
+            let amount = i128::from_str(&balance.ui_token_amount.amount).unwrap_or_default();
+            *deltas.entry(balance.mint.as_str()).or_default() += sign * amount;
+        }
+    }
+    deltas
+        .into_iter()
+        .filter(|(_, delta)| *delta != 0)
+        .max_by_key(|(_, delta)| delta.abs())
+        .map(|(mint, _)| mint.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use solana_account_decoder::parse_token::UiTokenAmount;
+
+    use super::*;
+
+    fn balance(mint: &str, owner: &str, amount: u64) -> UiTransactionTokenBalance {
+        UiTransactionTokenBalance {
+            account_index: 1,
+            mint: mint.to_string(),
+            ui_token_amount: UiTokenAmount {
+                ui_amount: None,
+                decimals: 6,
+                amount: amount.to_string(),
+                ui_amount_string: String::new(),
+            },
+            owner: OptionSerializer::Some(owner.to_string()),
+            program_id: OptionSerializer::None,
+        }
+    }
+
+    #[test]
+    fn test_traded_mint() {
+        let owner = Pubkey::new_unique().to_string();
+        let other = Pubkey::new_unique().to_string();
+        let wsol = spl_token::native_mint::ID.to_string();
+        let (mint, unchanged) = ("MintA", "MintB");
+        let pre = OptionSerializer::Some(vec![
+            balance(&wsol, &owner, 5_000),
+            balance(unchanged, &owner, 10),
+        ]);
+        // the account of a freshly bought mint only shows in the post balances
+        let post = OptionSerializer::Some(vec![
+            balance(&wsol, &owner, 0),
+            balance(unchanged, &owner, 10),
+            balance(mint, &owner, 1_000),
+            balance("MintC", &other, 9_999),
+        ]);
+        assert_eq!(traded_mint(&pre, &post, &owner), Some(mint.to_string()));
+        assert_eq!(traded_mint(&post, &pre, &owner), Some(mint.to_string()));
+        assert_eq!(traded_mint(&pre, &pre, &owner), None);
+        assert_eq!(traded_mint(&pre, &OptionSerializer::None, &other), None);
+    }
+}
@@ -0,0 +1,113 @@
+use std::env;
+
+use anyhow::{anyhow, Result};
+use axum::http::{header, HeaderName, HeaderValue, Method};
+use reqwest::Url;
+use tower_http::cors::{AllowOrigin, CorsLayer};
+use tracing::{info, warn};
+
+use crate::{idempotency, request_id};
+
+// any origin, for local development only
+fn get_cors_dev_mode() -> bool {
+    env::var("CORS_DEV_MODE").ok() == Some("true".to_string())
+}
+
+/// The CORS policy from CORS_ALLOWED_ORIGINS, fails on an origin that does not parse
+///
+/// Listed origins are reflected back with credentials allowed, CORS_DEV_MODE=true allows any
+/// origin without credentials, with neither no cross origin request is allowed.
+pub fn layer() -> Result<CorsLayer> {
+    let origins = parse_origins(&env::var("CORS_ALLOWED_ORIGINS").unwrap_or_default())?;
+    let layer = CorsLayer::new()
+        .expose_headers([
+            HeaderName::from_static(request_id::REQUEST_ID_HEADER),
+            HeaderName::from_static(idempotency::REPLAYED_HEADER),
+        ])
+        .allow_methods([
+            Method::GET,
+            Method::POST,
+            Method::PUT,
+            Method::OPTIONS,
+            Method::DELETE,
+        ])
+        .allow_headers([
+            header::CONTENT_TYPE,
+            header::AUTHORIZATION,
+            HeaderName::from_static(request_id::REQUEST_ID_HEADER),
+            HeaderName::from_static(idempotency::IDEMPOTENCY_KEY_HEADER),
+        ]);
+    if get_cors_dev_mode() {
+        if !origins.is_empty() {
+            warn!("CORS_DEV_MODE is on, CORS_ALLOWED_ORIGINS is ignored");
+        }
+        warn!("CORS_DEV_MODE is on, any origin can call the api");
+        return Ok(layer.allow_origin(AllowOrigin::any()));
+    }
+    if origins.is_empty() {
+        info!("cors: no CORS_ALLOWED_ORIGINS, cross origin requests are rejected");
+    } else {
+        info!("cors: allowed origins {:?}", origins);
+    }
+    Ok(layer
+        .allow_origin(AllowOrigin::list(origins))
+        .allow_credentials(true))
+}
+
+// comma separated `scheme://host[:port]` origins
+fn parse_origins(value: &str) -> Result<Vec<HeaderValue>> {
+    value
+        .split(',')
+        .map(str::trim)
+        .filter(|origin| !origin.is_empty())
+        .map(parse_origin)
+        .collect()
+}
+
+fn parse_origin(origin: &str) -> Result<HeaderValue> {
+    if origin == "*" {
+        return Err(anyhow!(
+            "CORS_ALLOWED_ORIGINS can't hold *, set CORS_DEV_MODE=true to allow any origin"
+        ));
+    }
+    let url = Url::parse(origin).map_err(|e| anyhow!("invalid CORS origin {}: {}", origin, e))?;
+    // browsers send the serialized origin, anything else would never match
+    let serialized = url.origin().ascii_serialization();
+    if !matches!(url.scheme(), "http" | "https") || serialized != origin.trim_end_matches('/') {
+        return Err(anyhow!(
+            "invalid CORS origin {}, expected scheme://host[:port] like {}",
+            origin,
+            serialized
+        ));
+    }
+    Ok(HeaderValue::from_str(&serialized)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_origins() {
+        let origins = parse_origins("https://app.example.com, http://localhost:3000/ ,,").unwrap();
+        assert_eq!(
+            origins,
+            vec![
+                HeaderValue::from_static("https://app.example.com"),
+                HeaderValue::from_static("http://localhost:3000"),
+            ]
+        );
+        assert!(parse_origins("").unwrap().is_empty());
+
+        for origin in [
+            "*",
+            "app.example.com",
+            "https://app.example.com/path",
+            "https://App.example.com",
+            "https://app.example.com:443",
+            "ftp://app.example.com",
+        ] {
+            assert!(parse_origin(origin).is_err(), "{}", origin);
+        }
+    }
+}
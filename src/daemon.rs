@@ -1,32 +1,68 @@
 use std::net::SocketAddr;
 
 use axum::{
-    http::{HeaderValue, Method},
-    routing::{get, post},
+    middleware,
+    routing::{delete, get, post},
     Router,
 };
-use tower_http::cors::CorsLayer;
-use tracing::info;
+use tracing::{info, warn};
 
-use crate::{api, jito};
+use crate::{api, auth, constants::get_network, cors, idempotency, jito, request_id, telemetry};
 
 pub async fn start_service(addr: &String, app_state: api::AppState) {
-    jito::init_tip_accounts().await.unwrap();
-    tokio::spawn(async {
-        jito::ws::tip_stream()
-            .await
-            .expect("Failed to get tip percentiles data");
-    });
+    let cors = cors::layer().expect("invalid CORS configuration");
+    if let Err(err) = telemetry::init() {
+        warn!("failed to install metrics recorder: {:#?}", err);
+    }
+    let network = get_network();
+    if network.supports_jito() {
+        jito::init_tip_accounts().await.unwrap();
+        tokio::spawn(async {
+            jito::ws::tip_stream()
+                .await
+                .expect("Failed to get tip percentiles data");
+        });
+    } else {
+        info!("jito disabled on {}", network.as_str());
+    }
+    tokio::spawn(idempotency::evict_periodically(
+        app_state.idempotency_keys.clone(),
+    ));
 
     let app = Router::new()
+        .route("/health", get(api::health))
+        .route("/ready", get(api::ready))
+        .route("/metrics", get(api::metrics))
+        .route("/ws/price/:token_address", get(api::price_stream))
         .nest(
             "/api",
             Router::new()
-                .route("/swap", post(api::swap))
+                .route(
+                    "/swap",
+                    post(api::swap).route_layer(middleware::from_fn_with_state(
+                        app_state.clone(),
+                        idempotency::idempotent,
+                    )),
+                )
+                .route("/swap/validate", post(api::validate_swap))
+                .route("/simulate-bundle", post(api::simulate_bundle))
+                .route("/filters", get(api::filters))
                 .route("/pool/:pool_id", get(api::get_pool))
                 .route("/coins/:mint", get(api::coins))
                 .route("/token_accounts", get(api::token_accounts))
+                .route("/token_accounts/close", post(api::close_token_accounts))
                 .route("/token_accounts/:mint", get(api::token_account))
+                .route("/token/:mint/metadata", get(api::token_metadata))
+                .route("/wallet/balance", get(api::wallet_balance))
+                .route(
+                    "/resume",
+                    post(api::resume).route_layer(middleware::from_fn(auth::require_admin_token)),
+                )
+                .route(
+                    "/wallet/load",
+                    post(api::load_wallet)
+                        .route_layer(middleware::from_fn(auth::require_admin_token)),
+                )
                 .route(
                     "/pool_info/:token_address",
                     get(api::get_pool_by_token_address),
@@ -35,21 +71,47 @@ pub async fn start_service(addr: &String, app_state: api::AppState) {
                     "/price",
                     Router::new()
                         .route("/raydium/:token_address", get(api::get_raydium_token_price))
-                        .route("/pump/:token_address", get(api::get_pump_token_price)),
+                        .route("/pump/:token_address", get(api::get_pump_token_price))
+                        .route("/meteora/:token_address", get(api::get_meteora_token_price))
+                        .route(
+                            "/pumpswap/:token_address",
+                            get(api::get_pumpswap_token_price),
+                        )
+                        .route("/batch", post(api::batch_price)),
                 )
-                .with_state(app_state),
+                .route("/copy", get(api::copy_tasks).post(api::start_copy))
+                .route("/copy/:target", delete(api::stop_copy))
+                .route(
+                    "/strategies",
+                    get(api::strategies).post(api::start_strategy),
+                )
+                .route(
+                    "/strategies/:mint",
+                    get(api::get_strategy).delete(api::stop_strategy),
+                )
+                .route("/dca", get(api::dca_schedules).post(api::start_dca))
+                .route("/dca/:id", get(api::get_dca).delete(api::stop_dca))
+                .route("/pump/create", post(api::create_pump_token))
+                .route("/stats/routes", get(api::route_stats))
+                .route("/trades/export", get(api::export_trades))
+                .route("/pnl", get(api::pnl))
+                .route("/tx/:signature", get(api::tx_status))
+                .route("/tx/:signature/bump", post(api::bump_tx))
+                .route("/wsol/wrap", post(api::wrap_sol))
+                .route("/wsol/unwrap", post(api::unwrap_sol))
+                .nest(
+                    "/admin",
+                    Router::new()
+                        .route(
+                            "/sell_only",
+                            get(api::get_sell_only).post(api::set_sell_only),
+                        )
+                        .route_layer(middleware::from_fn(auth::require_admin_token)),
+                ),
         )
-        .layer(
-            CorsLayer::new()
-                .allow_origin("*".parse::<HeaderValue>().unwrap())
-                .allow_methods([
-                    Method::GET,
-                    Method::POST,
-                    Method::PUT,
-                    Method::OPTIONS,
-                    Method::DELETE,
-                ]),
-        );
+        .with_state(app_state)
+        .layer(middleware::from_fn(request_id::assign_request_id))
+        .layer(cors);
 
     let listener = tokio::net::TcpListener::bind(addr).await.unwrap();
     info!("listening on {}", listener.local_addr().unwrap());
@@ -0,0 +1,375 @@
+use std::{
+    collections::BTreeMap,
+    str::FromStr,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+    time::Duration,
+};
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use solana_sdk::{native_token::lamports_to_sol, pubkey::Pubkey, signer::Signer};
+use spl_token::amount_to_ui_amount;
+use tokio::{sync::Mutex as AsyncMutex, task::JoinHandle, time::MissedTickBehavior};
+use tracing::{debug, info, warn};
+
+use crate::{
+    api::AppState,
+    error::{ApiError, ErrorCode},
+    extract::JsonBody,
+    ledger::now_millis,
+    swap::{self, SwapDirection, SwapInType, SwapParams, SwapResult},
+    telemetry, tx,
+};
+
+const MAX_TOTAL_BUYS: u32 = 10_000;
+// 30 days
+const MAX_INTERVAL_SECS: u64 = 30 * 24 * 3600;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DcaConfig {
+    pub mint: String,
+    /// sol spent on each buy
+    pub sol_per_buy: f64,
+    /// seconds between two buys, the first one runs right away
+    pub interval_secs: u64,
+    /// buys to run, failed ones included
+    pub total_buys: u32,
+    pub slippage: Option<u64>,
+    #[serde(default)]
+    pub jito: bool,
+    /// stop the schedule on the first failed buy instead of moving on to the next one
+    #[serde(default)]
+    pub abort_on_failure: bool,
+}
+
+impl JsonBody for DcaConfig {
+    fn example() -> Option<Value> {
+        Some(json!({
+            "mint": "EKpQGSJtjMFqKZ9KQanSqYXRcF8fBopzLHYxdM65zcjm",
+            "sol_per_buy": 0.05,
+            "interval_secs": 3600,
+            "total_buys": 24,
+            "slippage": 10,
+        }))
+    }
+}
+
+impl DcaConfig {
+    fn validate(&self) -> Result<()> {
+        Pubkey::from_str(&self.mint).map_err(|e| {
+            ApiError::new(
+                ErrorCode::InvalidInput,
+                format!("failed to parse mint pubkey: {}", e),
+            )
+        })?;
+        if !self.sol_per_buy.is_finite() || self.sol_per_buy <= 0.0 {
+            return Err(ApiError::new(
+                ErrorCode::InvalidInput,
+                "sol_per_buy must be greater than 0",
+            )
+            .into());
+        }
+        if !(1..=MAX_INTERVAL_SECS).contains(&self.interval_secs) {
+            return Err(ApiError::new(
+                ErrorCode::InvalidInput,
+                format!("interval_secs must be between 1 and {}", MAX_INTERVAL_SECS),
+            )
+            .into());
+        }
+        if !(1..=MAX_TOTAL_BUYS).contains(&self.total_buys) {
+            return Err(ApiError::new(
+                ErrorCode::InvalidInput,
+                format!("total_buys must be between 1 and {}", MAX_TOTAL_BUYS),
+            )
+            .into());
+        }
+        if self.slippage.is_some_and(|slippage| slippage >= 100) {
+            return Err(
+                ApiError::new(ErrorCode::InvalidInput, "slippage must be below 100%").into(),
+            );
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DcaStatus {
+    Running,
+    Completed,
+    /// stopped by a failed buy with `abort_on_failure`
+    Aborted,
+    /// deleted before the last buy
+    Cancelled,
+}
+
+/// One buy of a schedule, amounts are ui amounts
+#[derive(Debug, Clone, Serialize)]
+pub struct DcaLeg {
+    /// unix timestamp in milliseconds
+    pub timestamp: u64,
+    pub success: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub signature: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sol_in: Option<f64>,
+    /// filled by the confirmed transaction, the quote when it could not be read
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tokens_out: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+#[derive(Debug)]
+struct DcaState {
+    status: DcaStatus,
+    legs: Vec<DcaLeg>,
+    next_buy_at: Option<u64>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct DcaInfo {
+    pub id: u64,
+    pub config: DcaConfig,
+    pub status: DcaStatus,
+    /// unix timestamp in milliseconds
+    pub started_at: u64,
+    /// unix timestamp in milliseconds, `null` once the schedule ended
+    pub next_buy_at: Option<u64>,
+    pub buys_done: u32,
+    pub buys_failed: u32,
+    pub sol_spent: f64,
+    pub tokens_bought: f64,
+    /// sol per token over the successful buys
+    pub avg_entry_price: Option<f64>,
+    pub legs: Vec<DcaLeg>,
+}
+
+struct DcaTask {
+    config: DcaConfig,
+    started_at: u64,
+    state: Arc<Mutex<DcaState>>,
+    handle: JoinHandle<()>,
+}
+
+impl DcaTask {
+    fn info(&self, id: u64) -> DcaInfo {
+        let state = self.state.lock().unwrap();
+        let (sol_spent, tokens_bought) = totals(&state.legs);
+        DcaInfo {
+            id,
+            config: self.config.clone(),
+            status: state.status,
+            started_at: self.started_at,
+            next_buy_at: state.next_buy_at,
+            buys_done: state.legs.iter().filter(|leg| leg.success).count() as u32,
+            buys_failed: state.legs.iter().filter(|leg| !leg.success).count() as u32,
+            sol_spent,
+            tokens_bought,
+            avg_entry_price: (tokens_bought > 0.0).then(|| sol_spent / tokens_bought),
+            legs: state.legs.clone(),
+        }
+    }
+}
+
+// (sol, tokens) of the successful legs that know both amounts
+fn totals(legs: &[DcaLeg]) -> (f64, f64) {
+    legs.iter()
+        .filter(|leg| leg.success)
+        .filter_map(|leg| leg.sol_in.zip(leg.tokens_out))
+        .fold((0.0, 0.0), |(sol, tokens), (sol_in, tokens_out)| {
+            (sol + sol_in, tokens + tokens_out)
+        })
+}
+
+/// DCA schedules by id, ended ones stay until deleted
+#[derive(Default)]
+pub struct DcaSchedules {
+    tasks: AsyncMutex<BTreeMap<u64, DcaTask>>,
+    next_id: AtomicU64,
+}
+
+impl DcaSchedules {
+    pub async fn start(&self, state: &AppState, config: DcaConfig) -> Result<DcaInfo> {
+        config.validate()?;
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed) + 1;
+        let shared = Arc::new(Mutex::new(DcaState {
+            status: DcaStatus::Running,
+            legs: vec![],
+            next_buy_at: Some(now_millis()),
+        }));
+        let handle = tokio::spawn(run(state.clone(), id, config.clone(), shared.clone()));
+        let task = DcaTask {
+            config,
+            started_at: now_millis(),
+            state: shared,
+            handle,
+        };
+        let info = task.info(id);
+        self.tasks.lock().await.insert(id, task);
+        Ok(info)
+    }
+
+    /// Cancel a running schedule and forget it
+    pub async fn stop(&self, id: u64) -> Result<DcaInfo> {
+        let task =
+            self.tasks.lock().await.remove(&id).ok_or_else(|| {
+                ApiError::new(ErrorCode::NotFound, format!("no dca schedule {}", id))
+            })?;
+        task.handle.abort();
+        let mut dca = task.state.lock().unwrap();
+        if dca.status == DcaStatus::Running {
+            dca.status = DcaStatus::Cancelled;
+            dca.next_buy_at = None;
+        }
+        drop(dca);
+        info!("dca {} deleted", id);
+        Ok(task.info(id))
+    }
+
+    pub async fn get(&self, id: u64) -> Result<DcaInfo> {
+        self.tasks
+            .lock()
+            .await
+            .get(&id)
+            .map(|task| task.info(id))
+            .ok_or_else(|| {
+                ApiError::new(ErrorCode::NotFound, format!("no dca schedule {}", id)).into()
+            })
+    }
+
+    pub async fn list(&self) -> Vec<DcaInfo> {
+        self.tasks
+            .lock()
+            .await
+            .iter()
+            .map(|(id, task)| task.info(*id))
+            .collect()
+    }
+}
+
+async fn run(state: AppState, id: u64, config: DcaConfig, shared: Arc<Mutex<DcaState>>) {
+    let _guard = telemetry::MonitorGuard::start();
+    info!("dca {} started: {:?}", id, config);
+    let interval_ms = config.interval_secs.saturating_mul(1000);
+    let mut interval = tokio::time::interval(Duration::from_secs(config.interval_secs));
+    interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
+    for n in 1..=config.total_buys {
+        interval.tick().await;
+        let leg = buy(&state, &config).await;
+        let failed = !leg.success;
+        if let Some(error) = &leg.error {
+            warn!(
+                "dca {} buy {}/{} failed: {}",
+                id, n, config.total_buys, error
+            );
+        } else {
+            info!("dca {} buy {}/{} done", id, n, config.total_buys);
+        }
+        let mut dca = shared.lock().unwrap();
+        dca.legs.push(leg);
+        if failed && config.abort_on_failure {
+            dca.status = DcaStatus::Aborted;
+            dca.next_buy_at = None;
+            return;
+        }
+        dca.next_buy_at = (n < config.total_buys).then(|| now_millis() + interval_ms);
+    }
+    shared.lock().unwrap().status = DcaStatus::Completed;
+    info!("dca {} completed", id);
+}
+
+async fn buy(state: &AppState, config: &DcaConfig) -> DcaLeg {
+    let params = SwapParams::market(
+        config.mint.clone(),
+        SwapDirection::Buy,
+        SwapInType::Qty,
+        config.sol_per_buy,
+        config.slippage,
+        config.jito,
+    );
+    let timestamp = now_millis();
+    match swap::swap_with_timeout(state.clone(), params).await {
+        Ok(result) => {
+            let signature = result.outcome.signatures.first().cloned();
+            let (sol_in, tokens_out) = match &signature {
+                Some(signature) => read_fill(state, &config.mint, signature)
+                    .await
+                    .inspect_err(|err| debug!("dca: no fill for {}: {}", signature, err))
+                    .unwrap_or_else(|_| quoted(&result)),
+                None => quoted(&result),
+            };
+            DcaLeg {
+                timestamp,
+                success: true,
+                signature,
+                sol_in: Some(sol_in),
+                tokens_out: Some(tokens_out),
+                error: None,
+            }
+        }
+        Err(err) => DcaLeg {
+            timestamp,
+            success: false,
+            signature: None,
+            sol_in: None,
+            tokens_out: None,
+            error: Some(err.to_string()),
+        },
+    }
+}
+
+// (sol, tokens) of the quote
+fn quoted(result: &SwapResult) -> (f64, f64) {
+    (result.summary.amount_in, result.summary.amount_out)
+}
+
+// (sol, tokens) the confirmed buy moved
+async fn read_fill(state: &AppState, mint: &str, signature: &str) -> Result<(f64, f64)> {
+    let mint = Pubkey::from_str(mint)?;
+    let changes = tx::get_balance_changes(
+        &state.client_blocking,
+        signature,
+        &state.wallet().pubkey(),
+        &mint,
+    )
+    .await?;
+    let decimals = state.decimals_cache.get(&state.client, &mint).await?;
+    Ok((
+        lamports_to_sol((-changes.trade_sol_delta()).max(0) as u64),
+        amount_to_ui_amount(changes.token_delta.max(0) as u64, decimals),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn leg(success: bool, sol_in: Option<f64>, tokens_out: Option<f64>) -> DcaLeg {
+        DcaLeg {
+            timestamp: 0,
+            success,
+            signature: None,
+            sol_in,
+            tokens_out,
+            error: None,
+        }
+    }
+
+    #[test]
+    fn test_totals() {
+        let legs = vec![
+            leg(true, Some(0.1), Some(1_000.0)),
+            leg(false, None, None),
+            leg(true, Some(0.1), Some(3_000.0)),
+        ];
+        let (sol, tokens) = totals(&legs);
+        assert!((sol - 0.2).abs() < 1e-12);
+        assert_eq!(tokens, 4_000.0);
+        assert_eq!(totals(&[]), (0.0, 0.0));
+    }
+}
@@ -0,0 +1,221 @@
+use axum::http::StatusCode;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use solana_client::client_error::ClientError;
+use solana_sdk::{instruction::InstructionError, transaction::TransactionError};
+
+// custom program errors raised when the output threshold is not met
+// raydium amm: ExceededSlippage
+const RAYDIUM_EXCEEDED_SLIPPAGE: u32 = 30;
+// pump: TooMuchSolRequired, TooLittleSolReceived
+const PUMP_TOO_MUCH_SOL_REQUIRED: u32 = 6002;
+const PUMP_TOO_LITTLE_SOL_RECEIVED: u32 = 6003;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ErrorCode {
+    InvalidInput,
+    Unauthorized,
+    Forbidden,
+    BuysDisabled,
+    MintBlocked,
+    InsufficientFunds,
+    SpendLimitExceeded,
+    InsufficientLiquidity,
+    NoRoute,
+    PoolPaused,
+    SlippageExceeded,
+    PoolNotFound,
+    NotFound,
+    AlreadyConfirmed,
+    IdempotencyKeyInUse,
+    TooManyAccounts,
+    TransactionTooLarge,
+    SimulationFailed,
+    RpcError,
+    MetadataUploadFailed,
+    TradingPaused,
+    ServiceUnavailable,
+    Timeout,
+    Internal,
+}
+
+impl ErrorCode {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ErrorCode::InvalidInput => "InvalidInput",
+            ErrorCode::Unauthorized => "Unauthorized",
+            ErrorCode::Forbidden => "Forbidden",
+            ErrorCode::BuysDisabled => "BuysDisabled",
+            ErrorCode::MintBlocked => "MintBlocked",
+            ErrorCode::InsufficientFunds => "InsufficientFunds",
+            ErrorCode::SpendLimitExceeded => "SpendLimitExceeded",
+            ErrorCode::InsufficientLiquidity => "InsufficientLiquidity",
+            ErrorCode::NoRoute => "NoRoute",
+            ErrorCode::PoolPaused => "PoolPaused",
+            ErrorCode::SlippageExceeded => "SlippageExceeded",
+            ErrorCode::PoolNotFound => "PoolNotFound",
+            ErrorCode::NotFound => "NotFound",
+            ErrorCode::AlreadyConfirmed => "AlreadyConfirmed",
+            ErrorCode::IdempotencyKeyInUse => "IdempotencyKeyInUse",
+            ErrorCode::TooManyAccounts => "TooManyAccounts",
+            ErrorCode::TransactionTooLarge => "TransactionTooLarge",
+            ErrorCode::SimulationFailed => "SimulationFailed",
+            ErrorCode::RpcError => "RpcError",
+            ErrorCode::MetadataUploadFailed => "MetadataUploadFailed",
+            ErrorCode::TradingPaused => "TradingPaused",
+            ErrorCode::ServiceUnavailable => "ServiceUnavailable",
+            ErrorCode::Timeout => "Timeout",
+            ErrorCode::Internal => "Internal",
+        }
+    }
+
+    pub fn status(&self) -> StatusCode {
+        match self {
+            ErrorCode::InvalidInput => StatusCode::BAD_REQUEST,
+            ErrorCode::Unauthorized => StatusCode::UNAUTHORIZED,
+            ErrorCode::Forbidden | ErrorCode::BuysDisabled | ErrorCode::MintBlocked => {
+                StatusCode::FORBIDDEN
+            }
+            ErrorCode::InsufficientFunds
+            | ErrorCode::SpendLimitExceeded
+            | ErrorCode::InsufficientLiquidity
+            | ErrorCode::NoRoute
+            | ErrorCode::PoolPaused
+            | ErrorCode::SlippageExceeded
+            | ErrorCode::TooManyAccounts
+            | ErrorCode::TransactionTooLarge
+            | ErrorCode::SimulationFailed => StatusCode::UNPROCESSABLE_ENTITY,
+            ErrorCode::PoolNotFound | ErrorCode::NotFound => StatusCode::NOT_FOUND,
+            ErrorCode::AlreadyConfirmed | ErrorCode::IdempotencyKeyInUse => StatusCode::CONFLICT,
+            ErrorCode::RpcError | ErrorCode::MetadataUploadFailed => StatusCode::BAD_GATEWAY,
+            ErrorCode::TradingPaused | ErrorCode::ServiceUnavailable => {
+                StatusCode::SERVICE_UNAVAILABLE
+            }
+            ErrorCode::Timeout => StatusCode::GATEWAY_TIMEOUT,
+            ErrorCode::Internal => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ApiError {
+    pub code: ErrorCode,
+    pub message: String,
+    /// extra context returned next to the message
+    pub data: Option<Value>,
+}
+
+impl ApiError {
+    pub fn new(code: ErrorCode, message: impl Into<String>) -> Self {
+        Self {
+            code,
+            message: message.into(),
+            data: None,
+        }
+    }
+
+    pub fn with_data(mut self, data: Value) -> Self {
+        self.data = Some(data);
+        self
+    }
+}
+
+impl From<anyhow::Error> for ApiError {
+    fn from(err: anyhow::Error) -> Self {
+        ApiError::from(&err)
+    }
+}
+
+impl From<&anyhow::Error> for ApiError {
+    fn from(err: &anyhow::Error) -> Self {
+        // also found under a context added on the way up
+        if let Some(api_error) = err.chain().find_map(|e| e.downcast_ref::<ApiError>()) {
+            return api_error.clone();
+        }
+        let message = err.to_string();
+        if let Some(client_error) = err.chain().find_map(|e| e.downcast_ref::<ClientError>()) {
+            if is_slippage_error(client_error.get_transaction_error()) {
+                return ApiError::new(ErrorCode::SlippageExceeded, message);
+            }
+            return ApiError::new(ErrorCode::RpcError, message);
+        }
+        if err
+            .chain()
+            .any(|e| e.downcast_ref::<reqwest::Error>().is_some())
+        {
+            return ApiError::new(ErrorCode::RpcError, message);
+        }
+        if is_slippage_message(&message) {
+            return ApiError::new(ErrorCode::SlippageExceeded, message);
+        }
+        ApiError::new(ErrorCode::Internal, message)
+    }
+}
+
+impl std::fmt::Display for ApiError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}: {}", self.code.as_str(), self.message)
+    }
+}
+
+impl std::error::Error for ApiError {}
+
+fn is_slippage_code(code: u32) -> bool {
+    matches!(
+        code,
+        RAYDIUM_EXCEEDED_SLIPPAGE | PUMP_TOO_MUCH_SOL_REQUIRED | PUMP_TOO_LITTLE_SOL_RECEIVED
+    )
+}
+
+fn is_slippage_error(err: Option<TransactionError>) -> bool {
+    match err {
+        Some(TransactionError::InstructionError(_, InstructionError::Custom(code))) => {
+            is_slippage_code(code)
+        }
+        _ => false,
+    }
+}
+
+// bundle and simulation failures only carry the rendered error
+fn is_slippage_message(message: &str) -> bool {
+    message
+        .split("custom program error: 0x")
+        .skip(1)
+        .filter_map(|rest| {
+            let hex: String = rest.chars().take_while(|c| c.is_ascii_hexdigit()).collect();
+            u32::from_str_radix(&hex, 16).ok()
+        })
+        .any(is_slippage_code)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_api_error_from_anyhow() {
+        let err: anyhow::Error =
+            ApiError::new(ErrorCode::PoolNotFound, "pool state not found").into();
+        let api_error = ApiError::from(&err);
+        assert_eq!(api_error.code, ErrorCode::PoolNotFound);
+        assert_eq!(api_error.message, "pool state not found");
+        assert_eq!(api_error.code.status(), StatusCode::NOT_FOUND);
+        assert_eq!(err.to_string(), "PoolNotFound: pool state not found");
+
+        // a context keeps the code
+        let err = err.context("swap failed");
+        assert_eq!(ApiError::from(&err).code, ErrorCode::PoolNotFound);
+
+        let err = ApiError::from(anyhow::anyhow!("something broke: badly"));
+        assert_eq!(err.code, ErrorCode::Internal);
+        assert_eq!(err.message, "something broke: badly");
+        // codes are not parsed out of messages
+        let err = ApiError::from(anyhow::anyhow!("PoolPaused: raydium pool 58oQ is paused"));
+        assert_eq!(err.code, ErrorCode::Internal);
+
+        let err = ApiError::from(anyhow::anyhow!(
+            "Error processing Instruction 3: custom program error: 0x1e"
+        ));
+        assert_eq!(err.code, ErrorCode::SlippageExceeded);
+    }
+}
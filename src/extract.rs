@@ -0,0 +1,132 @@
+use axum::{
+    async_trait,
+    extract::{rejection::JsonRejection, FromRequest, Request},
+    response::Response,
+    Json,
+};
+use serde::de::DeserializeOwned;
+use serde_json::{json, Value};
+
+use crate::{
+    error::{ApiError, ErrorCode},
+    helper::api_error,
+};
+
+/// A request body read by `ApiJson`
+pub trait JsonBody: DeserializeOwned {
+    /// valid body returned next to parse errors
+    fn example() -> Option<Value> {
+        None
+    }
+
+    /// values accepted by an enum `field`, named in parse errors
+    fn allowed_values(_field: &str) -> Option<&'static [&'static str]> {
+        None
+    }
+}
+
+/// `Json` whose rejections are `InvalidInput` api errors naming the offending field
+///
+/// `data.field` is the path of the field, `data.allowed` the values it accepts and
+/// `data.example` a valid body, when known.
+pub struct ApiJson<T>(pub T);
+
+#[async_trait]
+impl<T, S> FromRequest<S> for ApiJson<T>
+where
+    T: JsonBody,
+    S: Send + Sync,
+{
+    type Rejection = Response;
+
+    async fn from_request(req: Request, state: &S) -> Result<Self, Self::Rejection> {
+        match Json::<T>::from_request(req, state).await {
+            Ok(Json(value)) => Ok(ApiJson(value)),
+            Err(rejection) => Err(api_error(rejection_error::<T>(rejection))),
+        }
+    }
+}
+
+// prefix of the data errors of axum, followed by `path: serde error`
+const DATA_ERROR_PREFIX: &str = "Failed to deserialize the JSON body into the target type: ";
+
+fn rejection_error<T: JsonBody>(rejection: JsonRejection) -> ApiError {
+    let (field, message) = match &rejection {
+        JsonRejection::JsonDataError(_) => {
+            let text = rejection.body_text();
+            let (field, detail) =
+                split_field(text.strip_prefix(DATA_ERROR_PREFIX).unwrap_or(&text));
+            let message = match &field {
+                Some(field) => format!("invalid field `{}`: {}", field, detail),
+                None => format!("invalid body: {}", detail),
+            };
+            (field, message)
+        }
+        JsonRejection::JsonSyntaxError(_) => {
+            (None, format!("malformed json: {}", rejection.body_text()))
+        }
+        JsonRejection::MissingJsonContentType(_) => (
+            None,
+            "expected a `Content-Type: application/json` body".to_string(),
+        ),
+        _ => (None, rejection.body_text()),
+    };
+    let mut data = json!({});
+    if let Some(field) = &field {
+        data["field"] = json!(field);
+        if let Some(allowed) = T::allowed_values(last_segment(field)) {
+            data["allowed"] = json!(allowed);
+        }
+    }
+    if let Some(example) = T::example() {
+        data["example"] = example;
+    }
+    ApiError::new(ErrorCode::InvalidInput, message).with_data(data)
+}
+
+// `direction: unknown variant ..` or `missing field `mint` at ..` into the field and the error
+fn split_field(detail: &str) -> (Option<String>, String) {
+    if let Some(rest) = detail.strip_prefix("missing field `") {
+        let field = rest.split('`').next().unwrap_or_default();
+        return (Some(field.to_string()), detail.to_string());
+    }
+    match detail.split_once(": ") {
+        Some((path, error)) if !path.is_empty() && !path.contains(' ') => {
+            (Some(path.to_string()), error.to_string())
+        }
+        _ => (None, detail.to_string()),
+    }
+}
+
+// `swaps[0].direction` -> `direction`
+fn last_segment(path: &str) -> &str {
+    let segment = path.rsplit('.').next().unwrap_or(path);
+    segment.split('[').next().unwrap_or(segment)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_split_field() {
+        let (field, error) = split_field(
+            "direction: unknown variant `bye`, expected `buy` or `sell` at line 1 column 45",
+        );
+        assert_eq!(field.as_deref(), Some("direction"));
+        assert!(error.starts_with("unknown variant `bye`"));
+
+        let (field, _) =
+            split_field("swaps[1].in_type: unknown variant `all`, expected `qty` or `pct`");
+        assert_eq!(field.as_deref(), Some("swaps[1].in_type"));
+        assert_eq!(last_segment("swaps[1].in_type"), "in_type");
+        assert_eq!(last_segment("swaps[1]"), "swaps");
+
+        let (field, error) = split_field("missing field `mint` at line 1 column 30");
+        assert_eq!(field.as_deref(), Some("mint"));
+        assert_eq!(error, "missing field `mint` at line 1 column 30");
+
+        let (field, _) = split_field("expected value at line 1 column 1");
+        assert_eq!(field, None);
+    }
+}
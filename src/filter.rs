@@ -0,0 +1,100 @@
+use std::{collections::BTreeSet, env, fs};
+
+use anyhow::{Context, Result};
+use serde::Serialize;
+use tracing::info;
+
+use crate::error::{ApiError, ErrorCode};
+
+/// Mints buys are restricted to, or rejected for, sells are never filtered
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct MintFilters {
+    /// only these mints may be bought when not empty
+    pub allowlist: BTreeSet<String>,
+    pub blocklist: BTreeSet<String>,
+}
+
+impl MintFilters {
+    /// MINT_ALLOWLIST / MINT_BLOCKLIST (comma separated) merged with
+    /// MINT_ALLOWLIST_FILE / MINT_BLOCKLIST_FILE (one mint per line)
+    pub fn from_env() -> Result<Self> {
+        let filters = Self {
+            allowlist: load_list("MINT_ALLOWLIST")?,
+            blocklist: load_list("MINT_BLOCKLIST")?,
+        };
+        if !filters.allowlist.is_empty() || !filters.blocklist.is_empty() {
+            info!(
+                "mint filters: {} allowed, {} blocked",
+                filters.allowlist.len(),
+                filters.blocklist.len()
+            );
+        }
+        Ok(filters)
+    }
+
+    pub fn check_buy(&self, mint: &str) -> Result<()> {
+        if self.blocklist.contains(mint) {
+            return Err(ApiError::new(
+                ErrorCode::MintBlocked,
+                format!("{} is on the blocklist", mint),
+            )
+            .into());
+        }
+        if !self.allowlist.is_empty() && !self.allowlist.contains(mint) {
+            return Err(ApiError::new(
+                ErrorCode::MintBlocked,
+                format!("{} is not on the allowlist", mint),
+            )
+            .into());
+        }
+        Ok(())
+    }
+}
+
+fn load_list(key: &str) -> Result<BTreeSet<String>> {
+    let mut mints = parse_list(&env::var(key).unwrap_or_default());
+    if let Ok(path) = env::var(format!("{}_FILE", key)) {
+        let content =
+            fs::read_to_string(&path).with_context(|| format!("failed to read {}", path))?;
+        mints.extend(parse_list(&content));
+    }
+    Ok(mints)
+}
+
+// commas or new lines, `#` starts a comment
+fn parse_list(content: &str) -> BTreeSet<String> {
+    content
+        .lines()
+        .map(|line| line.split('#').next().unwrap_or_default())
+        .flat_map(|line| line.split(','))
+        .map(str::trim)
+        .filter(|mint| !mint.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_mint_filters() {
+        let list = parse_list("MintA, MintB\n# scams\nMintC # rugged\n\n");
+        assert_eq!(list.len(), 3);
+        assert!(list.contains("MintC"));
+
+        let filters = MintFilters {
+            allowlist: BTreeSet::new(),
+            blocklist: parse_list("MintA"),
+        };
+        assert!(filters.check_buy("MintA").is_err());
+        assert!(filters.check_buy("MintB").is_ok());
+
+        let filters = MintFilters {
+            allowlist: parse_list("MintB"),
+            blocklist: BTreeSet::new(),
+        };
+        assert!(filters.check_buy("MintA").is_err());
+        assert!(filters.check_buy("MintB").is_ok());
+    }
+}
@@ -1,23 +1,110 @@
-use std::collections::HashMap;
+use std::{collections::HashMap, env};
 
 use anyhow::{anyhow, Context, Result};
-use axum::Json;
+use axum::{
+    response::{IntoResponse, Response},
+    Json,
+};
 use serde::{Deserialize, Serialize};
-use serde_json::{json, Value};
+use serde_json::json;
+use solana_sdk::{commitment_config::CommitmentConfig, hash::hashv};
 
-use crate::get_client_build;
+use crate::{
+    constants::get_network,
+    error::{ApiError, ErrorCode},
+    get_client_build, request_id,
+};
 
-pub fn api_ok<T: Serialize>(data: T) -> Json<Value> {
+/// First 8 bytes of the sha256 of `preimage`, e.g. `account:Pool` or `global:swap`
+pub fn anchor_discriminator(preimage: &str) -> [u8; 8] {
+    let mut discriminator = [0u8; 8];
+    discriminator.copy_from_slice(&hashv(&[preimage.as_bytes()]).to_bytes()[..8]);
+    discriminator
+}
+
+/// `processed`, `confirmed` or `finalized`, unset is `confirmed`
+pub fn parse_commitment(commitment: Option<&str>) -> Result<CommitmentConfig> {
+    match commitment {
+        None | Some("confirmed") => Ok(CommitmentConfig::confirmed()),
+        Some("processed") => Ok(CommitmentConfig::processed()),
+        Some("finalized") => Ok(CommitmentConfig::finalized()),
+        Some(other) => Err(ApiError::new(
+            ErrorCode::InvalidInput,
+            format!(
+                "unknown commitment {}, expected processed, confirmed or finalized",
+                other
+            ),
+        )
+        .into()),
+    }
+}
+
+pub fn api_ok<T: Serialize>(data: T) -> Response {
     Json(json!({
         "status": "ok",
         "data": data
     }))
+    .into_response()
 }
-pub fn api_error(msg: &str) -> Json<Value> {
-    Json(json!({
+/// `{"status":"error","code":..,"message":..,"request_id":..}` with the status of the error code
+pub fn api_error(err: impl Into<ApiError>) -> Response {
+    let err = err.into();
+    let mut body = json!({
         "status": "error",
-        "message": msg
-    }))
+        "code": err.code,
+        "message": err.message
+    });
+    if let Some(data) = err.data {
+        body["data"] = data;
+    }
+    if let Some(request_id) = request_id::current() {
+        body["request_id"] = json!(request_id);
+    }
+    (err.code.status(), Json(body)).into_response()
+}
+
+// keep only scheme and host, rpc providers often put api keys in the path or query
+pub fn redact_url(url: &str) -> String {
+    match reqwest::Url::parse(url) {
+        Ok(parsed) => {
+            let host = parsed.host_str().unwrap_or_default();
+            let port = parsed.port().map(|p| format!(":{p}")).unwrap_or_default();
+            let rest = if parsed.path() != "/" || parsed.query().is_some() {
+                "/***"
+            } else {
+                ""
+            };
+            format!("{}://{}{}{}", parsed.scheme(), host, port, rest)
+        }
+        Err(_) => "***".to_string(),
+    }
+}
+
+fn get_explorer() -> String {
+    env::var("EXPLORER").unwrap_or("solscan".to_string())
+}
+
+fn get_explorer_cluster() -> String {
+    get_network().as_str().to_string()
+}
+
+/// Transaction url on the EXPLORER (`solscan` or `solanafm`) of the NETWORK cluster
+pub fn explorer_tx_url(signature: &str) -> String {
+    explorer_url(&get_explorer(), &get_explorer_cluster(), signature)
+}
+
+fn explorer_url(explorer: &str, cluster: &str, signature: &str) -> String {
+    let base = match explorer {
+        "solanafm" => "https://solana.fm/tx",
+        _ => "https://solscan.io/tx",
+    };
+    match cluster {
+        "mainnet" | "mainnet-beta" => format!("{}/{}", base, signature),
+        "devnet" if explorer == "solanafm" => {
+            format!("{}/{}?cluster=devnet-solana", base, signature)
+        }
+        cluster => format!("{}/{}?cluster={}", base, signature, cluster),
+    }
 }
 
 #[derive(Debug, Deserialize)]
@@ -72,4 +159,47 @@ mod tests {
         debug!("sol price: {}", price);
         assert!(price > 0.0)
     }
+
+    #[test]
+    fn test_parse_commitment() {
+        assert_eq!(
+            parse_commitment(None).unwrap(),
+            CommitmentConfig::confirmed()
+        );
+        assert_eq!(
+            parse_commitment(Some("processed")).unwrap(),
+            CommitmentConfig::processed()
+        );
+        assert!(parse_commitment(Some("max")).is_err());
+    }
+
+    #[test]
+    fn test_explorer_url() {
+        assert_eq!(
+            explorer_url("solscan", "mainnet", "5Z6r"),
+            "https://solscan.io/tx/5Z6r"
+        );
+        assert_eq!(
+            explorer_url("solscan", "devnet", "5Z6r"),
+            "https://solscan.io/tx/5Z6r?cluster=devnet"
+        );
+        assert_eq!(
+            explorer_url("solanafm", "devnet", "5Z6r"),
+            "https://solana.fm/tx/5Z6r?cluster=devnet-solana"
+        );
+    }
+
+    #[test]
+    fn test_redact_url() {
+        assert_eq!(
+            redact_url("https://mainnet.helius-rpc.com/?api-key=secret"),
+            "https://mainnet.helius-rpc.com/***"
+        );
+        assert_eq!(
+            redact_url("https://solana-mainnet.g.alchemy.com/v2/secret"),
+            "https://solana-mainnet.g.alchemy.com/***"
+        );
+        assert_eq!(redact_url("http://127.0.0.1:8899"), "http://127.0.0.1:8899");
+        assert_eq!(redact_url("not a url"), "***");
+    }
 }
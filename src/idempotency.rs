@@ -0,0 +1,385 @@
+use std::{
+    collections::{hash_map::DefaultHasher, HashMap},
+    env,
+    hash::{Hash, Hasher},
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use axum::{
+    body::{to_bytes, Body, Bytes},
+    extract::{Request, State},
+    http::{request::Parts, HeaderMap, HeaderName, HeaderValue, StatusCode},
+    middleware::Next,
+    response::Response,
+};
+use serde::Deserialize;
+use solana_sdk::signer::Signer;
+use tracing::{debug, info, Instrument};
+
+use crate::{
+    api::AppState,
+    breaker,
+    error::{ApiError, ErrorCode},
+    helper::api_error,
+    ledger::now_millis,
+    request_id,
+};
+
+pub const IDEMPOTENCY_KEY_HEADER: &str = "idempotency-key";
+// set on a response replayed for a repeated key
+pub const REPLAYED_HEADER: &str = "idempotent-replayed";
+
+const MAX_KEY_LEN: usize = 255;
+// the default body limit of the json extractor
+const MAX_BODY_BYTES: usize = 2 * 1024 * 1024;
+const EVICT_INTERVAL: Duration = Duration::from_secs(60);
+
+fn get_idempotency_ttl_secs() -> u64 {
+    env::var("IDEMPOTENCY_TTL_SECS")
+        .ok()
+        .and_then(|v| v.parse::<u64>().ok())
+        .unwrap_or(3600)
+}
+
+fn is_valid_key(key: &str) -> bool {
+    !key.is_empty() && key.len() <= MAX_KEY_LEN && key.chars().all(|c| c.is_ascii_graphic())
+}
+
+#[derive(Debug, Clone)]
+struct StoredResponse {
+    status: StatusCode,
+    headers: HeaderMap,
+    body: Bytes,
+}
+
+impl StoredResponse {
+    fn replay(self) -> Response {
+        let mut response = Response::new(Body::from(self.body));
+        *response.status_mut() = self.status;
+        *response.headers_mut() = self.headers;
+        response.headers_mut().insert(
+            HeaderName::from_static(REPLAYED_HEADER),
+            HeaderValue::from_static("true"),
+        );
+        response
+    }
+}
+
+#[derive(Debug)]
+enum Slot {
+    InFlight,
+    Done(StoredResponse),
+}
+
+#[derive(Debug)]
+struct Entry {
+    // hash of the request, a key reused for another request is rejected
+    fingerprint: u64,
+    /// unix timestamp in milliseconds
+    created_at: u64,
+    slot: Slot,
+}
+
+#[derive(Debug)]
+enum Begin {
+    New,
+    InFlight,
+    Mismatch,
+    Replay(StoredResponse),
+}
+
+// (wallet, key)
+type Scope = (String, String);
+
+/// Responses by wallet and `Idempotency-Key` for IDEMPOTENCY_TTL_SECS, see `idempotent`
+pub struct IdempotencyKeys {
+    entries: Mutex<HashMap<Scope, Entry>>,
+    ttl_ms: u64,
+}
+
+impl Default for IdempotencyKeys {
+    fn default() -> Self {
+        Self::new(get_idempotency_ttl_secs() * 1000)
+    }
+}
+
+impl IdempotencyKeys {
+    pub fn new(ttl_ms: u64) -> Self {
+        Self {
+            entries: Mutex::new(HashMap::new()),
+            ttl_ms,
+        }
+    }
+
+    // claim `scope` for a new request unless a live entry holds it
+    fn begin_at(&self, scope: &Scope, fingerprint: u64, now: u64) -> Begin {
+        let mut entries = self.entries.lock().unwrap();
+        match entries.get(scope) {
+            Some(entry) if now.saturating_sub(entry.created_at) < self.ttl_ms => {
+                if entry.fingerprint != fingerprint {
+                    return Begin::Mismatch;
+                }
+                match &entry.slot {
+                    Slot::InFlight => Begin::InFlight,
+                    Slot::Done(response) => Begin::Replay(response.clone()),
+                }
+            }
+            _ => {
+                entries.insert(
+                    scope.clone(),
+                    Entry {
+                        fingerprint,
+                        created_at: now,
+                        slot: Slot::InFlight,
+                    },
+                );
+                Begin::New
+            }
+        }
+    }
+
+    fn complete(&self, scope: &Scope, response: StoredResponse) {
+        if let Some(entry) = self.entries.lock().unwrap().get_mut(scope) {
+            entry.slot = Slot::Done(response);
+        }
+    }
+
+    // release a key whose request never completed, so a retry runs it again
+    fn forget(&self, scope: &Scope) {
+        let mut entries = self.entries.lock().unwrap();
+        if matches!(entries.get(scope), Some(entry) if matches!(entry.slot, Slot::InFlight)) {
+            entries.remove(scope);
+        }
+    }
+
+    /// Drop the expired keys, returns how many
+    pub fn evict_expired(&self) -> usize {
+        self.evict_at(now_millis())
+    }
+
+    fn evict_at(&self, now: u64) -> usize {
+        let mut entries = self.entries.lock().unwrap();
+        let before = entries.len();
+        entries.retain(|_, entry| now.saturating_sub(entry.created_at) < self.ttl_ms);
+        before - entries.len()
+    }
+}
+
+/// Evict expired keys every minute
+pub async fn evict_periodically(keys: Arc<IdempotencyKeys>) {
+    let mut interval = tokio::time::interval(EVICT_INTERVAL);
+    loop {
+        interval.tick().await;
+        let evicted = keys.evict_expired();
+        if evicted > 0 {
+            debug!("evicted {} idempotency keys", evicted);
+        }
+    }
+}
+
+fn fingerprint(parts: &Parts, body: &[u8]) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    parts.method.hash(&mut hasher);
+    parts.uri.hash(&mut hasher);
+    body.hash(&mut hasher);
+    hasher.finish()
+}
+
+#[derive(Deserialize)]
+struct ErrorBody {
+    code: ErrorCode,
+}
+
+// an error response rejecting the request before it sent anything, see `breaker::counts_as_failure`
+fn is_rejection(status: StatusCode, body: &[u8]) -> bool {
+    !status.is_success()
+        && serde_json::from_slice::<ErrorBody>(body)
+            .is_ok_and(|body| !breaker::counts_as_failure(body.code))
+}
+
+// holds a claimed key, released on drop unless the response was stored
+struct Pending {
+    keys: Arc<IdempotencyKeys>,
+    scope: Option<Scope>,
+}
+
+impl Pending {
+    async fn run(mut self, request: Request, next: Next) -> Response {
+        let (parts, body) = next.run(request).await.into_parts();
+        let body = match to_bytes(body, usize::MAX).await {
+            Ok(body) => body,
+            Err(err) => {
+                return api_error(ApiError::new(
+                    ErrorCode::Internal,
+                    format!("failed to read the response: {}", err),
+                ))
+            }
+        };
+        if let Some(scope) = self.scope.take() {
+            if is_rejection(parts.status, &body) {
+                // nothing ran, a retry with the same key runs the request again
+                self.keys.forget(&scope);
+            } else {
+                self.keys.complete(
+                    &scope,
+                    StoredResponse {
+                        status: parts.status,
+                        headers: parts.headers.clone(),
+                        body: body.clone(),
+                    },
+                );
+            }
+        }
+        Response::from_parts(parts, Body::from(body))
+    }
+}
+
+impl Drop for Pending {
+    fn drop(&mut self) {
+        if let Some(scope) = self.scope.take() {
+            self.keys.forget(&scope);
+        }
+    }
+}
+
+/// Run a request carrying `Idempotency-Key` at most once per wallet and key
+///
+/// A repeat within IDEMPOTENCY_TTL_SECS gets the stored response back with
+/// `Idempotent-Replayed: true`, successes and failures that may have sent something, e.g.
+/// `Timeout`, included. Rejections that sent nothing are not kept, a retry runs again.
+/// A repeat while the first one runs is `IdempotencyKeyInUse`, a key reused with another
+/// body is `InvalidInput`.
+/// The request runs in its own task so its response is stored even if the client goes away.
+pub async fn idempotent(State(state): State<AppState>, request: Request, next: Next) -> Response {
+    let Some(key) = request.headers().get(IDEMPOTENCY_KEY_HEADER) else {
+        return next.run(request).await;
+    };
+    let Some(key) = key.to_str().ok().filter(|key| is_valid_key(key)) else {
+        return api_error(ApiError::new(
+            ErrorCode::InvalidInput,
+            format!(
+                "Idempotency-Key must be 1 to {} visible ascii characters",
+                MAX_KEY_LEN
+            ),
+        ));
+    };
+    let scope = (state.wallet().pubkey().to_string(), key.to_string());
+    let (parts, body) = request.into_parts();
+    let body = match to_bytes(body, MAX_BODY_BYTES).await {
+        Ok(body) => body,
+        Err(err) => {
+            return api_error(ApiError::new(
+                ErrorCode::InvalidInput,
+                format!("failed to read the request body: {}", err),
+            ))
+        }
+    };
+
+    let keys = state.idempotency_keys.clone();
+    match keys.begin_at(&scope, fingerprint(&parts, &body), now_millis()) {
+        Begin::New => {}
+        Begin::Replay(response) => {
+            info!("replaying the response of idempotency key {}", scope.1);
+            return response.replay();
+        }
+        Begin::InFlight => {
+            return api_error(ApiError::new(
+                ErrorCode::IdempotencyKeyInUse,
+                format!(
+                    "a request with idempotency key {} is still running, retry once it is done",
+                    scope.1
+                ),
+            ))
+        }
+        Begin::Mismatch => {
+            return api_error(ApiError::new(
+                ErrorCode::InvalidInput,
+                format!(
+                    "idempotency key {} was already used for another request",
+                    scope.1
+                ),
+            ))
+        }
+    }
+
+    let pending = Pending {
+        keys,
+        scope: Some(scope),
+    };
+    let request = Request::from_parts(parts, Body::from(body));
+    let run = request_id::propagate(pending.run(request, next).in_current_span());
+    tokio::spawn(run).await.unwrap_or_else(|err| {
+        api_error(ApiError::new(
+            ErrorCode::Internal,
+            format!("request failed: {}", err),
+        ))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn response(body: &'static str) -> StoredResponse {
+        StoredResponse {
+            status: StatusCode::OK,
+            headers: HeaderMap::new(),
+            body: Bytes::from_static(body.as_bytes()),
+        }
+    }
+
+    #[test]
+    fn test_idempotency_keys() {
+        let keys = IdempotencyKeys::new(60_000);
+        let scope = ("wallet".to_string(), "key".to_string());
+        assert!(matches!(keys.begin_at(&scope, 1, 0), Begin::New));
+        assert!(matches!(keys.begin_at(&scope, 1, 1), Begin::InFlight));
+        assert!(matches!(keys.begin_at(&scope, 2, 1), Begin::Mismatch));
+
+        keys.complete(&scope, response("first"));
+        match keys.begin_at(&scope, 1, 2) {
+            Begin::Replay(stored) => assert_eq!(stored.body, "first"),
+            other => panic!("expected a replay, got {:?}", other),
+        }
+        // keys are per wallet
+        let other_wallet = ("other".to_string(), "key".to_string());
+        assert!(matches!(keys.begin_at(&other_wallet, 1, 2), Begin::New));
+
+        // a request that did not complete releases its key
+        keys.forget(&other_wallet);
+        assert!(matches!(keys.begin_at(&other_wallet, 1, 3), Begin::New));
+        // a completed one is kept
+        keys.forget(&scope);
+        assert!(matches!(keys.begin_at(&scope, 1, 3), Begin::Replay(_)));
+
+        // expired keys run again and are evicted
+        assert!(matches!(keys.begin_at(&scope, 2, 60_000), Begin::New));
+        assert_eq!(keys.evict_at(60_003), 1);
+        assert_eq!(keys.evict_at(120_000), 1);
+        assert_eq!(keys.entries.lock().unwrap().len(), 0);
+
+        let body = |code: &str| format!(r#"{{"status":"error","code":"{}","message":""}}"#, code);
+        assert!(is_rejection(
+            StatusCode::UNPROCESSABLE_ENTITY,
+            body("NoRoute").as_bytes()
+        ));
+        assert!(!is_rejection(
+            StatusCode::GATEWAY_TIMEOUT,
+            body("Timeout").as_bytes()
+        ));
+        assert!(!is_rejection(
+            StatusCode::BAD_GATEWAY,
+            body("RpcError").as_bytes()
+        ));
+        assert!(!is_rejection(
+            StatusCode::OK,
+            br#"{"status":"ok","data":{}}"#
+        ));
+
+        assert!(is_valid_key("3f2b8c1e-9a4d-4e7b"));
+        assert!(!is_valid_key(""));
+        assert!(!is_valid_key("with space"));
+        assert!(!is_valid_key(&"a".repeat(256)));
+    }
+}
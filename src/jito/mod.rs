@@ -1,19 +1,24 @@
-use std::{future::Future, str::FromStr, sync::LazyLock, time::Duration};
+use std::{env, future::Future, str::FromStr, sync::LazyLock, time::Duration};
 
 use anyhow::{anyhow, Result};
 use api::{get_tip_accounts, TipAccountResult};
 use indicatif::{ProgressBar, ProgressStyle};
 use rand::{seq::IteratorRandom, thread_rng};
 use serde::Deserialize;
-use serde_json::Value;
-use solana_sdk::pubkey::Pubkey;
+use serde_json::{json, Value};
+use solana_sdk::{bs58, pubkey::Pubkey, transaction::VersionedTransaction};
 use tokio::{
     sync::RwLock,
     time::{sleep, Instant},
 };
 use tracing::{debug, error, info, warn};
 
-use crate::get_env_var;
+use crate::{
+    constants::get_network,
+    error::{ApiError, ErrorCode},
+    get_client_build, get_random_rpc_url,
+    tx::SimulatedTransaction,
+};
 
 pub mod api;
 pub mod ws;
@@ -32,10 +37,31 @@ pub struct TipPercentileData {
     pub ema_landed_tips_50th_percentile: f64,
 }
 
-pub static BLOCK_ENGINE_URL: LazyLock<String> =
-    LazyLock::new(|| get_env_var("JITO_BLOCK_ENGINE_URL"));
-pub static TIP_STREAM_URL: LazyLock<String> = LazyLock::new(|| get_env_var("JITO_TIP_STREAM_URL"));
-pub static TIP_PERCENTILE: LazyLock<String> = LazyLock::new(|| get_env_var("JITO_TIP_PERCENTILE"));
+pub static BLOCK_ENGINE_URL: LazyLock<String> = LazyLock::new(|| {
+    env::var("JITO_BLOCK_ENGINE_URL").unwrap_or("https://mainnet.block-engine.jito.wtf".to_string())
+});
+pub static TIP_STREAM_URL: LazyLock<String> = LazyLock::new(|| {
+    env::var("JITO_TIP_STREAM_URL")
+        .unwrap_or("wss://bundles.jito.wtf/api/v1/bundles/tip_stream".to_string())
+});
+pub static TIP_PERCENTILE: LazyLock<String> =
+    LazyLock::new(|| env::var("JITO_TIP_PERCENTILE").unwrap_or("50".to_string()));
+
+/// Jito only runs on mainnet, reject jito sends on other networks
+pub fn check_network() -> Result<()> {
+    let network = get_network();
+    if !network.supports_jito() {
+        return Err(ApiError::new(
+            ErrorCode::InvalidInput,
+            format!(
+                "jito is not available on {}, send without jito",
+                network.as_str()
+            ),
+        )
+        .into());
+    }
+    Ok(())
+}
 
 pub static TIP_ACCOUNTS: LazyLock<RwLock<Vec<String>>> = LazyLock::new(|| RwLock::new(vec![]));
 
@@ -172,6 +198,120 @@ where
         sleep(interval).await;
     }
 }
+// a jito-solana rpc serving `simulateBundle`, RPC_ENDPOINTS when unset
+fn get_simulate_rpc_url() -> Result<String> {
+    match env::var("JITO_SIMULATE_RPC_URL") {
+        Ok(url) if !url.trim().is_empty() => Ok(url),
+        _ => get_random_rpc_url(),
+    }
+}
+
+/// Simulate `bundle` with `simulateBundle`, each transaction runs on the state the previous
+/// ones left, as they would land. Returns one result per transaction, in order.
+pub async fn simulate_bundle(bundle: &[VersionedTransaction]) -> Result<Vec<SimulatedTransaction>> {
+    let encoded = bundle
+        .iter()
+        .map(|txn| Ok(bs58::encode(bincode::serialize(txn)?).into_string()))
+        .collect::<Result<Vec<_>>>()?;
+    let accounts_configs = vec![Value::Null; bundle.len()];
+    let request = json!({
+        "jsonrpc": "2.0",
+        "id": 1,
+        "method": "simulateBundle",
+        "params": [
+            { "encodedTransactions": encoded },
+            {
+                "preExecutionAccountsConfigs": accounts_configs,
+                "postExecutionAccountsConfigs": accounts_configs,
+                "transactionEncoding": "base58",
+                "skipSigVerify": false,
+                "replaceRecentBlockhash": false,
+            },
+        ],
+    });
+    let response: Value = get_client_build()?
+        .post(get_simulate_rpc_url()?)
+        .json(&request)
+        .send()
+        .await
+        .map_err(|e| {
+            ApiError::new(
+                ErrorCode::RpcError,
+                format!("simulateBundle request failed: {}", e),
+            )
+        })?
+        .json()
+        .await
+        .map_err(|e| {
+            ApiError::new(
+                ErrorCode::RpcError,
+                format!("invalid simulateBundle response: {}", e),
+            )
+        })?;
+    if let Some(error) = response.get("error") {
+        return Err(ApiError::new(
+            ErrorCode::RpcError,
+            format!(
+                "simulateBundle failed, JITO_SIMULATE_RPC_URL must be a jito-solana rpc: {}",
+                error
+            ),
+        )
+        .into());
+    }
+    let signatures: Vec<String> = bundle
+        .iter()
+        .map(|txn| txn.signatures[0].to_string())
+        .collect();
+    Ok(bundle_simulation_results(
+        &response["result"]["value"],
+        &signatures,
+    ))
+}
+
+// results of `signatures` from a `simulateBundle` value, the transactions after a failed one
+// did not run
+fn bundle_simulation_results(value: &Value, signatures: &[String]) -> Vec<SimulatedTransaction> {
+    let results = value["transactionResults"]
+        .as_array()
+        .cloned()
+        .unwrap_or_default();
+    let failure = &value["summary"]["failed"];
+    let failed_signature = failure["tx_signature"].as_str();
+    let mut reverted = false;
+    signatures
+        .iter()
+        .enumerate()
+        .map(|(index, signature)| {
+            let result = results.get(index);
+            let err = match result.map(|result| &result["err"]) {
+                Some(err) if !err.is_null() => Some(err.to_string()),
+                _ if failed_signature == Some(signature.as_str()) => {
+                    Some(failure["error"].to_string())
+                }
+                _ if reverted || (result.is_none() && !failure.is_null()) => {
+                    Some("not run, an earlier transaction of the bundle failed".to_string())
+                }
+                _ => None,
+            };
+            reverted |= err.is_some();
+            let logs = result
+                .and_then(|result| result["logs"].as_array())
+                .map(|logs| {
+                    logs.iter()
+                        .filter_map(|log| log.as_str().map(str::to_string))
+                        .collect()
+                })
+                .unwrap_or_default();
+            SimulatedTransaction {
+                success: err.is_none(),
+                err,
+                logs,
+                units_consumed: result.and_then(|result| result["unitsConsumed"].as_u64()),
+            }
+        })
+        .collect()
+}
+
 pub fn new_progress_bar() -> ProgressBar {
     let progress_bar = ProgressBar::new(42);
     progress_bar.set_style(
@@ -189,7 +329,36 @@ mod tests {
 
     use serde_json::{json, Value};
 
-    use super::wait_for_bundle_confirmation;
+    use super::{bundle_simulation_results, wait_for_bundle_confirmation};
+
+    #[test]
+    fn test_bundle_simulation_results() {
+        let signatures = ["buy", "sell", "tip"].map(str::to_string);
+        let succeeded = json!({
+            "summary": "succeeded",
+            "transactionResults": [
+                {"err": null, "logs": ["buy ok"], "unitsConsumed": 60000},
+                {"err": null, "logs": [], "unitsConsumed": 50000},
+                {"err": null, "logs": [], "unitsConsumed": 150},
+            ],
+        });
+        let results = bundle_simulation_results(&succeeded, &signatures);
+        assert!(results.iter().all(|result| result.success));
+        assert_eq!(results[0].logs, vec!["buy ok".to_string()]);
+        assert_eq!(results[1].units_consumed, Some(50000));
+
+        // the sell reverted, the tip after it never ran
+        let failed = json!({
+            "summary": {"failed": {"error": {"TransactionFailure": "custom program error: 0x1"}, "tx_signature": "sell"}},
+            "transactionResults": [
+                {"err": null, "logs": [], "unitsConsumed": 60000},
+            ],
+        });
+        let results = bundle_simulation_results(&failed, &signatures);
+        assert!(results[0].success);
+        assert!(results[1].err.as_deref().unwrap().contains("0x1"));
+        assert!(!results[2].success);
+    }
 
     fn generate_statuses(bundle_id: String, confirmation_status: &str) -> Vec<Value> {
         vec![json!({
@@ -1,15 +1,21 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+
 use crate::jito::{TipPercentileData, TIPS_PERCENTILE, TIP_STREAM_URL};
 use anyhow::{Context, Result};
 use futures_util::StreamExt;
 use tokio_tungstenite::{connect_async, tungstenite::Message};
 use tracing::{debug, error, info, warn};
 
+/// Whether the tip stream is currently connected and receiving
+pub static TIP_STREAM_CONNECTED: AtomicBool = AtomicBool::new(false);
+
 pub async fn tip_stream() -> Result<()> {
     let (ws_stream, _) = connect_async(TIP_STREAM_URL.to_string())
         .await
         .context("Failed to connect to WebSocket server")?;
 
     info!("Connected to WebSocket server: tip_stream");
+    TIP_STREAM_CONNECTED.store(true, Ordering::Relaxed);
 
     let (mut _write, mut read) = ws_stream.split();
 
@@ -42,6 +48,7 @@ pub async fn tip_stream() -> Result<()> {
             _ => {}
         }
     }
+    TIP_STREAM_CONNECTED.store(false, Ordering::Relaxed);
 
     Ok(())
 }
@@ -0,0 +1,554 @@
+use std::{
+    collections::{BTreeMap, VecDeque},
+    env,
+    str::FromStr,
+    sync::atomic::{AtomicU64, Ordering},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use solana_sdk::{native_token::LAMPORTS_PER_SOL, pubkey::Pubkey};
+use spl_token::amount_to_ui_amount;
+use tokio::sync::RwLock;
+
+use crate::{
+    error::{ApiError, ErrorCode},
+    swap::{SwapDirection, Venue},
+    tx::TxCosts,
+};
+
+pub const VENUES: [Venue; 4] = [Venue::Pump, Venue::Raydium, Venue::Meteora, Venue::PumpSwap];
+
+fn get_ledger_capacity() -> usize {
+    env::var("LEDGER_CAPACITY")
+        .ok()
+        .and_then(|v| usize::from_str(&v).ok())
+        .unwrap_or(10_000)
+}
+
+pub fn now_millis() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or_default()
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TradeRecord {
+    pub id: u64,
+    /// unix timestamp in milliseconds
+    pub timestamp: u64,
+    pub wallet: String,
+    pub mint: String,
+    pub venue: Venue,
+    pub direction: SwapDirection,
+    pub success: bool,
+    pub signatures: Vec<String>,
+    pub error: Option<String>,
+    /// submission to confirmation
+    pub duration_ms: u64,
+    /// raw amount of the input token
+    pub amount_in: Option<u64>,
+    /// quoted raw amount of the output token before slippage
+    pub expected_out: Option<u64>,
+    pub price_impact_pct: Option<f64>,
+    /// filled in from the confirmed transaction, see `tx::get_balance_changes`
+    pub amount_out: Option<u64>,
+    pub realized_slippage_pct: Option<f64>,
+    /// usd per sol when the fill was read
+    #[serde(default)]
+    pub sol_price: Option<f64>,
+    /// filled in from the confirmed transactions, see `tx::get_tx_costs`
+    #[serde(default)]
+    pub costs: Option<TxCosts>,
+}
+
+/// Successful trades to export, `from` and `to` are unix timestamps in milliseconds, inclusive
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct TradeFilter {
+    pub from: Option<u64>,
+    pub to: Option<u64>,
+    pub mint: Option<String>,
+}
+
+impl TradeFilter {
+    pub fn validate(&self) -> Result<()> {
+        if let Some(mint) = &self.mint {
+            Pubkey::from_str(mint).map_err(|e| {
+                ApiError::new(
+                    ErrorCode::InvalidInput,
+                    format!("failed to parse mint pubkey: {}", e),
+                )
+            })?;
+        }
+        if let (Some(from), Some(to)) = (self.from, self.to) {
+            if from > to {
+                return Err(ApiError::new(
+                    ErrorCode::InvalidInput,
+                    format!("from {} is after to {}", from, to),
+                )
+                .into());
+            }
+        }
+        Ok(())
+    }
+
+    pub fn matches(&self, record: &TradeRecord) -> bool {
+        record.success
+            && self.from.map_or(true, |from| record.timestamp >= from)
+            && self.to.map_or(true, |to| record.timestamp <= to)
+            && self.mint.as_ref().map_or(true, |mint| &record.mint == mint)
+    }
+}
+
+pub const TRADES_CSV_HEADER: &str =
+    "timestamp,wallet,mint,direction,amount_in,amount_out,price,sol_price,signature\n";
+
+/// Csv line of a trade in ui amounts, the token side needs the mint `decimals`
+///
+/// `amount_out` is the filled amount, the quote until the fill is read. `price` is sol per token.
+pub fn trade_csv_row(record: &TradeRecord, decimals: Option<u8>) -> String {
+    let sol = |amount: u64| amount_to_ui_amount(amount, spl_token::native_mint::DECIMALS);
+    let token = |amount: u64| decimals.map(|decimals| amount_to_ui_amount(amount, decimals));
+    let amount_out = record.amount_out.or(record.expected_out);
+    let (sol_amount, token_amount, amount_in, amount_out) = match record.direction {
+        SwapDirection::Buy => {
+            let sol_amount = record.amount_in.map(sol);
+            let token_amount = amount_out.and_then(token);
+            (sol_amount, token_amount, sol_amount, token_amount)
+        }
+        SwapDirection::Sell => {
+            let sol_amount = amount_out.map(sol);
+            let token_amount = record.amount_in.and_then(token);
+            (sol_amount, token_amount, token_amount, sol_amount)
+        }
+    };
+    let price = match (sol_amount, token_amount) {
+        (Some(sol_amount), Some(token_amount)) if token_amount > 0.0 => {
+            Some(sol_amount / token_amount)
+        }
+        _ => None,
+    };
+    let number = |value: Option<f64>| value.map(|v| v.to_string()).unwrap_or_default();
+    let fields = [
+        record.timestamp.to_string(),
+        record.wallet.clone(),
+        record.mint.clone(),
+        record.direction.as_str().to_string(),
+        number(amount_in),
+        number(amount_out),
+        number(price),
+        number(record.sol_price),
+        record.signatures.first().cloned().unwrap_or_default(),
+    ];
+    let mut row = fields
+        .iter()
+        .map(|field| csv_field(field))
+        .collect::<Vec<_>>()
+        .join(",");
+    row.push('\n');
+    row
+}
+
+// quote fields holding a separator, a quote or a line break
+fn csv_field(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+/// Realized profit of the trades of one mint, sol amounts are ui amounts, token amounts raw
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct MintPnl {
+    pub mint: String,
+    pub buys: u64,
+    pub sells: u64,
+    pub sol_spent: f64,
+    pub sol_received: f64,
+    pub tokens_bought: u64,
+    pub tokens_sold: u64,
+    /// sells against the average buy price, before fees, sells of tokens bought before the
+    /// oldest record are left out
+    pub realized_pnl: f64,
+    /// network fees and jito tips
+    pub fees: f64,
+    pub net_pnl: f64,
+    /// trades whose costs are not read yet, their fees are not counted
+    pub costs_pending: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Pnl {
+    pub realized_pnl: f64,
+    pub fees: f64,
+    pub net_pnl: f64,
+    pub mints: Vec<MintPnl>,
+}
+
+#[derive(Default)]
+struct MintTotals {
+    buys: u64,
+    sells: u64,
+    // lamports and raw token amounts
+    sol_spent: u128,
+    sol_received: u128,
+    tokens_bought: u128,
+    tokens_sold: u128,
+    fees: u128,
+    costs_pending: u64,
+}
+
+impl MintTotals {
+    // lamports, sold tokens valued at the average buy price, sells past the tokens bought
+    // within the ledger have no known cost and are left out
+    fn realized_pnl(&self) -> f64 {
+        if self.tokens_bought == 0 || self.tokens_sold == 0 {
+            return 0.0;
+        }
+        let sold = self.tokens_sold.min(self.tokens_bought) as f64;
+        let received = self.sol_received as f64 * sold / self.tokens_sold as f64;
+        let cost = self.sol_spent as f64 * sold / self.tokens_bought as f64;
+        received - cost
+    }
+}
+
+/// Profit of the successful `records` by mint, amounts out are the fills, the quotes until read
+pub fn pnl(records: &[TradeRecord]) -> Pnl {
+    let mut totals: BTreeMap<&str, MintTotals> = BTreeMap::new();
+    for record in records.iter().filter(|r| r.success) {
+        let mint = totals.entry(record.mint.as_str()).or_default();
+        let amount_in = record.amount_in.unwrap_or_default() as u128;
+        let amount_out = record
+            .amount_out
+            .or(record.expected_out)
+            .unwrap_or_default() as u128;
+        match record.direction {
+            SwapDirection::Buy => {
+                mint.buys += 1;
+                mint.sol_spent += amount_in;
+                mint.tokens_bought += amount_out;
+            }
+            SwapDirection::Sell => {
+                mint.sells += 1;
+                mint.tokens_sold += amount_in;
+                mint.sol_received += amount_out;
+            }
+        }
+        match &record.costs {
+            Some(costs) => mint.fees += costs.total as u128,
+            None => mint.costs_pending += 1,
+        }
+    }
+    let sol = |lamports: f64| lamports / LAMPORTS_PER_SOL as f64;
+    let mints: Vec<MintPnl> = totals
+        .into_iter()
+        .map(|(mint, totals)| {
+            let realized_pnl = totals.realized_pnl();
+            MintPnl {
+                mint: mint.to_string(),
+                buys: totals.buys,
+                sells: totals.sells,
+                sol_spent: sol(totals.sol_spent as f64),
+                sol_received: sol(totals.sol_received as f64),
+                tokens_bought: totals.tokens_bought as u64,
+                tokens_sold: totals.tokens_sold as u64,
+                realized_pnl: sol(realized_pnl),
+                fees: sol(totals.fees as f64),
+                net_pnl: sol(realized_pnl - totals.fees as f64),
+                costs_pending: totals.costs_pending,
+            }
+        })
+        .collect();
+    let realized_pnl = mints.iter().map(|m| m.realized_pnl).sum();
+    let fees = mints.iter().map(|m| m.fees).sum();
+    Pnl {
+        realized_pnl,
+        fees,
+        net_pnl: mints.iter().map(|m| m.net_pnl).sum(),
+        mints,
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct RouteStats {
+    pub venue: Venue,
+    pub swaps: u64,
+    pub succeeded: u64,
+    pub success_rate: f64,
+    pub avg_realized_slippage_pct: f64,
+    pub avg_confirmation_ms: f64,
+    pub avg_price_impact_pct: f64,
+}
+
+/// In-memory record of recent swaps, oldest entries are evicted past capacity
+pub struct Ledger {
+    records: RwLock<VecDeque<TradeRecord>>,
+    next_id: AtomicU64,
+    capacity: usize,
+}
+
+impl Default for Ledger {
+    fn default() -> Self {
+        Self::new(get_ledger_capacity())
+    }
+}
+
+impl Ledger {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            records: RwLock::new(VecDeque::new()),
+            next_id: AtomicU64::new(1),
+            capacity,
+        }
+    }
+
+    pub async fn record(&self, mut record: TradeRecord) -> u64 {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        record.id = id;
+        let mut records = self.records.write().await;
+        records.push_back(record);
+        while records.len() > self.capacity {
+            records.pop_front();
+        }
+        id
+    }
+
+    pub async fn update<F>(&self, id: u64, f: F)
+    where
+        F: FnOnce(&mut TradeRecord),
+    {
+        let mut records = self.records.write().await;
+        if let Some(record) = records.iter_mut().rev().find(|r| r.id == id) {
+            f(record);
+        }
+    }
+
+    /// Up to `limit` records matching `filter` with an id above `after`, oldest first,
+    /// fewer than `limit` means the end was reached
+    pub async fn page<F>(&self, after: u64, limit: usize, filter: F) -> Vec<TradeRecord>
+    where
+        F: Fn(&TradeRecord) -> bool,
+    {
+        let records = self.records.read().await;
+        let start = records.partition_point(|r| r.id <= after);
+        records
+            .range(start..)
+            .filter(|r| filter(r))
+            .take(limit)
+            .cloned()
+            .collect()
+    }
+
+    /// Records newer than `since` (unix ms)
+    pub async fn since(&self, since: u64) -> Vec<TradeRecord> {
+        let records = self.records.read().await;
+        records
+            .iter()
+            .filter(|r| r.timestamp >= since)
+            .cloned()
+            .collect()
+    }
+
+    pub async fn pnl(&self, filter: &TradeFilter) -> Pnl {
+        let records = self.records.read().await;
+        let records: Vec<TradeRecord> = records
+            .iter()
+            .filter(|r| filter.matches(r))
+            .cloned()
+            .collect();
+        pnl(&records)
+    }
+
+    pub async fn route_stats(&self, window_ms: u64) -> Vec<RouteStats> {
+        let records = self.since(now_millis().saturating_sub(window_ms)).await;
+        VENUES
+            .iter()
+            .map(|venue| route_stats(*venue, &records))
+            .collect()
+    }
+}
+
+fn average(values: impl Iterator<Item = f64>) -> f64 {
+    let (sum, count) = values.fold((0.0, 0u64), |(sum, count), v| (sum + v, count + 1));
+    if count == 0 {
+        0.0
+    } else {
+        sum / count as f64
+    }
+}
+
+pub fn route_stats(venue: Venue, records: &[TradeRecord]) -> RouteStats {
+    let records: Vec<&TradeRecord> = records.iter().filter(|r| r.venue == venue).collect();
+    let swaps = records.len() as u64;
+    let succeeded: Vec<&&TradeRecord> = records.iter().filter(|r| r.success).collect();
+
+    RouteStats {
+        venue,
+        swaps,
+        succeeded: succeeded.len() as u64,
+        success_rate: if swaps == 0 {
+            0.0
+        } else {
+            succeeded.len() as f64 / swaps as f64
+        },
+        avg_realized_slippage_pct: average(
+            succeeded.iter().filter_map(|r| r.realized_slippage_pct),
+        ),
+        avg_confirmation_ms: average(succeeded.iter().map(|r| r.duration_ms as f64)),
+        avg_price_impact_pct: average(succeeded.iter().filter_map(|r| r.price_impact_pct)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record(venue: Venue, success: bool, duration_ms: u64, slippage: Option<f64>) -> TradeRecord {
+        TradeRecord {
+            id: 0,
+            timestamp: now_millis(),
+            wallet: "wallet".to_string(),
+            mint: "mint".to_string(),
+            venue,
+            direction: SwapDirection::Buy,
+            success,
+            signatures: vec![],
+            error: None,
+            duration_ms,
+            amount_in: Some(100),
+            expected_out: Some(100),
+            price_impact_pct: Some(1.0),
+            amount_out: None,
+            realized_slippage_pct: slippage,
+            sol_price: None,
+            costs: None,
+        }
+    }
+
+    #[test]
+    fn test_route_stats_empty() {
+        let stats = route_stats(Venue::Pump, &[]);
+        assert_eq!(stats.swaps, 0);
+        assert_eq!(stats.success_rate, 0.0);
+        assert_eq!(stats.avg_confirmation_ms, 0.0);
+        assert_eq!(stats.avg_realized_slippage_pct, 0.0);
+    }
+
+    #[test]
+    fn test_route_stats() {
+        let records = vec![
+            record(Venue::Pump, true, 1000, Some(1.0)),
+            record(Venue::Pump, true, 3000, None),
+            record(Venue::Pump, false, 10000, None),
+            record(Venue::Raydium, true, 500, Some(2.0)),
+        ];
+        let stats = route_stats(Venue::Pump, &records);
+        assert_eq!(stats.swaps, 3);
+        assert_eq!(stats.succeeded, 2);
+        assert!((stats.success_rate - 2.0 / 3.0).abs() < f64::EPSILON);
+        assert_eq!(stats.avg_confirmation_ms, 2000.0);
+        assert_eq!(stats.avg_realized_slippage_pct, 1.0);
+        assert_eq!(stats.avg_price_impact_pct, 1.0);
+    }
+
+    #[tokio::test]
+    async fn test_ledger_capacity() {
+        let ledger = Ledger::new(2);
+        for _ in 0..3 {
+            ledger.record(record(Venue::Raydium, true, 1, None)).await;
+        }
+        let records = ledger.since(0).await;
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[0].id, 2);
+    }
+
+    #[tokio::test]
+    async fn test_ledger_page() {
+        let ledger = Ledger::new(10);
+        for success in [true, false, true, true] {
+            ledger.record(record(Venue::Pump, success, 1, None)).await;
+        }
+        let filter = TradeFilter::default();
+        let page = ledger.page(0, 2, |r| filter.matches(r)).await;
+        assert_eq!(page.iter().map(|r| r.id).collect::<Vec<_>>(), vec![1, 3]);
+        let page = ledger.page(3, 2, |r| filter.matches(r)).await;
+        assert_eq!(page.iter().map(|r| r.id).collect::<Vec<_>>(), vec![4]);
+        assert!(ledger.page(4, 2, |r| filter.matches(r)).await.is_empty());
+    }
+
+    #[test]
+    fn test_trade_csv_row() {
+        let mut buy = record(Venue::Pump, true, 1, None);
+        buy.timestamp = 1718000000000;
+        buy.amount_in = Some(500_000_000);
+        buy.expected_out = Some(20_000_000_000);
+        buy.amount_out = Some(19_000_000_000);
+        buy.sol_price = Some(150.0);
+        buy.signatures = vec!["sig".to_string()];
+        assert_eq!(
+            trade_csv_row(&buy, Some(6)),
+            "1718000000000,wallet,mint,buy,0.5,19000,0.000026315789473684212,150,sig\n"
+        );
+        // token amounts are left empty without the decimals
+        assert_eq!(
+            trade_csv_row(&buy, None),
+            "1718000000000,wallet,mint,buy,0.5,,,150,sig\n"
+        );
+
+        let mut sell = buy.clone();
+        sell.direction = SwapDirection::Sell;
+        sell.amount_in = Some(1_000_000);
+        sell.amount_out = None;
+        sell.expected_out = Some(25_000);
+        sell.sol_price = None;
+        assert_eq!(
+            trade_csv_row(&sell, Some(6)),
+            "1718000000000,wallet,mint,sell,1,0.000025,0.000025,,sig\n"
+        );
+        assert_eq!(csv_field("a,\"b\""), "\"a,\"\"b\"\"\"");
+
+        let filter = TradeFilter {
+            from: Some(1718000000000),
+            to: None,
+            mint: Some("other".to_string()),
+        };
+        assert!(!filter.matches(&buy));
+        assert!(TradeFilter::default().matches(&buy));
+        assert!(!TradeFilter::default().matches(&record(Venue::Pump, false, 1, None)));
+    }
+
+    #[test]
+    fn test_pnl() {
+        let mut buy = record(Venue::Pump, true, 1, None);
+        buy.amount_in = Some(1_000_000_000);
+        buy.amount_out = Some(4_000);
+        buy.costs = Some(TxCosts {
+            total: 10_000,
+            ..Default::default()
+        });
+        // sells half at twice the price, the fill is not read yet
+        let mut sell = record(Venue::Pump, true, 1, None);
+        sell.direction = SwapDirection::Sell;
+        sell.amount_in = Some(2_000);
+        sell.expected_out = Some(1_000_000_000);
+        let failed = record(Venue::Pump, false, 1, None);
+        let mut other = buy.clone();
+        other.mint = "other".to_string();
+
+        let pnl = pnl(&[buy, sell, failed, other]);
+        assert_eq!(pnl.mints.len(), 2);
+        let mint = &pnl.mints[0];
+        assert_eq!((mint.mint.as_str(), mint.buys, mint.sells), ("mint", 1, 1));
+        assert_eq!((mint.tokens_bought, mint.tokens_sold), (4_000, 2_000));
+        assert!((mint.realized_pnl - 0.5).abs() < 1e-12);
+        assert!((mint.fees - 0.00001).abs() < 1e-12);
+        assert!((mint.net_pnl - 0.49999).abs() < 1e-12);
+        assert_eq!(mint.costs_pending, 1);
+        // nothing sold yet
+        assert_eq!(pnl.mints[1].realized_pnl, 0.0);
+        assert!((pnl.net_pnl - (0.49999 - 0.00001)).abs() < 1e-12);
+    }
+}
@@ -1,28 +1,43 @@
-use std::{env, sync::Arc};
+use std::{env, sync::Arc, time::Duration};
 
 use anyhow::{anyhow, Result};
 use rand::seq::SliceRandom;
 use reqwest::Proxy;
 use solana_client::nonblocking::rpc_client::RpcClient;
-use solana_sdk::signature::Keypair;
+use solana_sdk::{commitment_config::CommitmentConfig, signature::Keypair};
 use tracing::debug;
 
+use crate::error::{ApiError, ErrorCode};
+
 pub mod api;
+pub mod auth;
+pub mod breaker;
 pub mod constants;
+pub mod copy;
+pub mod cors;
 pub mod daemon;
+pub mod dca;
+pub mod error;
+pub mod extract;
+pub mod filter;
 pub mod helper;
+pub mod idempotency;
 pub mod jito;
+pub mod ledger;
 pub mod logger;
+pub mod meteora;
 pub mod pool;
+pub mod price;
 pub mod pump;
 pub mod raydium;
+pub mod request_id;
+pub mod signal;
+pub mod strategy;
 pub mod swap;
+pub mod telemetry;
 pub mod token;
 pub mod tx;
-
-fn get_env_var(key: &str) -> String {
-    env::var(key).unwrap_or_else(|_| panic!("Environment variable {} is not set", key))
-}
+pub mod webhook;
 
 pub fn get_client_build() -> Result<reqwest::Client> {
     let mut client_builder = reqwest::Client::builder();
@@ -37,7 +52,11 @@ pub fn get_client_build() -> Result<reqwest::Client> {
 }
 
 pub fn get_random_rpc_url() -> Result<String> {
-    let cluster_urls = env::var("RPC_ENDPOINTS")?
+    // the public endpoint of NETWORK when unset or empty
+    let cluster_urls = env::var("RPC_ENDPOINTS")
+        .ok()
+        .filter(|urls| !urls.trim().is_empty())
+        .unwrap_or_else(|| constants::get_network().default_rpc_url().to_string())
         .split(",")
         .map(|s| s.trim().to_string())
         .collect::<Vec<String>>();
@@ -50,15 +69,56 @@ pub fn get_random_rpc_url() -> Result<String> {
     return Ok(random_url);
 }
 
+pub fn get_random_ws_url() -> Result<String> {
+    let ws_urls = env::var("RPC_WEBSOCKET_ENDPOINTS")
+        .ok()
+        .filter(|urls| !urls.trim().is_empty())
+        .unwrap_or_else(|| constants::get_network().default_ws_url().to_string())
+        .split(",")
+        .map(|s| s.trim().to_string())
+        .collect::<Vec<String>>();
+    let random_url = ws_urls
+        .choose(&mut rand::thread_rng())
+        .ok_or(anyhow!("No RPC websocket endpoints configured"))?
+        .clone();
+
+    debug!("Choose rpc websocket: {}", random_url);
+    Ok(random_url)
+}
+
+// per request deadline, a hung rpc connection would otherwise block forever
+fn get_rpc_timeout() -> Duration {
+    let timeout_ms = env::var("RPC_TIMEOUT_MS")
+        .ok()
+        .and_then(|v| v.parse::<u64>().ok())
+        .unwrap_or(30_000);
+    Duration::from_millis(timeout_ms)
+}
+
 pub fn get_rpc_client() -> Result<Arc<RpcClient>> {
+    get_rpc_client_with_commitment(CommitmentConfig::confirmed())
+}
+
+pub fn get_rpc_client_with_commitment(commitment: CommitmentConfig) -> Result<Arc<RpcClient>> {
     let random_url = get_random_rpc_url()?;
-    let client = RpcClient::new(random_url);
+    let client =
+        RpcClient::new_with_timeout_and_commitment(random_url, get_rpc_timeout(), commitment);
     return Ok(Arc::new(client));
 }
 
 pub fn get_rpc_client_blocking() -> Result<Arc<solana_client::rpc_client::RpcClient>> {
+    get_rpc_client_blocking_with_commitment(CommitmentConfig::confirmed())
+}
+
+pub fn get_rpc_client_blocking_with_commitment(
+    commitment: CommitmentConfig,
+) -> Result<Arc<solana_client::rpc_client::RpcClient>> {
     let random_url = get_random_rpc_url()?;
-    let client = solana_client::rpc_client::RpcClient::new(random_url);
+    let client = solana_client::rpc_client::RpcClient::new_with_timeout_and_commitment(
+        random_url,
+        get_rpc_timeout(),
+        commitment,
+    );
     return Ok(Arc::new(client));
 }
 
@@ -67,11 +127,71 @@ pub fn get_wallet() -> Result<Arc<Keypair>> {
     return Ok(Arc::new(wallet));
 }
 
+/// Keypair stored at `path`, as the json byte array of `solana-keygen` or a base58 string
+pub fn read_wallet_file(path: &str) -> Result<Keypair> {
+    let content = std::fs::read_to_string(path).map_err(|e| {
+        ApiError::new(
+            ErrorCode::InvalidInput,
+            format!("failed to read keypair file {}: {}", path, e),
+        )
+    })?;
+    let content = content.trim();
+    let bytes = if content.starts_with('[') {
+        serde_json::from_str::<Vec<u8>>(content).map_err(|e| {
+            ApiError::new(
+                ErrorCode::InvalidInput,
+                format!("malformed keypair json in {}: {}", path, e),
+            )
+        })?
+    } else {
+        solana_sdk::bs58::decode(content).into_vec().map_err(|e| {
+            ApiError::new(
+                ErrorCode::InvalidInput,
+                format!("malformed base58 keypair in {}: {}", path, e),
+            )
+        })?
+    };
+    Keypair::from_bytes(&bytes).map_err(|e| {
+        ApiError::new(
+            ErrorCode::InvalidInput,
+            format!("invalid keypair in {}: {}", path, e),
+        )
+        .into()
+    })
+}
+
 #[cfg(test)]
 mod tests {
+    use solana_sdk::signer::Signer;
+
+    use super::*;
+
     #[ctor::ctor]
     fn init() {
         crate::logger::init();
         dotenvy::dotenv().ok();
     }
+
+    #[test]
+    fn test_read_wallet_file() {
+        let keypair = Keypair::new();
+        let dir = env::temp_dir();
+        let json_path = dir.join(format!("raytx-{}.json", keypair.pubkey()));
+        let base58_path = dir.join(format!("raytx-{}.txt", keypair.pubkey()));
+        std::fs::write(&json_path, format!("{:?}", keypair.to_bytes().to_vec())).unwrap();
+        std::fs::write(&base58_path, keypair.to_base58_string()).unwrap();
+
+        for path in [&json_path, &base58_path] {
+            let loaded = read_wallet_file(path.to_str().unwrap()).unwrap();
+            assert_eq!(loaded.pubkey(), keypair.pubkey());
+        }
+
+        std::fs::write(&json_path, "[1, 2, 3]").unwrap();
+        let err = read_wallet_file(json_path.to_str().unwrap()).unwrap_err();
+        assert_eq!(ApiError::from(&err).code, ErrorCode::InvalidInput);
+        assert!(read_wallet_file("/nonexistent/raytx.json").is_err());
+
+        std::fs::remove_file(json_path).ok();
+        std::fs::remove_file(base58_path).ok();
+    }
 }
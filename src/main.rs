@@ -1,17 +1,21 @@
 use anyhow::Result;
-mod signal;
 use clap::{ArgGroup, Parser, Subcommand};
 use raytx::{
     api::AppState,
-    daemon, get_rpc_client, get_rpc_client_blocking, get_wallet, jito, logger,
+    constants, daemon, get_rpc_client, get_rpc_client_blocking, get_wallet, jito, logger,
     raydium::get_pool_info,
-    swap::{self, SwapDirection, SwapInType},
+    signal::{Signal, SignalSet},
+    swap::{self, Slippage, SwapDirection, SwapInType, SwapParams},
     token,
+    tx::ConfirmMode,
 };
 use std::{env, str::FromStr};
 use tracing::{debug, info};
 
-use solana_sdk::{pubkey::Pubkey, signature::Signer};
+use solana_sdk::{
+    commitment_config::CommitmentConfig, native_token::sol_to_lamports, pubkey::Pubkey,
+    signature::Signer,
+};
 
 #[derive(Parser)]
 #[command(name = "raytx", version, about, long_about = None)]
@@ -38,6 +42,19 @@ enum Command {
         amount_in_pct: Option<f64>,
         #[arg(long, help = "use jito to swap", default_value_t = false)]
         jito: bool,
+        #[arg(
+            long,
+            help = "allow buys above MAX_SOL_PER_SWAP",
+            default_value_t = false
+        )]
+        confirm_large: bool,
+        #[arg(
+            long,
+            help = "min sol received by a pump.fun sell, replaces the slippage"
+        )]
+        min_sol_out: Option<f64>,
+        #[arg(long, help = "raydium pool id, skips pool discovery")]
+        pool_id: Option<String>,
     },
     Daemon {
         #[arg(
@@ -82,14 +99,12 @@ async fn main() -> Result<()> {
     }
     let cli = Cli::parse();
     logger::init();
+    let network = constants::get_network();
+    info!("network: {}", network.as_str());
     let client = get_rpc_client()?;
     let client_blocking = get_rpc_client_blocking()?;
     let wallet = get_wallet()?;
-    let app_state = AppState {
-        client,
-        client_blocking,
-        wallet,
-    };
+    let app_state = AppState::new(client, client_blocking, wallet);
 
     match &cli.command {
         Some(Command::Swap {
@@ -98,6 +113,9 @@ async fn main() -> Result<()> {
             amount_in,
             amount_in_pct,
             jito,
+            confirm_large,
+            min_sol_out,
+            pool_id,
         }) => {
             let (amount_in, in_type) = if let Some(amount_in) = amount_in {
                 (amount_in, SwapInType::Qty)
@@ -114,6 +132,7 @@ async fn main() -> Result<()> {
             );
             // jito
             if *jito {
+                jito::check_network()?;
                 jito::init_tip_accounts()
                     .await
                     .map_err(|err| {
@@ -132,12 +151,23 @@ async fn main() -> Result<()> {
 
             swap::swap(
                 app_state,
-                mint,
-                *amount_in,
-                direction.clone(),
-                in_type,
-                slippage,
-                *jito,
+                SwapParams {
+                    mint: mint.clone(),
+                    direction: direction.clone(),
+                    amount_in: *amount_in,
+                    amount_in_usd: None,
+                    commitment: CommitmentConfig::confirmed(),
+                    in_type,
+                    slippage: Slippage::Fixed(slippage),
+                    use_jito: *jito,
+                    confirm_large: *confirm_large,
+                    pool_selection: swap::get_pool_selection_policy(),
+                    webhook_url: None,
+                    min_sol_out: min_sol_out.map(sol_to_lamports),
+                    pool_id: pool_id.clone(),
+                    confirm_mode: ConfirmMode::Sync,
+                    auto_bump_slippage: false,
+                },
             )
             .await?;
         }
@@ -147,13 +177,13 @@ async fn main() -> Result<()> {
         Some(Command::Token(token_command)) => match token_command {
             TokenCommand::List => {
                 let token_accounts =
-                    token::token_accounts(&app_state.client, &app_state.wallet.pubkey()).await;
+                    token::token_accounts(&app_state.client, &app_state.wallet().pubkey()).await;
                 info!("token_accounts: {:#?}", token_accounts);
             }
             TokenCommand::Show { mint } => {
                 let mint = Pubkey::from_str(mint).expect("failed to parse mint pubkey");
                 let token_account =
-                    token::token_account(&app_state.client, &app_state.wallet.pubkey(), mint)
+                    token::token_account(&app_state.client, &app_state.wallet().pubkey(), mint)
                         .await?;
                 info!("token_account: {:#?}", token_account);
                 let pool_info = get_pool_info(
@@ -166,8 +196,10 @@ async fn main() -> Result<()> {
             }
         },
         Some(Command::Signal { name, value }) => {
-            let signal = signal::Signal::new(name, *value);
-            signal::process_signal(&signal);
+            let signals = SignalSet {
+                signals: vec![Signal::new(name, *value)],
+            };
+            info!("signal {}: {:?}", name, signals.decision());
         }
         _ => {}
     }
@@ -0,0 +1,856 @@
+use std::{str::FromStr, sync::Arc};
+
+use anyhow::{anyhow, Result};
+use common::common_utils;
+use serde::Serialize;
+use solana_client::{
+    nonblocking::rpc_client::RpcClient,
+    rpc_filter::{Memcmp, RpcFilterType},
+};
+use solana_sdk::{
+    instruction::{AccountMeta, Instruction},
+    program_pack::Pack,
+    pubkey::Pubkey,
+    signature::Keypair,
+    signer::Signer,
+    system_instruction,
+};
+use spl_associated_token_account::{
+    get_associated_token_address, instruction::create_associated_token_account_idempotent,
+};
+use spl_token::{amount_to_ui_amount, state::Account};
+use tracing::{debug, info};
+
+use crate::{
+    error::{ApiError, ErrorCode},
+    helper::anchor_discriminator,
+    pump::TEN_THOUSAND,
+    swap::{
+        check_pool_liquidity, check_quote, check_route, pct_of, price_impact_pct, to_base_units,
+        Slippage, SwapDirection, SwapInType, SwapOutcome,
+    },
+    token::{self, DecimalsCache},
+    tx,
+};
+
+pub const DLMM_PROGRAM: &str = "LBUZKhRxPF3XUpBCjp4YzTKgLccjZhTSDM9YuVaPwxo";
+
+const MAX_BIN_PER_ARRAY: i64 = 70;
+// bin array indexes covered by the bitmap of the pair, beyond it the extension account is needed
+const BIN_ARRAY_BITMAP_SIZE: i64 = 512;
+// bin arrays probed past the active one in the swap direction, the first initialized ones are used
+const SWAP_BIN_ARRAYS_PROBE: i64 = 6;
+const SWAP_BIN_ARRAYS: usize = 3;
+const FEE_PRECISION: u128 = 1_000_000_000;
+// bin prices are Q64.64
+const SCALE_OFFSET: u32 = 64;
+
+// LbPair field offsets, after the 8 bytes anchor discriminator
+const LB_PAIR_BASE_FACTOR: usize = 8;
+const LB_PAIR_ACTIVE_ID: usize = 76;
+const LB_PAIR_BIN_STEP: usize = 80;
+const LB_PAIR_STATUS: usize = 82;
+const LB_PAIR_TOKEN_X_MINT: usize = 88;
+const LB_PAIR_TOKEN_Y_MINT: usize = 120;
+const LB_PAIR_RESERVE_X: usize = 152;
+const LB_PAIR_RESERVE_Y: usize = 184;
+const LB_PAIR_ORACLE: usize = 552;
+const LB_PAIR_MIN_LEN: usize = 584;
+
+// BinArray: discriminator, index i64, version u8, padding [u8; 7], lb_pair, bins
+const BIN_ARRAY_BINS: usize = 56;
+// amount_x u64, amount_y u64, price u128, then liquidity and fee fields
+const BIN_LEN: usize = 144;
+
+fn read_u16(data: &[u8], offset: usize) -> u16 {
+    u16::from_le_bytes(data[offset..offset + 2].try_into().unwrap())
+}
+
+fn read_u64(data: &[u8], offset: usize) -> u64 {
+    u64::from_le_bytes(data[offset..offset + 8].try_into().unwrap())
+}
+
+fn read_pubkey(data: &[u8], offset: usize) -> Pubkey {
+    Pubkey::new_from_array(data[offset..offset + 32].try_into().unwrap())
+}
+
+/// The fields of a DLMM pair account a swap needs
+#[derive(Debug, Clone, PartialEq)]
+pub struct LbPair {
+    pub active_id: i32,
+    pub bin_step: u16,
+    pub base_factor: u16,
+    /// 0 is enabled
+    pub status: u8,
+    pub token_x_mint: Pubkey,
+    pub token_y_mint: Pubkey,
+    pub reserve_x: Pubkey,
+    pub reserve_y: Pubkey,
+    pub oracle: Pubkey,
+}
+
+impl LbPair {
+    pub fn from_account_data(data: &[u8]) -> Result<Self> {
+        if data.len() < LB_PAIR_MIN_LEN || data[..8] != anchor_discriminator("account:LbPair") {
+            return Err(
+                ApiError::new(ErrorCode::InvalidInput, "not a meteora dlmm pair account").into(),
+            );
+        }
+        Ok(Self {
+            active_id: i32::from_le_bytes(
+                data[LB_PAIR_ACTIVE_ID..LB_PAIR_ACTIVE_ID + 4]
+                    .try_into()
+                    .unwrap(),
+            ),
+            bin_step: read_u16(data, LB_PAIR_BIN_STEP),
+            base_factor: read_u16(data, LB_PAIR_BASE_FACTOR),
+            status: data[LB_PAIR_STATUS],
+            token_x_mint: read_pubkey(data, LB_PAIR_TOKEN_X_MINT),
+            token_y_mint: read_pubkey(data, LB_PAIR_TOKEN_Y_MINT),
+            reserve_x: read_pubkey(data, LB_PAIR_RESERVE_X),
+            reserve_y: read_pubkey(data, LB_PAIR_RESERVE_Y),
+            oracle: read_pubkey(data, LB_PAIR_ORACLE),
+        })
+    }
+
+    /// Base fee with 1e9 precision, the variable fee is left to the slippage
+    pub fn base_fee_rate(&self) -> u128 {
+        self.base_factor as u128 * self.bin_step as u128 * 10
+    }
+
+    /// Raw amount of y per raw x at the active bin
+    pub fn active_price(&self) -> f64 {
+        (1.0 + self.bin_step as f64 / TEN_THOUSAND as f64).powi(self.active_id)
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Bin {
+    pub amount_x: u64,
+    pub amount_y: u64,
+    pub price: u128,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct BinArray {
+    pub index: i64,
+    pub bins: Vec<Bin>,
+}
+
+impl BinArray {
+    pub fn from_account_data(data: &[u8]) -> Result<Self> {
+        let bins_len = BIN_ARRAY_BINS + BIN_LEN * MAX_BIN_PER_ARRAY as usize;
+        if data.len() < bins_len || data[..8] != anchor_discriminator("account:BinArray") {
+            return Err(ApiError::new(
+                ErrorCode::InvalidInput,
+                "not a meteora dlmm bin array account",
+            )
+            .into());
+        }
+        let bins = (0..MAX_BIN_PER_ARRAY as usize)
+            .map(|i| {
+                let offset = BIN_ARRAY_BINS + i * BIN_LEN;
+                Bin {
+                    amount_x: read_u64(data, offset),
+                    amount_y: read_u64(data, offset + 8),
+                    price: u128::from_le_bytes(data[offset + 16..offset + 32].try_into().unwrap()),
+                }
+            })
+            .collect();
+        Ok(Self {
+            index: read_u64(data, 8) as i64,
+            bins,
+        })
+    }
+
+    fn bin(&self, bin_id: i32) -> Option<&Bin> {
+        let offset = bin_id as i64 - self.index * MAX_BIN_PER_ARRAY;
+        if (0..MAX_BIN_PER_ARRAY).contains(&offset) {
+            self.bins.get(offset as usize)
+        } else {
+            None
+        }
+    }
+}
+
+/// Index of the bin array holding `bin_id`
+pub fn bin_array_index(bin_id: i32) -> i64 {
+    (bin_id as i64).div_euclid(MAX_BIN_PER_ARRAY)
+}
+
+pub fn bin_array_pda(lb_pair: &Pubkey, index: i64, program_id: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(
+        &[b"bin_array", lb_pair.as_ref(), &index.to_le_bytes()],
+        program_id,
+    )
+    .0
+}
+
+// (x * price) >> 64 without overflowing on prices above 1
+fn mul_shr(x: u64, price: u128) -> u128 {
+    let x = x as u128;
+    let high = price >> SCALE_OFFSET;
+    let low = price & u64::MAX as u128;
+    x.saturating_mul(high)
+        .saturating_add((x * low) >> SCALE_OFFSET)
+}
+
+// (x << 64) / price rounded up
+fn shl_div_ceil(x: u64, price: u128) -> u128 {
+    ((x as u128) << SCALE_OFFSET).div_ceil(price)
+}
+
+/// Output of swapping `amount_in` through the bins of `bin_arrays`, starting at the active bin
+///
+/// `swap_for_y` walks down the bins selling x for y, otherwise up selling y for x.
+pub fn quote(
+    pair_id: &Pubkey,
+    pair: &LbPair,
+    bin_arrays: &[BinArray],
+    amount_in: u64,
+    swap_for_y: bool,
+) -> Result<u64> {
+    let fee = (amount_in as u128 * pair.base_fee_rate()).div_ceil(FEE_PRECISION) as u64;
+    let mut remaining = amount_in.saturating_sub(fee);
+    let mut amount_out: u128 = 0;
+    let mut bin_id = pair.active_id;
+    while remaining > 0 {
+        let Some(bin) = bin_arrays
+            .iter()
+            .find_map(|bin_array| bin_array.bin(bin_id))
+        else {
+            break;
+        };
+        let available = if swap_for_y {
+            bin.amount_y
+        } else {
+            bin.amount_x
+        };
+        if bin.price > 0 && available > 0 {
+            let out = if swap_for_y {
+                mul_shr(remaining, bin.price)
+            } else {
+                ((remaining as u128) << SCALE_OFFSET) / bin.price
+            };
+            if out <= available as u128 {
+                amount_out += out;
+                remaining = 0;
+                break;
+            }
+            // drain the bin and move on
+            let used = if swap_for_y {
+                shl_div_ceil(available, bin.price)
+            } else {
+                mul_shr(available, bin.price) + 1
+            };
+            amount_out += available as u128;
+            remaining = remaining.saturating_sub(used.min(u64::MAX as u128) as u64);
+        }
+        bin_id += if swap_for_y { -1 } else { 1 };
+    }
+    if remaining > 0 {
+        return Err(ApiError::new(
+            ErrorCode::InsufficientLiquidity,
+            format!(
+                "not enough liquidity in the loaded bins of meteora pair {}, {} left unswapped",
+                pair_id, remaining
+            ),
+        )
+        .into());
+    }
+    Ok(amount_out.min(u64::MAX as u128) as u64)
+}
+
+/// The swap `Meteora::swap` would send, also what validation reports
+#[derive(Debug, Clone)]
+pub struct MeteoraQuote {
+    pub pair_id: Pubkey,
+    pub pair: LbPair,
+    /// input token is token x
+    pub swap_for_y: bool,
+    pub reserve_in: u64,
+    pub slippage_bps: u64,
+    pub expected_out: u64,
+    pub min_out: u64,
+    /// initialized bin arrays the swap may cross, in swap order
+    pub bin_arrays: Vec<Pubkey>,
+    pub bitmap_extension: Option<Pubkey>,
+}
+
+pub async fn quote_swap(
+    rpc_client: Arc<solana_client::rpc_client::RpcClient>,
+    pair_id: Option<&str>,
+    mint: &str,
+    amount_in: u64,
+    swap_direction: &SwapDirection,
+    slippage: Slippage,
+) -> Result<MeteoraQuote> {
+    let program_id = Pubkey::from_str(DLMM_PROGRAM)?;
+    let (pair_id, pair) = get_pair_state(rpc_client.clone(), pair_id, Some(mint)).await?;
+    if pair.status != 0 {
+        return Err(ApiError::new(
+            ErrorCode::PoolPaused,
+            format!(
+                "meteora pair {} is disabled, status {}",
+                pair_id, pair.status
+            ),
+        )
+        .into());
+    }
+    let native_mint = spl_token::native_mint::ID;
+    let swap_for_y = match swap_direction {
+        SwapDirection::Buy => pair.token_x_mint == native_mint,
+        SwapDirection::Sell => pair.token_x_mint != native_mint,
+    };
+
+    let active_index = bin_array_index(pair.active_id);
+    let step = if swap_for_y { -1 } else { 1 };
+    let indexes: Vec<i64> = (0..SWAP_BIN_ARRAYS_PROBE)
+        .map(|i| active_index + i * step)
+        .collect();
+    let mut load_pubkeys = vec![pair.reserve_x, pair.reserve_y];
+    load_pubkeys.extend(
+        indexes
+            .iter()
+            .map(|index| bin_array_pda(&pair_id, *index, &program_id)),
+    );
+    let accounts = rpc_client.get_multiple_accounts(&load_pubkeys)?;
+    let reserve_amount = |account: &Option<solana_sdk::account::Account>| -> Result<u64> {
+        let account = account.as_ref().ok_or_else(|| {
+            ApiError::new(
+                ErrorCode::PoolNotFound,
+                format!("meteora pair {} reserve not found", pair_id),
+            )
+        })?;
+        Ok(common_utils::unpack_token(&account.data)?.base.amount)
+    };
+    let (reserve_x, reserve_y) = (reserve_amount(&accounts[0])?, reserve_amount(&accounts[1])?);
+    let (reserve_in, reserve_out) = if swap_for_y {
+        (reserve_x, reserve_y)
+    } else {
+        (reserve_y, reserve_x)
+    };
+    check_route(&pair_id, reserve_in, reserve_out)?;
+    let mut bin_arrays = vec![];
+    let mut bin_array_pubkeys = vec![];
+    for (pubkey, account) in load_pubkeys[2..].iter().zip(&accounts[2..]) {
+        if let Some(account) = account {
+            bin_arrays.push(BinArray::from_account_data(&account.data)?);
+            bin_array_pubkeys.push(*pubkey);
+        }
+        if bin_arrays.len() == SWAP_BIN_ARRAYS {
+            break;
+        }
+    }
+    debug!(
+        "meteora pair {} active_id {}, bin arrays: {:?}",
+        pair_id, pair.active_id, bin_array_pubkeys
+    );
+    if bin_arrays.is_empty() {
+        return Err(ApiError::new(
+            ErrorCode::NoRoute,
+            format!(
+                "meteora pair {} has no liquidity around the active bin",
+                pair_id
+            ),
+        )
+        .into());
+    }
+    let bitmap_extension = indexes
+        .iter()
+        .any(|index| !(-BIN_ARRAY_BITMAP_SIZE..BIN_ARRAY_BITMAP_SIZE).contains(index))
+        .then(|| Pubkey::find_program_address(&[b"bitmap", pair_id.as_ref()], &program_id).0);
+
+    let slippage_bps = slippage.resolve_bps(amount_in, reserve_in);
+    let expected_out = quote(&pair_id, &pair, &bin_arrays, amount_in, swap_for_y)?;
+    check_quote(&pair_id, expected_out)?;
+    let min_out = (expected_out as u128 * (TEN_THOUSAND - slippage_bps.min(TEN_THOUSAND)) as u128
+        / TEN_THOUSAND as u128) as u64;
+    Ok(MeteoraQuote {
+        pair_id,
+        pair,
+        swap_for_y,
+        reserve_in,
+        slippage_bps,
+        expected_out,
+        min_out,
+        bin_arrays: bin_array_pubkeys,
+        bitmap_extension,
+    })
+}
+
+pub fn dlmm_swap(
+    quote: &MeteoraQuote,
+    owner: &Pubkey,
+    user_token_in: &Pubkey,
+    user_token_out: &Pubkey,
+    amount_in: u64,
+) -> Result<Instruction> {
+    let program_id = Pubkey::from_str(DLMM_PROGRAM)?;
+    let event_authority = Pubkey::find_program_address(&[b"__event_authority"], &program_id).0;
+    let pair = &quote.pair;
+    let mut accounts = vec![
+        AccountMeta::new(quote.pair_id, false),
+        // optional accounts are passed as the program id when missing
+        AccountMeta::new_readonly(quote.bitmap_extension.unwrap_or(program_id), false),
+        AccountMeta::new(pair.reserve_x, false),
+        AccountMeta::new(pair.reserve_y, false),
+        AccountMeta::new(*user_token_in, false),
+        AccountMeta::new(*user_token_out, false),
+        AccountMeta::new_readonly(pair.token_x_mint, false),
+        AccountMeta::new_readonly(pair.token_y_mint, false),
+        AccountMeta::new(pair.oracle, false),
+        // host fee account
+        AccountMeta::new_readonly(program_id, false),
+        AccountMeta::new_readonly(*owner, true),
+        AccountMeta::new_readonly(spl_token::ID, false),
+        AccountMeta::new_readonly(spl_token::ID, false),
+        AccountMeta::new_readonly(event_authority, false),
+        AccountMeta::new_readonly(program_id, false),
+    ];
+    accounts.extend(
+        quote
+            .bin_arrays
+            .iter()
+            .map(|bin_array| AccountMeta::new(*bin_array, false)),
+    );
+    let mut data = anchor_discriminator("global:swap").to_vec();
+    data.extend_from_slice(&amount_in.to_le_bytes());
+    data.extend_from_slice(&quote.min_out.to_le_bytes());
+    Ok(Instruction {
+        program_id,
+        accounts,
+        data,
+    })
+}
+
+pub async fn get_pair_state(
+    rpc_client: Arc<solana_client::rpc_client::RpcClient>,
+    pair_id: Option<&str>,
+    mint: Option<&str>,
+) -> Result<(Pubkey, LbPair)> {
+    if let Some(pair_id) = pair_id {
+        let pair_pubkey = Pubkey::from_str(pair_id).map_err(|e| {
+            ApiError::new(
+                ErrorCode::InvalidInput,
+                format!("invalid pair id {}: {}", pair_id, e),
+            )
+        })?;
+        let account = rpc_client
+            .get_account_with_commitment(&pair_pubkey, rpc_client.commitment())?
+            .value
+            .ok_or_else(|| {
+                ApiError::new(
+                    ErrorCode::PoolNotFound,
+                    format!("meteora pair {} not found", pair_pubkey),
+                )
+            })?;
+        return Ok((pair_pubkey, LbPair::from_account_data(&account.data)?));
+    }
+    let mint =
+        mint.ok_or_else(|| ApiError::new(ErrorCode::InvalidInput, "pair id or mint is required"))?;
+    let pairs = find_pairs_by_mint(rpc_client.clone(), mint).await?;
+    if pairs.len() <= 1 {
+        return pairs.into_iter().next().ok_or_else(|| pair_not_found(mint));
+    }
+    // the deepest sol reserve
+    let native_mint = spl_token::native_mint::ID;
+    let sol_reserves: Vec<Pubkey> = pairs
+        .iter()
+        .map(|(_, pair)| {
+            if pair.token_x_mint == native_mint {
+                pair.reserve_x
+            } else {
+                pair.reserve_y
+            }
+        })
+        .collect();
+    let accounts = rpc_client.get_multiple_accounts(&sol_reserves)?;
+    let liquidity = |account: &Option<solana_sdk::account::Account>| {
+        account
+            .as_ref()
+            .and_then(|account| common_utils::unpack_token(&account.data).ok())
+            .map_or(0, |token| token.base.amount)
+    };
+    pairs
+        .into_iter()
+        .zip(accounts.iter().map(liquidity))
+        .max_by_key(|(_, liquidity)| *liquidity)
+        .map(|(pair, _)| pair)
+        .ok_or_else(|| pair_not_found(mint))
+}
+
+/// `PoolNotFound` for a mint without a sol dlmm pair
+pub fn pair_not_found(mint: &str) -> anyhow::Error {
+    ApiError::new(
+        ErrorCode::PoolNotFound,
+        format!("no meteora dlmm pair pairs {} with WSOL", mint),
+    )
+    .into()
+}
+
+/// All DLMM pairs of `mint` against sol, in both x/y orders
+pub async fn find_pairs_by_mint(
+    rpc_client: Arc<solana_client::rpc_client::RpcClient>,
+    mint: &str,
+) -> Result<Vec<(Pubkey, LbPair)>> {
+    debug!("finding meteora pairs by mint: {}", mint);
+    let mint = Pubkey::from_str(mint)?;
+    let program_id = Pubkey::from_str(DLMM_PROGRAM)?;
+    let discriminator = anchor_discriminator("account:LbPair");
+    // (token_x_mint, token_y_mint)
+    let pairs = vec![
+        (mint, spl_token::native_mint::ID),
+        (spl_token::native_mint::ID, mint),
+    ];
+    let mut found_pairs = vec![];
+    for (token_x_mint, token_y_mint) in pairs {
+        let filters = Some(vec![
+            RpcFilterType::Memcmp(Memcmp::new_base58_encoded(0, &discriminator)),
+            RpcFilterType::Memcmp(Memcmp::new_base58_encoded(
+                LB_PAIR_TOKEN_X_MINT,
+                &token_x_mint.to_bytes(),
+            )),
+            RpcFilterType::Memcmp(Memcmp::new_base58_encoded(
+                LB_PAIR_TOKEN_Y_MINT,
+                &token_y_mint.to_bytes(),
+            )),
+        ]);
+        let accounts =
+            common::rpc::get_program_accounts_with_filters(&rpc_client, program_id, filters)?;
+        for (pair_id, account) in accounts {
+            found_pairs.push((pair_id, LbPair::from_account_data(&account.data)?));
+        }
+    }
+    Ok(found_pairs)
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct MeteoraPoolPrice {
+    pub pair_id: String,
+    pub active_id: i32,
+    pub bin_step: u16,
+    /// ui amounts held by the pair
+    pub token_reserve: f64,
+    pub sol_reserve: f64,
+    /// sol per token at the active bin
+    pub price: f64,
+}
+
+pub struct Meteora {
+    pub client: Arc<RpcClient>,
+    pub keypair: Arc<Keypair>,
+    pub client_blocking: Option<Arc<solana_client::rpc_client::RpcClient>>,
+    pub pool_id: Option<String>,
+    pub decimals_cache: Arc<DecimalsCache>,
+}
+
+impl Meteora {
+    pub fn new(client: Arc<RpcClient>, keypair: Arc<Keypair>) -> Self {
+        Self {
+            client,
+            keypair,
+            client_blocking: None,
+            pool_id: None,
+            decimals_cache: Arc::new(DecimalsCache::default()),
+        }
+    }
+
+    pub fn with_decimals_cache(&mut self, decimals_cache: Arc<DecimalsCache>) -> &mut Self {
+        self.decimals_cache = decimals_cache;
+        self
+    }
+
+    pub fn with_blocking_client(
+        &mut self,
+        client: Arc<solana_client::rpc_client::RpcClient>,
+    ) -> &mut Self {
+        self.client_blocking = Some(client);
+        self
+    }
+
+    pub fn with_pool_id(&mut self, pool_id: Option<String>) -> &mut Self {
+        self.pool_id = pool_id;
+        self
+    }
+
+    fn blocking_client(&self) -> Result<Arc<solana_client::rpc_client::RpcClient>> {
+        self.client_blocking
+            .clone()
+            .ok_or(anyhow!("meteora needs a blocking rpc client"))
+    }
+
+    /// Active bin price of the pair, by pair id or the deepest sol pair of the mint
+    pub async fn get_pool_price(
+        &self,
+        pool_id: Option<&str>,
+        mint: Option<&str>,
+    ) -> Result<MeteoraPoolPrice> {
+        let client_blocking = self.blocking_client()?;
+        let (pair_id, pair) = get_pair_state(client_blocking.clone(), pool_id, mint).await?;
+        let native_mint = spl_token::native_mint::ID;
+        let sol_is_x = pair.token_x_mint == native_mint;
+        let token_mint = if sol_is_x {
+            pair.token_y_mint
+        } else {
+            pair.token_x_mint
+        };
+        let token_decimals = self.decimals_cache.get(&self.client, &token_mint).await?;
+        let sol_decimals = spl_token::native_mint::DECIMALS;
+        let accounts = client_blocking.get_multiple_accounts(&[pair.reserve_x, pair.reserve_y])?;
+        let amounts: Vec<u64> = accounts
+            .iter()
+            .map(|account| {
+                account
+                    .as_ref()
+                    .and_then(|account| common_utils::unpack_token(&account.data).ok())
+                    .map_or(0, |token| token.base.amount)
+            })
+            .collect();
+        let (decimals_x, decimals_y) = if sol_is_x {
+            (sol_decimals, token_decimals)
+        } else {
+            (token_decimals, sol_decimals)
+        };
+        // ui y per ui x
+        let price_xy = pair.active_price() * 10f64.powi(decimals_x as i32 - decimals_y as i32);
+        let (price, token_reserve, sol_reserve) = if sol_is_x {
+            (1.0 / price_xy, amounts[1], amounts[0])
+        } else {
+            (price_xy, amounts[0], amounts[1])
+        };
+        Ok(MeteoraPoolPrice {
+            pair_id: pair_id.to_string(),
+            active_id: pair.active_id,
+            bin_step: pair.bin_step,
+            token_reserve: amount_to_ui_amount(token_reserve, token_decimals),
+            sol_reserve: amount_to_ui_amount(sol_reserve, sol_decimals),
+            price,
+        })
+    }
+
+    pub async fn swap(
+        &self,
+        mint_str: &str,
+        amount_in: f64,
+        swap_direction: SwapDirection,
+        in_type: SwapInType,
+        slippage: Slippage,
+        use_jito: bool,
+    ) -> Result<SwapOutcome> {
+        let owner = self.keypair.pubkey();
+        let mint = Pubkey::from_str(mint_str)
+            .map_err(|e| anyhow!("failed to parse mint pubkey: {}", e))?;
+        let program_id = spl_token::ID;
+        let native_mint = spl_token::native_mint::ID;
+        let client = self.blocking_client()?;
+
+        let (token_in, token_out) = match swap_direction {
+            SwapDirection::Buy => (native_mint, mint),
+            SwapDirection::Sell => (mint, native_mint),
+        };
+        let in_ata = get_associated_token_address(&owner, &token_in);
+        let out_ata = get_associated_token_address(&owner, &token_out);
+
+        let mut create_instruction = None;
+        let mut close_instruction = None;
+
+        let (amount_specified, amount_ui_pretty) = match swap_direction {
+            SwapDirection::Buy => {
+                // the idempotent variant is a no-op if the ata already exists
+                create_instruction = Some(create_associated_token_account_idempotent(
+                    &owner,
+                    &owner,
+                    &token_out,
+                    &program_id,
+                ));
+                (
+                    to_base_units(amount_in, spl_token::native_mint::DECIMALS)?,
+                    (amount_in, spl_token::native_mint::DECIMALS),
+                )
+            }
+            SwapDirection::Sell => {
+                let in_account = token::get_account_info(
+                    self.client.clone(),
+                    self.keypair.clone(),
+                    &token_in,
+                    &in_ata,
+                )
+                .await?;
+                let decimals = self.decimals_cache.get(&self.client, &token_in).await?;
+                let amount = match in_type {
+                    SwapInType::Qty => to_base_units(amount_in, decimals)?,
+                    SwapInType::Pct => {
+                        let amount_in_pct = amount_in.min(1.0);
+                        if amount_in_pct == 1.0 {
+                            // sell all, close ata
+                            info!("sell all. will be close ATA for mint {}", token_in);
+                            close_instruction = Some(spl_token::instruction::close_account(
+                                &program_id,
+                                &in_ata,
+                                &owner,
+                                &owner,
+                                &vec![&owner],
+                            )?);
+                            in_account.base.amount
+                        } else {
+                            pct_of(in_account.base.amount, amount_in_pct)?
+                        }
+                    }
+                };
+                (amount, (amount_to_ui_amount(amount, decimals), decimals))
+            }
+        };
+        if amount_specified == 0 {
+            return Err(ApiError::new(ErrorCode::InvalidInput, "nothing to swap").into());
+        }
+
+        let quote = quote_swap(
+            client.clone(),
+            self.pool_id.as_deref(),
+            mint_str,
+            amount_specified,
+            &swap_direction,
+            slippage,
+        )
+        .await?;
+        if let SwapDirection::Buy = swap_direction {
+            check_pool_liquidity(&quote.pair_id, quote.reserve_in)?;
+        }
+        let price_impact_pct = price_impact_pct(amount_specified, quote.reserve_in);
+        info!(
+            "meteora swap: {}, value: {:?} -> {}, pair: {}, expected_out: {}, min_out: {}",
+            token_in, amount_ui_pretty, token_out, quote.pair_id, quote.expected_out, quote.min_out
+        );
+
+        // build instructions, sol goes through a tmp wsol account like raydium swaps
+        let mut instructions = vec![];
+        let seed = &format!("{}", Keypair::new().pubkey())[..32];
+        let wsol_account = Pubkey::create_with_seed(&owner, seed, &spl_token::id())?;
+        let rent = self
+            .client
+            .get_minimum_balance_for_rent_exemption(Account::LEN)
+            .await?;
+        let total_amount = match swap_direction {
+            SwapDirection::Buy => rent + amount_specified,
+            SwapDirection::Sell => rent,
+        };
+        instructions.push(system_instruction::create_account_with_seed(
+            &owner,
+            &wsol_account,
+            &owner,
+            seed,
+            total_amount,
+            Account::LEN as u64,
+            &spl_token::id(),
+        ));
+        instructions.push(spl_token::instruction::initialize_account(
+            &spl_token::id(),
+            &wsol_account,
+            &native_mint,
+            &owner,
+        )?);
+        if let Some(create_instruction) = create_instruction {
+            instructions.push(create_instruction);
+        }
+        let (user_token_in, user_token_out) = match swap_direction {
+            SwapDirection::Buy => (wsol_account, out_ata),
+            SwapDirection::Sell => (in_ata, wsol_account),
+        };
+        instructions.push(dlmm_swap(
+            &quote,
+            &owner,
+            &user_token_in,
+            &user_token_out,
+            amount_specified,
+        )?);
+        instructions.push(spl_token::instruction::close_account(
+            &program_id,
+            &wsol_account,
+            &owner,
+            &owner,
+            &vec![&owner],
+        )?);
+        if let Some(close_instruction) = close_instruction {
+            instructions.push(close_instruction);
+        }
+
+        let signatures =
+            tx::new_signed_and_send(&client, &self.keypair, instructions, use_jito).await?;
+        Ok(SwapOutcome {
+            signatures,
+            slippage_bps: quote.slippage_bps,
+            amount_in: amount_specified,
+            expected_out: quote.expected_out,
+            price_impact_pct,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pair(active_id: i32) -> LbPair {
+        LbPair {
+            active_id,
+            bin_step: 100,
+            base_factor: 0,
+            status: 0,
+            token_x_mint: Pubkey::new_unique(),
+            token_y_mint: spl_token::native_mint::ID,
+            reserve_x: Pubkey::new_unique(),
+            reserve_y: Pubkey::new_unique(),
+            oracle: Pubkey::new_unique(),
+        }
+    }
+
+    #[test]
+    fn test_bin_array_index() {
+        assert_eq!(bin_array_index(0), 0);
+        assert_eq!(bin_array_index(69), 0);
+        assert_eq!(bin_array_index(70), 1);
+        assert_eq!(bin_array_index(-1), -1);
+        assert_eq!(bin_array_index(-70), -1);
+        assert_eq!(bin_array_index(-71), -2);
+    }
+
+    #[test]
+    fn test_quote() {
+        // price 1.0 in bin 0, 0.5 in bin -1
+        let mut bins = vec![Bin::default(); MAX_BIN_PER_ARRAY as usize];
+        bins[0] = Bin {
+            amount_x: 0,
+            amount_y: 100,
+            price: 1u128 << SCALE_OFFSET,
+        };
+        let mut lower_bins = vec![Bin::default(); MAX_BIN_PER_ARRAY as usize];
+        lower_bins[69] = Bin {
+            amount_x: 0,
+            amount_y: 1000,
+            price: 1u128 << (SCALE_OFFSET - 1),
+        };
+        let bin_arrays = vec![
+            BinArray { index: 0, bins },
+            BinArray {
+                index: -1,
+                bins: lower_bins,
+            },
+        ];
+        let pair_id = Pubkey::new_unique();
+        // within the active bin
+        assert_eq!(
+            quote(&pair_id, &pair(0), &bin_arrays, 50, true).unwrap(),
+            50
+        );
+        // drains the active bin, the other 100 x buy 50 y one bin down
+        assert_eq!(
+            quote(&pair_id, &pair(0), &bin_arrays, 200, true).unwrap(),
+            150
+        );
+        // no bins with y to sell for x
+        let err = ApiError::from(&quote(&pair_id, &pair(0), &bin_arrays, 50, false).unwrap_err());
+        assert_eq!(err.code, ErrorCode::InsufficientLiquidity);
+        assert!(err.message.contains(&pair_id.to_string()));
+    }
+}
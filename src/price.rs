@@ -0,0 +1,393 @@
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap},
+    env,
+    future::Future,
+    str::FromStr,
+    time::Duration,
+};
+
+use anyhow::Result;
+use futures_util::{stream, StreamExt};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use solana_sdk::pubkey::Pubkey;
+use tokio::sync::{broadcast, Mutex, RwLock};
+use tracing::{debug, info};
+
+use crate::{
+    api::AppState,
+    error::{ApiError, ErrorCode},
+    helper::get_solana_price,
+    ledger::now_millis,
+    meteora::Meteora,
+    pump::{self, Pump},
+    raydium::Raydium,
+    swap::Venue,
+};
+
+fn get_price_stream_interval() -> Duration {
+    let interval_ms = env::var("PRICE_STREAM_INTERVAL_MS")
+        .ok()
+        .and_then(|v| v.parse::<u64>().ok())
+        .unwrap_or(1000);
+    Duration::from_millis(interval_ms.max(100))
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct PriceTick {
+    pub mint: String,
+    pub venue: Venue,
+    /// sol per token
+    pub price: f64,
+    /// unix timestamp in milliseconds
+    pub timestamp: u64,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PriceVenue {
+    /// bonding curve until it completes, pumpswap or raydium after
+    #[default]
+    Auto,
+    Raydium,
+    Pump,
+    Meteora,
+    PumpSwap,
+}
+
+impl PriceVenue {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            PriceVenue::Auto => "auto",
+            PriceVenue::Raydium => "raydium",
+            PriceVenue::Pump => "pump",
+            PriceVenue::Meteora => "meteora",
+            PriceVenue::PumpSwap => "pumpswap",
+        }
+    }
+}
+
+fn get_price_batch_max() -> usize {
+    env::var("PRICE_BATCH_MAX")
+        .ok()
+        .and_then(|v| v.parse::<usize>().ok())
+        .unwrap_or(50)
+}
+
+fn get_price_batch_concurrency() -> usize {
+    env::var("PRICE_BATCH_CONCURRENCY")
+        .ok()
+        .and_then(|v| v.parse::<usize>().ok())
+        .unwrap_or(8)
+        .max(1)
+}
+
+/// Price of a mint on the requested venue
+pub async fn get_token_price(state: &AppState, mint: &str, venue: PriceVenue) -> Result<PriceTick> {
+    let mint_pubkey = Pubkey::from_str(mint)?;
+    let venue = match venue {
+        PriceVenue::Pump => Venue::Pump,
+        PriceVenue::Raydium => Venue::Raydium,
+        PriceVenue::Meteora => Venue::Meteora,
+        PriceVenue::PumpSwap => Venue::PumpSwap,
+        PriceVenue::Auto => {
+            let pump_program = Pubkey::from_str(pump::PUMP_PROGRAM)?;
+            let complete = pump::get_bonding_curve_account(
+                state.client_blocking.clone(),
+                &mint_pubkey,
+                &pump_program,
+            )
+            .await
+            .ok()
+            .map(|(_, _, curve)| curve.complete);
+            match complete {
+                Some(false) => Venue::Pump,
+                Some(true)
+                    if pump::find_pump_swap_pools(state.client_blocking.clone(), mint)
+                        .await
+                        .is_ok_and(|pools| !pools.is_empty()) =>
+                {
+                    Venue::PumpSwap
+                }
+                _ => Venue::Raydium,
+            }
+        }
+    };
+
+    let price = match venue {
+        Venue::Pump => {
+            let mut swapx = Pump::new(state.client.clone(), state.wallet());
+            swapx.with_blocking_client(state.client_blocking.clone());
+            let (_, _, price) = swapx.get_pump_price(mint).await?;
+            price
+        }
+        Venue::Raydium => {
+            let mut swapx = Raydium::new(state.client.clone(), state.wallet());
+            swapx.with_blocking_client(state.client_blocking.clone());
+            swapx.get_pool_price(None, Some(mint)).await?.price
+        }
+        Venue::Meteora => {
+            let mut swapx = Meteora::new(state.client.clone(), state.wallet());
+            swapx
+                .with_blocking_client(state.client_blocking.clone())
+                .with_decimals_cache(state.decimals_cache.clone());
+            swapx.get_pool_price(None, Some(mint)).await?.price
+        }
+        Venue::PumpSwap => {
+            let mut swapx = Pump::new(state.client.clone(), state.wallet());
+            swapx
+                .with_blocking_client(state.client_blocking.clone())
+                .with_decimals_cache(state.decimals_cache.clone());
+            swapx.get_pump_swap_price(mint).await?.price
+        }
+    };
+    Ok(PriceTick {
+        mint: mint.to_string(),
+        venue,
+        price,
+        timestamp: now_millis(),
+    })
+}
+
+/// Prices of many mints with bounded concurrency, failures are reported per mint
+pub async fn get_token_prices(
+    state: &AppState,
+    mints: Vec<String>,
+    venue: PriceVenue,
+    fresh: bool,
+) -> Result<BTreeMap<String, Value>> {
+    let max = get_price_batch_max();
+    let mints: BTreeSet<String> = mints.into_iter().collect();
+    if mints.len() > max {
+        return Err(ApiError::new(
+            ErrorCode::InvalidInput,
+            format!(
+                "batch of {} tokens is above the max of {}",
+                mints.len(),
+                max
+            ),
+        )
+        .into());
+    }
+    let prices = stream::iter(mints)
+        .map(|mint| async move {
+            let cache_key = cache_venue(state, &format!("tick:{}", venue.as_str()));
+            let price = state
+                .price_cache
+                .get_or_fetch(&mint, &cache_key, fresh, || async {
+                    Ok::<Value, anyhow::Error>(json!(get_token_price(state, &mint, venue).await?))
+                })
+                .await
+                .unwrap_or_else(|err| json!({ "error": err.to_string() }));
+            (mint, price)
+        })
+        .buffer_unordered(get_price_batch_concurrency())
+        .collect::<BTreeMap<String, Value>>()
+        .await;
+    Ok(prices)
+}
+
+/// Cache key of `venue`, prices read at different commitments are kept apart
+pub fn cache_venue(state: &AppState, venue: &str) -> String {
+    format!("{}:{:?}", venue, state.client.commitment().commitment)
+}
+
+/// Usd price per token of every mint that could be priced, in batches of PRICE_BATCH_MAX
+pub async fn get_usd_prices(state: &AppState, mints: Vec<String>) -> Result<HashMap<String, f64>> {
+    let sol_price = get_solana_price().await.map_err(|e| {
+        ApiError::new(
+            ErrorCode::RpcError,
+            format!("failed to get the sol price: {}", e),
+        )
+    })?;
+    let native_mint = spl_token::native_mint::ID.to_string();
+    let mut prices = HashMap::new();
+    let (wsol, mints): (Vec<String>, Vec<String>) =
+        mints.into_iter().partition(|mint| *mint == native_mint);
+    if !wsol.is_empty() {
+        prices.insert(native_mint, sol_price);
+    }
+    for batch in mints.chunks(get_price_batch_max().max(1)) {
+        let ticks = get_token_prices(state, batch.to_vec(), PriceVenue::Auto, false).await?;
+        for (mint, tick) in ticks {
+            match tick["price"].as_f64() {
+                Some(price) => {
+                    prices.insert(mint, price * sol_price);
+                }
+                None => debug!("no usd price for {}: {}", mint, tick),
+            }
+        }
+    }
+    Ok(prices)
+}
+
+fn get_price_cache_ttl() -> Duration {
+    let ttl_ms = env::var("PRICE_CACHE_TTL_MS")
+        .ok()
+        .and_then(|v| v.parse::<u64>().ok())
+        .unwrap_or(500);
+    Duration::from_millis(ttl_ms)
+}
+
+// expired entries are swept once the cache grows past this
+const PRICE_CACHE_SWEEP_LEN: usize = 1024;
+
+/// Recently computed price responses keyed by (mint, venue)
+pub struct PriceCache {
+    ttl: Duration,
+    entries: RwLock<HashMap<(String, String), (u64, Value)>>,
+}
+
+impl Default for PriceCache {
+    fn default() -> Self {
+        Self::new(get_price_cache_ttl())
+    }
+}
+
+impl PriceCache {
+    pub fn new(ttl: Duration) -> Self {
+        Self {
+            ttl,
+            entries: RwLock::new(HashMap::new()),
+        }
+    }
+
+    /// Reuse a value younger than the ttl unless `fresh`, otherwise fetch and store it
+    ///
+    /// Object values get `cached_at` (unix ms) and `age_ms` added.
+    pub async fn get_or_fetch<F, Fut>(
+        &self,
+        mint: &str,
+        venue: &str,
+        fresh: bool,
+        fetch: F,
+    ) -> Result<Value>
+    where
+        F: FnOnce() -> Fut,
+        Fut: Future<Output = Result<Value>>,
+    {
+        let key = (mint.to_string(), venue.to_string());
+        let now = now_millis();
+        let ttl_ms = self.ttl.as_millis() as u64;
+        if !fresh {
+            if let Some((cached_at, value)) = self.entries.read().await.get(&key) {
+                let age_ms = now.saturating_sub(*cached_at);
+                if age_ms < ttl_ms {
+                    return Ok(with_cache_fields(value.clone(), *cached_at, age_ms));
+                }
+            }
+        }
+
+        let value = fetch().await?;
+        let cached_at = now_millis();
+        if ttl_ms > 0 {
+            let mut entries = self.entries.write().await;
+            if entries.len() >= PRICE_CACHE_SWEEP_LEN {
+                entries.retain(|_, (at, _)| cached_at.saturating_sub(*at) < ttl_ms);
+            }
+            entries.insert(key, (cached_at, value.clone()));
+        }
+        Ok(with_cache_fields(value, cached_at, 0))
+    }
+}
+
+fn with_cache_fields(mut value: Value, cached_at: u64, age_ms: u64) -> Value {
+    if let Some(object) = value.as_object_mut() {
+        object.insert("cached_at".to_string(), json!(cached_at));
+        object.insert("age_ms".to_string(), json!(age_ms));
+    }
+    value
+}
+
+/// One polling task per mint, fanned out to every subscriber through a broadcast channel
+#[derive(Default)]
+pub struct PriceStreams {
+    senders: Mutex<HashMap<String, broadcast::Sender<String>>>,
+}
+
+impl PriceStreams {
+    /// Receive json encoded ticks for `mint`, starting the poller if it is not running
+    pub async fn subscribe(&self, state: &AppState, mint: &str) -> broadcast::Receiver<String> {
+        let mut senders = self.senders.lock().await;
+        if let Some(sender) = senders.get(mint) {
+            return sender.subscribe();
+        }
+        let (sender, receiver) = broadcast::channel(16);
+        senders.insert(mint.to_string(), sender.clone());
+        tokio::spawn(poll_price(state.clone(), mint.to_string(), sender));
+        receiver
+    }
+}
+
+// exits after the last subscriber goes away
+async fn poll_price(state: AppState, mint: String, sender: broadcast::Sender<String>) {
+    info!("price stream started: {}", mint);
+    let mut interval = tokio::time::interval(get_price_stream_interval());
+    loop {
+        interval.tick().await;
+        {
+            let mut senders = state.price_streams.senders.lock().await;
+            if sender.receiver_count() == 0 {
+                if senders
+                    .get(&mint)
+                    .is_some_and(|current| current.same_channel(&sender))
+                {
+                    senders.remove(&mint);
+                }
+                break;
+            }
+        }
+        let message = match get_token_price(&state, &mint, PriceVenue::Auto).await {
+            Ok(tick) => json!(tick),
+            Err(err) => {
+                debug!("price stream {} err: {}", mint, err);
+                json!({ "mint": mint, "error": err.to_string() })
+            }
+        };
+        let _ = sender.send(message.to_string());
+    }
+    info!("price stream stopped: {}", mint);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_price_cache() {
+        let cache = PriceCache::new(Duration::from_secs(60));
+        let value = cache
+            .get_or_fetch("mint", "pump", false, || async {
+                Ok(json!({ "price": 1.0 }))
+            })
+            .await
+            .unwrap();
+        assert_eq!(value["price"], 1.0);
+        assert_eq!(value["age_ms"], 0);
+
+        // served from the cache, the fetch is not called
+        let value = cache
+            .get_or_fetch("mint", "pump", false, || async {
+                Ok(json!({ "price": 2.0 }))
+            })
+            .await
+            .unwrap();
+        assert_eq!(value["price"], 1.0);
+
+        let value = cache
+            .get_or_fetch("mint", "raydium", false, || async {
+                Ok(json!({ "price": 3.0 }))
+            })
+            .await
+            .unwrap();
+        assert_eq!(value["price"], 3.0);
+
+        let value = cache
+            .get_or_fetch("mint", "pump", true, || async {
+                Ok(json!({ "price": 2.0 }))
+            })
+            .await
+            .unwrap();
+        assert_eq!(value["price"], 2.0);
+    }
+}
@@ -1,30 +1,42 @@
-use std::{env, str::FromStr, sync::Arc};
+use std::{env, str::FromStr, sync::Arc, time::Duration};
 
 use anyhow::{anyhow, Context, Result};
 use borsh::from_slice;
 use borsh_derive::{BorshDeserialize, BorshSerialize};
+use common::common_utils;
 use raydium_amm::math::U128;
-use reqwest::Proxy;
+use reqwest::{multipart, Proxy};
 use serde::{Deserialize, Serialize};
-use solana_client::nonblocking::rpc_client::RpcClient;
+use solana_client::{
+    nonblocking::rpc_client::RpcClient,
+    rpc_filter::{Memcmp, RpcFilterType},
+};
 use solana_sdk::{
     instruction::{AccountMeta, Instruction},
+    program_pack::Pack,
     pubkey::Pubkey,
     signature::Keypair,
     signer::Signer,
-    system_program,
+    system_instruction, system_program,
 };
 use spl_associated_token_account::{
-    get_associated_token_address, instruction::create_associated_token_account,
+    get_associated_token_address, instruction::create_associated_token_account_idempotent,
 };
-use spl_token::{amount_to_ui_amount, ui_amount_to_amount};
-use spl_token_client::token::TokenError;
+use spl_token::{amount_to_ui_amount, state::Account};
 
-use tracing::{debug, error, info, warn};
+use tracing::{debug, info, warn};
 
 use crate::{
-    swap::{SwapDirection, SwapInType},
-    token, tx,
+    constants::{get_network, Network},
+    error::{ApiError, ErrorCode},
+    get_client_build,
+    helper::anchor_discriminator,
+    swap::{
+        check_pool_liquidity, check_quote, check_route, pct_of, price_impact_pct, to_base_units,
+        Slippage, SwapDirection, SwapInType, SwapOutcome,
+    },
+    token::{self, DecimalsCache},
+    tx,
 };
 pub const TEN_THOUSAND: u64 = 10000;
 pub const TOKEN_PROGRAM: &str = "TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA";
@@ -32,16 +44,33 @@ pub const RENT_PROGRAM: &str = "SysvarRent111111111111111111111111111111111";
 pub const ASSOCIATED_TOKEN_PROGRAM: &str = "ATokenGPvbdGVxr1b2hvZbsiqW5xWH25efTNsLJA8knL";
 pub const PUMP_GLOBAL: &str = "4wTV1YmiEkRvAtNtsSGPtUrqRYQMe5SKy2uB4Jjaxnjf";
 pub const PUMP_FEE_RECIPIENT: &str = "CebN5WGQ4jvEPvsVU4EoHEpgzq1VV7AbicfhtW4xC9iM";
+// Global: discriminator, initialized bool, authority, fee_recipient
+const PUMP_GLOBAL_FEE_RECIPIENT: usize = 41;
 pub const PUMP_PROGRAM: &str = "6EF8rrecthR5Dkzon8Nwu78hRvfCKubJ14M5uBEwF6P";
-// pub const PUMP_FUN_MINT_AUTHORITY: &str = "TSLvdd1pWpHVjahSpsvCXUbgwsL3JAcvokwaKt1eokM";
 pub const PUMP_ACCOUNT: &str = "Ce6TQqeHC9p8KetsN6JsjHK7UTZk7nasjjnr7XxXp9F1";
 pub const PUMP_BUY_METHOD: u64 = 16927863322537952870;
 pub const PUMP_SELL_METHOD: u64 = 12502976635542562355;
 
+/// Fee recipient of pump.fun trades, read from the global account off mainnet
+pub fn get_fee_recipient(rpc_client: &solana_client::rpc_client::RpcClient) -> Result<Pubkey> {
+    if get_network() == Network::Mainnet {
+        return Ok(Pubkey::from_str(PUMP_FEE_RECIPIENT)?);
+    }
+    let global = Pubkey::from_str(PUMP_GLOBAL)?;
+    let data = rpc_client.get_account_data(&global)?;
+    if data.len() < PUMP_GLOBAL_FEE_RECIPIENT + 32 {
+        return Err(anyhow!("failed to parse pump global account {}", global));
+    }
+    Ok(read_pubkey(&data, PUMP_GLOBAL_FEE_RECIPIENT))
+}
+
 pub struct Pump {
     pub client: Arc<RpcClient>,
     pub keypair: Arc<Keypair>,
     pub client_blocking: Option<Arc<solana_client::rpc_client::RpcClient>>,
+    /// lamports floor for sells, replaces the slippage threshold
+    pub min_sol_out: Option<u64>,
+    pub decimals_cache: Arc<DecimalsCache>,
 }
 
 impl Pump {
@@ -50,9 +79,21 @@ impl Pump {
             client,
             keypair,
             client_blocking: None,
+            min_sol_out: None,
+            decimals_cache: Arc::new(DecimalsCache::default()),
         }
     }
 
+    pub fn with_min_sol_out(&mut self, min_sol_out: Option<u64>) -> &mut Self {
+        self.min_sol_out = min_sol_out;
+        self
+    }
+
+    pub fn with_decimals_cache(&mut self, decimals_cache: Arc<DecimalsCache>) -> &mut Self {
+        self.decimals_cache = decimals_cache;
+        self
+    }
+
     pub fn with_blocking_client(
         &mut self,
         client: Arc<solana_client::rpc_client::RpcClient>,
@@ -92,11 +133,9 @@ impl Pump {
         amount_in: f64,
         swap_direction: SwapDirection,
         in_type: SwapInType,
-        slippage: u64,
+        slippage: Slippage,
         use_jito: bool,
-    ) -> Result<Vec<String>> {
-        // slippage_bps = 50u64; // 0.5%
-        let slippage_bps = slippage * 100;
+    ) -> Result<SwapOutcome> {
         let owner = self.keypair.pubkey();
         let mint =
             Pubkey::from_str(mint).map_err(|e| anyhow!("failed to parse mint pubkey: {}", e))?;
@@ -107,45 +146,23 @@ impl Pump {
             SwapDirection::Buy => (native_mint, mint, PUMP_BUY_METHOD),
             SwapDirection::Sell => (mint, native_mint, PUMP_SELL_METHOD),
         };
-        let pump_program = Pubkey::from_str(PUMP_PROGRAM)?;
-        let (bonding_curve, associated_bonding_curve, bonding_curve_account) =
-            get_bonding_curve_account(self.client_blocking.clone().unwrap(), &mint, &pump_program)
-                .await?;
         let in_ata = get_associated_token_address(&owner, &token_in);
-        let out_ata = get_associated_token_address(&owner, &token_out);
 
         let mut create_instruction = None;
         let mut close_instruction = None;
 
         let (amount_specified, amount_ui_pretty) = match swap_direction {
             SwapDirection::Buy => {
-                // Create base ATA if it doesn't exist.
-                match token::get_account_info(
-                    self.client.clone(),
-                    self.keypair.clone(),
+                // Create base ATA, the idempotent variant is a no-op if it already exists
+                create_instruction = Some(create_associated_token_account_idempotent(
+                    &owner,
+                    &owner,
                     &token_out,
-                    &out_ata,
-                )
-                .await
-                {
-                    Ok(_) => debug!("base ata exists. skipping creation.."),
-                    Err(TokenError::AccountNotFound) | Err(TokenError::AccountInvalidOwner) => {
-                        info!(
-                            "base ATA for mint {} does not exist. will be create",
-                            token_out
-                        );
-                        create_instruction = Some(create_associated_token_account(
-                            &owner,
-                            &owner,
-                            &token_out,
-                            &program_id,
-                        ));
-                    }
-                    Err(error) => error!("error retrieving out ATA: {}", error),
-                }
+                    &program_id,
+                ));
 
                 (
-                    ui_amount_to_amount(amount_in, spl_token::native_mint::DECIMALS),
+                    to_base_units(amount_in, spl_token::native_mint::DECIMALS)?,
                     (amount_in, spl_token::native_mint::DECIMALS),
                 )
             }
@@ -157,11 +174,9 @@ impl Pump {
                     &in_ata,
                 )
                 .await?;
-                let in_mint =
-                    token::get_mint_info(self.client.clone(), self.keypair.clone(), &token_in)
-                        .await?;
+                let decimals = self.decimals_cache.get(&self.client, &token_in).await?;
                 let amount = match in_type {
-                    SwapInType::Qty => ui_amount_to_amount(amount_in, in_mint.base.decimals),
+                    SwapInType::Qty => to_base_units(amount_in, decimals)?,
                     SwapInType::Pct => {
                         let amount_in_pct = amount_in.min(1.0);
                         if amount_in_pct == 1.0 {
@@ -176,17 +191,11 @@ impl Pump {
                             )?);
                             in_account.base.amount
                         } else {
-                            (amount_in_pct * 100.0) as u64 * in_account.base.amount / 100
+                            pct_of(in_account.base.amount, amount_in_pct)?
                         }
                     }
                 };
-                (
-                    amount,
-                    (
-                        amount_to_ui_amount(amount, in_mint.base.decimals),
-                        in_mint.base.decimals,
-                    ),
-                )
+                (amount, (amount_to_ui_amount(amount, decimals), decimals))
             }
         };
 
@@ -199,78 +208,57 @@ impl Pump {
             .client_blocking
             .clone()
             .context("failed to get rpc client")?;
+        let fee_recipient = get_fee_recipient(&client)?;
 
+        let pump_program = Pubkey::from_str(PUMP_PROGRAM)?;
+        let quote = quote_curve_swap(
+            client.clone(),
+            &mint,
+            amount_specified,
+            &swap_direction,
+            slippage,
+            self.min_sol_out,
+        )
+        .await?;
+        let (bonding_curve, associated_bonding_curve) =
+            (quote.bonding_curve, quote.associated_bonding_curve);
         // Calculate tokens out
-        let virtual_sol_reserves = U128::from(bonding_curve_account.virtual_sol_reserves);
-        let virtual_token_reserves = U128::from(bonding_curve_account.virtual_token_reserves);
-        let unit_price = (bonding_curve_account.virtual_sol_reserves as f64
-            / bonding_curve_account.virtual_token_reserves as f64)
+        let unit_price = (quote.bonding_curve_account.virtual_sol_reserves as f64
+            / quote.bonding_curve_account.virtual_token_reserves as f64)
             / 1000.0;
-
-        let (token_amount, sol_amount_threshold, input_accouts) = match swap_direction {
-            SwapDirection::Buy => {
-                let max_sol_cost = max_amount_with_slippage(amount_specified, slippage_bps);
-
-                (
-                    U128::from(amount_specified)
-                        .checked_mul(virtual_token_reserves)
-                        .unwrap()
-                        .checked_div(virtual_sol_reserves)
-                        .unwrap()
-                        .as_u64(),
-                    max_sol_cost,
-                    vec![
-                        AccountMeta::new_readonly(Pubkey::from_str(PUMP_GLOBAL)?, false),
-                        AccountMeta::new(Pubkey::from_str(PUMP_FEE_RECIPIENT)?, false),
-                        AccountMeta::new_readonly(mint, false),
-                        AccountMeta::new(bonding_curve, false),
-                        AccountMeta::new(associated_bonding_curve, false),
-                        AccountMeta::new(out_ata, false),
-                        AccountMeta::new(owner, true),
-                        AccountMeta::new_readonly(system_program::id(), false),
-                        AccountMeta::new_readonly(program_id, false),
-                        AccountMeta::new_readonly(Pubkey::from_str(RENT_PROGRAM)?, false),
-                        AccountMeta::new_readonly(Pubkey::from_str(PUMP_ACCOUNT)?, false),
-                        AccountMeta::new_readonly(pump_program, false),
-                    ],
-                )
-            }
-            SwapDirection::Sell => {
-                let sol_output = U128::from(amount_specified)
-                    .checked_mul(virtual_sol_reserves)
-                    .unwrap()
-                    .checked_div(virtual_token_reserves)
-                    .unwrap()
-                    .as_u64();
-                let min_sol_output = min_amount_with_slippage(sol_output, slippage_bps);
-
-                (
-                    amount_specified,
-                    min_sol_output,
-                    vec![
-                        AccountMeta::new_readonly(Pubkey::from_str(PUMP_GLOBAL)?, false),
-                        AccountMeta::new(Pubkey::from_str(PUMP_FEE_RECIPIENT)?, false),
-                        AccountMeta::new_readonly(mint, false),
-                        AccountMeta::new(bonding_curve, false),
-                        AccountMeta::new(associated_bonding_curve, false),
-                        AccountMeta::new(in_ata, false),
-                        AccountMeta::new(owner, true),
-                        AccountMeta::new_readonly(system_program::id(), false),
-                        AccountMeta::new_readonly(
-                            Pubkey::from_str(ASSOCIATED_TOKEN_PROGRAM)?,
-                            false,
-                        ),
-                        AccountMeta::new_readonly(program_id, false),
-                        AccountMeta::new_readonly(Pubkey::from_str(PUMP_ACCOUNT)?, false),
-                        AccountMeta::new_readonly(pump_program, false),
-                    ],
-                )
-            }
+        let price_impact_pct = price_impact_pct(amount_specified, quote.reserve_in);
+        let input_accouts = match swap_direction {
+            SwapDirection::Buy => buy_accounts(
+                &mint,
+                &bonding_curve,
+                &associated_bonding_curve,
+                &owner,
+                &fee_recipient,
+            )?,
+            SwapDirection::Sell => vec![
+                AccountMeta::new_readonly(Pubkey::from_str(PUMP_GLOBAL)?, false),
+                AccountMeta::new(fee_recipient, false),
+                AccountMeta::new_readonly(mint, false),
+                AccountMeta::new(bonding_curve, false),
+                AccountMeta::new(associated_bonding_curve, false),
+                AccountMeta::new(in_ata, false),
+                AccountMeta::new(owner, true),
+                AccountMeta::new_readonly(system_program::id(), false),
+                AccountMeta::new_readonly(Pubkey::from_str(ASSOCIATED_TOKEN_PROGRAM)?, false),
+                AccountMeta::new_readonly(program_id, false),
+                AccountMeta::new_readonly(Pubkey::from_str(PUMP_ACCOUNT)?, false),
+                AccountMeta::new_readonly(pump_program, false),
+            ],
         };
-
+        let (token_amount, sol_amount_threshold, expected_out, slippage_bps) = (
+            quote.token_amount,
+            quote.sol_amount_threshold,
+            quote.expected_out,
+            quote.slippage_bps,
+        );
         info!(
-            "token_amount: {}, sol_amount_threshold: {}, unit_price: {} sol",
-            token_amount, sol_amount_threshold, unit_price
+            "token_amount: {}, sol_amount_threshold: {}, unit_price: {} sol, price_impact: {:.4}%, slippage_bps: {}",
+            token_amount, sol_amount_threshold, unit_price, price_impact_pct, slippage_bps
         );
 
         let build_swap_instruction = Instruction::new_with_bincode(
@@ -293,8 +281,375 @@ impl Pump {
             return Err(anyhow!("instructions is empty, no tx required"));
         }
 
-        tx::new_signed_and_send(&client, &self.keypair, instructions, use_jito).await
+        let signatures =
+            tx::new_signed_and_send(&client, &self.keypair, instructions, use_jito).await?;
+        Ok(SwapOutcome {
+            signatures,
+            slippage_bps,
+            amount_in: amount_specified,
+            expected_out,
+            price_impact_pct,
+        })
+    }
+}
+
+pub const PUMP_CREATE_METHOD: u64 = 8576854823835016728;
+pub const PUMP_MINT_AUTHORITY: &str = "TSLvdd1pWpHVjahSpsvCXUbgwsL3JAcvokwaKt1eokM";
+// reserves of a freshly created bonding curve
+pub const INITIAL_VIRTUAL_SOL_RESERVES: u64 = 30_000_000_000;
+pub const INITIAL_VIRTUAL_TOKEN_RESERVES: u64 = 1_073_000_000_000_000;
+pub const INITIAL_REAL_TOKEN_RESERVES: u64 = 793_100_000_000_000;
+pub const TOKEN_TOTAL_SUPPLY: u64 = 1_000_000_000_000_000;
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct TokenMetadata {
+    pub name: String,
+    pub symbol: String,
+    pub description: String,
+    pub image_uri: String,
+    pub twitter: Option<String>,
+    pub telegram: Option<String>,
+    pub website: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct CreateOutcome {
+    pub mint: String,
+    pub bonding_curve: String,
+    pub metadata_uri: String,
+    pub signatures: Vec<String>,
+    /// raw tokens bought by the dev buy
+    pub dev_buy_tokens: u64,
+}
+
+#[derive(Debug, Deserialize)]
+struct IpfsResponse {
+    #[serde(rename = "metadataUri")]
+    metadata_uri: String,
+}
+
+const IMAGE_FETCH_TIMEOUT: Duration = Duration::from_secs(10);
+const MAX_IMAGE_BYTES: usize = 5 * 1024 * 1024;
+
+/// Upload the image and metadata json through pump.fun ipfs, returns the metadata uri
+pub async fn upload_metadata(metadata: &TokenMetadata) -> Result<String> {
+    let image_url = reqwest::Url::parse(&metadata.image_uri).map_err(|e| {
+        ApiError::new(
+            ErrorCode::InvalidInput,
+            format!("failed to parse image_uri: {}", e),
+        )
+    })?;
+    if !matches!(image_url.scheme(), "http" | "https") {
+        return Err(ApiError::new(
+            ErrorCode::InvalidInput,
+            format!(
+                "image_uri must be an http or https url, got {}",
+                image_url.scheme()
+            ),
+        )
+        .into());
+    }
+    let client = get_client_build()?;
+    let upload = async {
+        let mut response = client
+            .get(image_url)
+            .timeout(IMAGE_FETCH_TIMEOUT)
+            .send()
+            .await?
+            .error_for_status()?;
+        if response
+            .content_length()
+            .is_some_and(|len| len > MAX_IMAGE_BYTES as u64)
+        {
+            return Err(anyhow!("image is larger than {} bytes", MAX_IMAGE_BYTES));
+        }
+        // the length header is optional, count what is read as well
+        let mut image = Vec::new();
+        while let Some(chunk) = response.chunk().await? {
+            if image.len() + chunk.len() > MAX_IMAGE_BYTES {
+                return Err(anyhow!("image is larger than {} bytes", MAX_IMAGE_BYTES));
+            }
+            image.extend_from_slice(&chunk);
+        }
+        let mut form = multipart::Form::new()
+            .part("file", multipart::Part::bytes(image).file_name("image.png"))
+            .text("name", metadata.name.clone())
+            .text("symbol", metadata.symbol.clone())
+            .text("description", metadata.description.clone())
+            .text("showName", "true");
+        for (key, value) in [
+            ("twitter", &metadata.twitter),
+            ("telegram", &metadata.telegram),
+            ("website", &metadata.website),
+        ] {
+            if let Some(value) = value {
+                form = form.text(key, value.clone());
+            }
+        }
+        let response = client
+            .post("https://pump.fun/api/ipfs")
+            .multipart(form)
+            .send()
+            .await?
+            .error_for_status()?
+            .json::<IpfsResponse>()
+            .await?;
+        Ok::<String, anyhow::Error>(response.metadata_uri)
+    };
+    upload
+        .await
+        .map_err(|e| ApiError::new(ErrorCode::MetadataUploadFailed, e.to_string()).into())
+}
+
+impl Pump {
+    /// Create a token on pump.fun, with an optional dev buy of `dev_buy` sol in the same transaction
+    pub async fn create(
+        &self,
+        name: &str,
+        symbol: &str,
+        metadata_uri: &str,
+        dev_buy: f64,
+        slippage: Slippage,
+        use_jito: bool,
+    ) -> Result<CreateOutcome> {
+        let owner = self.keypair.pubkey();
+        let client = self
+            .client_blocking
+            .clone()
+            .context("failed to get rpc client")?;
+        let mint_keypair = Keypair::new();
+        let mint = mint_keypair.pubkey();
+        let pump_program = Pubkey::from_str(PUMP_PROGRAM)?;
+        let mpl_program = Pubkey::from_str(token::METADATA_PROGRAM)?;
+        let bonding_curve = get_pda(&mint, &pump_program)?;
+        let associated_bonding_curve = get_associated_token_address(&bonding_curve, &mint);
+        let metadata = token::get_metadata_pda(&mint)?;
+
+        let data = borsh::to_vec(&(
+            PUMP_CREATE_METHOD,
+            name.to_string(),
+            symbol.to_string(),
+            metadata_uri.to_string(),
+        ))?;
+        let mut instructions = vec![Instruction::new_with_bytes(
+            pump_program,
+            &data,
+            vec![
+                AccountMeta::new(mint, true),
+                AccountMeta::new_readonly(Pubkey::from_str(PUMP_MINT_AUTHORITY)?, false),
+                AccountMeta::new(bonding_curve, false),
+                AccountMeta::new(associated_bonding_curve, false),
+                AccountMeta::new_readonly(Pubkey::from_str(PUMP_GLOBAL)?, false),
+                AccountMeta::new_readonly(mpl_program, false),
+                AccountMeta::new(metadata, false),
+                AccountMeta::new(owner, true),
+                AccountMeta::new_readonly(system_program::id(), false),
+                AccountMeta::new_readonly(spl_token::ID, false),
+                AccountMeta::new_readonly(Pubkey::from_str(ASSOCIATED_TOKEN_PROGRAM)?, false),
+                AccountMeta::new_readonly(Pubkey::from_str(RENT_PROGRAM)?, false),
+                AccountMeta::new_readonly(Pubkey::from_str(PUMP_ACCOUNT)?, false),
+                AccountMeta::new_readonly(pump_program, false),
+            ],
+        )];
+
+        let mut dev_buy_tokens = 0;
+        let amount_specified = if dev_buy > 0.0 {
+            to_base_units(dev_buy, spl_token::native_mint::DECIMALS)?
+        } else {
+            0
+        };
+        if amount_specified > 0 {
+            let bonding_curve_account = BondingCurveAccount {
+                discriminator: 0,
+                virtual_token_reserves: INITIAL_VIRTUAL_TOKEN_RESERVES,
+                virtual_sol_reserves: INITIAL_VIRTUAL_SOL_RESERVES,
+                real_token_reserves: INITIAL_REAL_TOKEN_RESERVES,
+                real_sol_reserves: 0,
+                token_total_supply: TOKEN_TOTAL_SUPPLY,
+                complete: false,
+            };
+            let slippage_bps = slippage.resolve_bps(amount_specified, INITIAL_VIRTUAL_SOL_RESERVES);
+            let (token_amount, max_sol_cost, _) = quote(
+                &bonding_curve_account,
+                amount_specified,
+                &SwapDirection::Buy,
+                slippage_bps,
+            );
+            dev_buy_tokens = token_amount;
+            instructions.push(create_associated_token_account_idempotent(
+                &owner,
+                &owner,
+                &mint,
+                &spl_token::ID,
+            ));
+            instructions.push(Instruction::new_with_bincode(
+                pump_program,
+                &(PUMP_BUY_METHOD, token_amount, max_sol_cost),
+                buy_accounts(
+                    &mint,
+                    &bonding_curve,
+                    &associated_bonding_curve,
+                    &owner,
+                    &get_fee_recipient(&client)?,
+                )?,
+            ));
+        }
+        info!(
+            "create token {} ({}) mint: {}, dev buy: {} sol",
+            name, symbol, mint, dev_buy
+        );
+
+        let extra_signers = [&mint_keypair];
+        let options = tx::SendOptions {
+            use_jito,
+            extra_signers: &extra_signers,
+            ..Default::default()
+        };
+        let signatures =
+            tx::new_signed_and_send_with_options(&client, &self.keypair, instructions, options)
+                .await?;
+        Ok(CreateOutcome {
+            mint: mint.to_string(),
+            bonding_curve: bonding_curve.to_string(),
+            metadata_uri: metadata_uri.to_string(),
+            signatures,
+            dev_buy_tokens,
+        })
+    }
+}
+
+/// Threshold and implied slippage of an absolute output floor, rejects a quote already below it
+pub fn min_out_threshold(expected_out: u64, min_out: u64) -> Result<(u64, u64)> {
+    if expected_out < min_out {
+        return Err(ApiError::new(
+            ErrorCode::SlippageExceeded,
+            format!(
+                "quoted output {} is below the min of {}",
+                expected_out, min_out
+            ),
+        )
+        .into());
+    }
+    if expected_out == 0 {
+        return Ok((min_out, 0));
+    }
+    let slippage_bps =
+        (expected_out - min_out) as u128 * TEN_THOUSAND as u128 / expected_out as u128;
+    Ok((min_out, slippage_bps as u64))
+}
+
+fn buy_accounts(
+    mint: &Pubkey,
+    bonding_curve: &Pubkey,
+    associated_bonding_curve: &Pubkey,
+    owner: &Pubkey,
+    fee_recipient: &Pubkey,
+) -> Result<Vec<AccountMeta>> {
+    let out_ata = get_associated_token_address(owner, mint);
+    Ok(vec![
+        AccountMeta::new_readonly(Pubkey::from_str(PUMP_GLOBAL)?, false),
+        AccountMeta::new(*fee_recipient, false),
+        AccountMeta::new_readonly(*mint, false),
+        AccountMeta::new(*bonding_curve, false),
+        AccountMeta::new(*associated_bonding_curve, false),
+        AccountMeta::new(out_ata, false),
+        AccountMeta::new(*owner, true),
+        AccountMeta::new_readonly(system_program::id(), false),
+        AccountMeta::new_readonly(spl_token::ID, false),
+        AccountMeta::new_readonly(Pubkey::from_str(RENT_PROGRAM)?, false),
+        AccountMeta::new_readonly(Pubkey::from_str(PUMP_ACCOUNT)?, false),
+        AccountMeta::new_readonly(Pubkey::from_str(PUMP_PROGRAM)?, false),
+    ])
+}
+
+/// (token_amount, sol_amount_threshold, expected_out) for a buy or sell of amount_specified
+pub fn quote(
+    bonding_curve_account: &BondingCurveAccount,
+    amount_specified: u64,
+    swap_direction: &SwapDirection,
+    slippage_bps: u64,
+) -> (u64, u64, u64) {
+    let virtual_sol_reserves = U128::from(bonding_curve_account.virtual_sol_reserves);
+    let virtual_token_reserves = U128::from(bonding_curve_account.virtual_token_reserves);
+    match swap_direction {
+        SwapDirection::Buy => {
+            let max_sol_cost = max_amount_with_slippage(amount_specified, slippage_bps);
+            let token_amount = U128::from(amount_specified)
+                .checked_mul(virtual_token_reserves)
+                .unwrap()
+                .checked_div(virtual_sol_reserves)
+                .unwrap()
+                .as_u64();
+            (token_amount, max_sol_cost, token_amount)
+        }
+        SwapDirection::Sell => {
+            let sol_output = U128::from(amount_specified)
+                .checked_mul(virtual_sol_reserves)
+                .unwrap()
+                .checked_div(virtual_token_reserves)
+                .unwrap()
+                .as_u64();
+            let min_sol_output = min_amount_with_slippage(sol_output, slippage_bps);
+            (amount_specified, min_sol_output, sol_output)
+        }
+    }
+}
+
+pub struct CurveQuote {
+    pub bonding_curve: Pubkey,
+    pub associated_bonding_curve: Pubkey,
+    pub bonding_curve_account: BondingCurveAccount,
+    pub reserve_in: u64,
+    pub slippage_bps: u64,
+    /// tokens bought or sold
+    pub token_amount: u64,
+    /// max lamports in for buys, min lamports out for sells
+    pub sol_amount_threshold: u64,
+    pub expected_out: u64,
+}
+
+/// Quote against the bonding curve of `mint`, `min_sol_out` replaces the slippage floor of sells
+pub async fn quote_curve_swap(
+    rpc_client: Arc<solana_client::rpc_client::RpcClient>,
+    mint: &Pubkey,
+    amount_specified: u64,
+    swap_direction: &SwapDirection,
+    slippage: Slippage,
+    min_sol_out: Option<u64>,
+) -> Result<CurveQuote> {
+    let pump_program = Pubkey::from_str(PUMP_PROGRAM)?;
+    let (bonding_curve, associated_bonding_curve, bonding_curve_account) =
+        get_bonding_curve_account(rpc_client, mint, &pump_program).await?;
+    check_curve_route(&bonding_curve, &bonding_curve_account, swap_direction)?;
+    let reserve_in = match swap_direction {
+        SwapDirection::Buy => bonding_curve_account.virtual_sol_reserves,
+        SwapDirection::Sell => bonding_curve_account.virtual_token_reserves,
+    };
+    if let SwapDirection::Buy = swap_direction {
+        // the virtual reserves include sol the curve does not hold
+        check_pool_liquidity(&bonding_curve, bonding_curve_account.real_sol_reserves)?;
+    }
+    let mut slippage_bps = slippage.resolve_bps(amount_specified, reserve_in);
+    let (token_amount, mut sol_amount_threshold, expected_out) = quote(
+        &bonding_curve_account,
+        amount_specified,
+        swap_direction,
+        slippage_bps,
+    );
+    if let (SwapDirection::Sell, Some(min_sol_out)) = (swap_direction, min_sol_out) {
+        (sol_amount_threshold, slippage_bps) = min_out_threshold(expected_out, min_sol_out)?;
     }
+    check_quote(&bonding_curve, expected_out)?;
+    Ok(CurveQuote {
+        bonding_curve,
+        associated_bonding_curve,
+        bonding_curve_account,
+        reserve_in,
+        slippage_bps,
+        token_amount,
+        sol_amount_threshold,
+        expected_out,
+    })
 }
 
 fn min_amount_with_slippage(input_amount: u64, slippage_bps: u64) -> u64 {
@@ -321,6 +676,15 @@ pub struct RaydiumInfo {
     pub price: f64,
 }
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PumpSwapInfo {
+    pub pool_id: String,
+    pub base: String,
+    pub base_amount: f64,
+    pub quote: String,
+    pub quote_amount: f64,
+    pub price: f64,
+}
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct PumpInfo {
     pub mint: String,
     pub name: String,
@@ -328,6 +692,9 @@ pub struct PumpInfo {
     pub associated_bonding_curve: String,
     pub raydium_pool: Option<String>,
     pub raydium_info: Option<RaydiumInfo>,
+    /// graduated coins migrate to a pumpswap pool, older ones to raydium
+    pub pump_swap_pool: Option<String>,
+    pub pump_swap_info: Option<PumpSwapInfo>,
     pub complete: bool,
     pub virtual_sol_reserves: u64,
     pub virtual_token_reserves: u64,
@@ -359,6 +726,36 @@ pub struct BondingCurveAccount {
     pub complete: bool,
 }
 
+/// Reject a swap on a curve that completed or holds nothing of the output token,
+/// the virtual reserves price the swap, the real ones are what the curve can pay out
+pub fn check_curve_route(
+    bonding_curve: &Pubkey,
+    bonding_curve_account: &BondingCurveAccount,
+    swap_direction: &SwapDirection,
+) -> Result<()> {
+    if bonding_curve_account.complete {
+        return Err(ApiError::new(
+            ErrorCode::NoRoute,
+            format!(
+                "bonding curve {} is complete, the coin trades on pumpswap or raydium",
+                bonding_curve
+            ),
+        )
+        .into());
+    }
+    let (reserve_in, reserve_out) = match swap_direction {
+        SwapDirection::Buy => (
+            bonding_curve_account.virtual_sol_reserves,
+            bonding_curve_account.real_token_reserves,
+        ),
+        SwapDirection::Sell => (
+            bonding_curve_account.virtual_token_reserves,
+            bonding_curve_account.real_sol_reserves,
+        ),
+    };
+    check_route(bonding_curve, reserve_in, reserve_out)
+}
+
 pub async fn get_bonding_curve_account(
     rpc_client: Arc<solana_client::rpc_client::RpcClient>,
     mint: &Pubkey,
@@ -424,6 +821,8 @@ pub async fn get_pump_info(
         associated_bonding_curve: associated_bonding_curve.to_string(),
         raydium_pool: None,
         raydium_info: None,
+        pump_swap_pool: None,
+        pump_swap_info: None,
         complete: bonding_curve_account.complete,
         virtual_sol_reserves: bonding_curve_account.virtual_sol_reserves,
         virtual_token_reserves: bonding_curve_account.virtual_token_reserves,
@@ -460,3 +859,614 @@ pub async fn get_pump_pool_from_pump(mint: &str) -> Result<PumpPool> {
     debug!("pump pool {:?}", result);
     Ok(result)
 }
+
+pub const PUMP_SWAP_PROGRAM: &str = "pAMMBay6oceH9fJKBRHGP5D4bD4sWpmSwMn52FMfXEA";
+
+// Pool field offsets: discriminator, pool_bump u8, index u16, creator, base_mint, quote_mint,
+// lp_mint, pool_base_token_account, pool_quote_token_account, lp_supply u64, coin_creator
+const POOL_BASE_MINT: usize = 43;
+const POOL_QUOTE_MINT: usize = 75;
+const POOL_BASE_TOKEN_ACCOUNT: usize = 139;
+const POOL_QUOTE_TOKEN_ACCOUNT: usize = 171;
+const POOL_COIN_CREATOR: usize = 211;
+const POOL_MIN_LEN: usize = 243;
+
+// GlobalConfig field offsets: discriminator, admin, lp_fee_basis_points u64,
+// protocol_fee_basis_points u64, disable_flags u8, protocol_fee_recipients [Pubkey; 8],
+// coin_creator_fee_basis_points u64
+const GLOBAL_CONFIG_LP_FEE: usize = 40;
+const GLOBAL_CONFIG_PROTOCOL_FEE: usize = 48;
+const GLOBAL_CONFIG_FEE_RECIPIENTS: usize = 57;
+const GLOBAL_CONFIG_COIN_CREATOR_FEE: usize = 313;
+
+fn read_u64(data: &[u8], offset: usize) -> u64 {
+    u64::from_le_bytes(data[offset..offset + 8].try_into().unwrap())
+}
+
+fn read_pubkey(data: &[u8], offset: usize) -> Pubkey {
+    Pubkey::new_from_array(data[offset..offset + 32].try_into().unwrap())
+}
+
+/// The fields of a PumpSwap pool account a swap needs, base is the coin and quote is WSOL
+#[derive(Debug, Clone, PartialEq)]
+pub struct PumpSwapPool {
+    pub base_mint: Pubkey,
+    pub quote_mint: Pubkey,
+    pub pool_base_token_account: Pubkey,
+    pub pool_quote_token_account: Pubkey,
+    pub coin_creator: Pubkey,
+}
+
+impl PumpSwapPool {
+    pub fn from_account_data(data: &[u8]) -> Result<Self> {
+        if data.len() < POOL_MIN_LEN || data[..8] != anchor_discriminator("account:Pool") {
+            return Err(
+                ApiError::new(ErrorCode::InvalidInput, "not a pumpswap pool account").into(),
+            );
+        }
+        Ok(Self {
+            base_mint: read_pubkey(data, POOL_BASE_MINT),
+            quote_mint: read_pubkey(data, POOL_QUOTE_MINT),
+            pool_base_token_account: read_pubkey(data, POOL_BASE_TOKEN_ACCOUNT),
+            pool_quote_token_account: read_pubkey(data, POOL_QUOTE_TOKEN_ACCOUNT),
+            coin_creator: read_pubkey(data, POOL_COIN_CREATOR),
+        })
+    }
+}
+
+/// PumpSwap fees in bps, all of them are taken on the quote side
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct PumpSwapFees {
+    pub lp_fee_bps: u64,
+    pub protocol_fee_bps: u64,
+    pub coin_creator_fee_bps: u64,
+}
+
+impl PumpSwapFees {
+    pub fn total_bps(&self) -> u64 {
+        self.lp_fee_bps + self.protocol_fee_bps + self.coin_creator_fee_bps
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct PumpSwapConfig {
+    pub global_config: Pubkey,
+    pub fees: PumpSwapFees,
+    pub protocol_fee_recipient: Pubkey,
+}
+
+pub fn get_pump_swap_config(
+    rpc_client: &solana_client::rpc_client::RpcClient,
+) -> Result<PumpSwapConfig> {
+    let program_id = Pubkey::from_str(PUMP_SWAP_PROGRAM)?;
+    let (global_config, _bump) =
+        Pubkey::find_program_address(&[b"global_config".as_ref()], &program_id);
+    let data = rpc_client.get_account_data(&global_config)?;
+    if data.len() < GLOBAL_CONFIG_COIN_CREATOR_FEE {
+        return Err(anyhow!(
+            "failed to parse pumpswap global config {}",
+            global_config
+        ));
+    }
+    let protocol_fee_recipient = (0..8)
+        .map(|i| read_pubkey(&data, GLOBAL_CONFIG_FEE_RECIPIENTS + i * 32))
+        .find(|recipient| *recipient != Pubkey::default())
+        .ok_or(anyhow!(
+            "pumpswap global config has no protocol fee recipient"
+        ))?;
+    // configs before creator fees end at the recipients
+    let coin_creator_fee_bps = if data.len() >= GLOBAL_CONFIG_COIN_CREATOR_FEE + 8 {
+        read_u64(&data, GLOBAL_CONFIG_COIN_CREATOR_FEE)
+    } else {
+        0
+    };
+    Ok(PumpSwapConfig {
+        global_config,
+        fees: PumpSwapFees {
+            lp_fee_bps: read_u64(&data, GLOBAL_CONFIG_LP_FEE),
+            protocol_fee_bps: read_u64(&data, GLOBAL_CONFIG_PROTOCOL_FEE),
+            coin_creator_fee_bps,
+        },
+        protocol_fee_recipient,
+    })
+}
+
+/// `PoolNotFound` for a mint without a sol pumpswap pool
+pub fn pump_swap_pool_not_found(mint: &str) -> anyhow::Error {
+    ApiError::new(
+        ErrorCode::PoolNotFound,
+        format!("no pumpswap pool pairs {} with WSOL", mint),
+    )
+    .into()
+}
+
+/// All PumpSwap pools of `mint` against sol
+pub async fn find_pump_swap_pools(
+    rpc_client: Arc<solana_client::rpc_client::RpcClient>,
+    mint: &str,
+) -> Result<Vec<(Pubkey, PumpSwapPool)>> {
+    debug!("finding pumpswap pools by mint: {}", mint);
+    let mint = Pubkey::from_str(mint)?;
+    let program_id = Pubkey::from_str(PUMP_SWAP_PROGRAM)?;
+    let filters = Some(vec![
+        RpcFilterType::Memcmp(Memcmp::new_base58_encoded(
+            0,
+            &anchor_discriminator("account:Pool"),
+        )),
+        RpcFilterType::Memcmp(Memcmp::new_base58_encoded(POOL_BASE_MINT, &mint.to_bytes())),
+        RpcFilterType::Memcmp(Memcmp::new_base58_encoded(
+            POOL_QUOTE_MINT,
+            &spl_token::native_mint::ID.to_bytes(),
+        )),
+    ]);
+    let accounts =
+        common::rpc::get_program_accounts_with_filters(&rpc_client, program_id, filters)?;
+    accounts
+        .into_iter()
+        .map(|(pool_id, account)| Ok((pool_id, PumpSwapPool::from_account_data(&account.data)?)))
+        .collect()
+}
+
+// (base, quote) amounts held by the pool
+fn get_pump_swap_reserves(
+    rpc_client: &solana_client::rpc_client::RpcClient,
+    pool: &PumpSwapPool,
+) -> Result<(u64, u64)> {
+    let accounts = rpc_client
+        .get_multiple_accounts(&[pool.pool_base_token_account, pool.pool_quote_token_account])?;
+    let amounts: Vec<u64> = accounts
+        .iter()
+        .map(|account| {
+            account
+                .as_ref()
+                .and_then(|account| common_utils::unpack_token(&account.data).ok())
+                .map_or(0, |token| token.base.amount)
+        })
+        .collect();
+    Ok((amounts[0], amounts[1]))
+}
+
+/// The pool of `mint` with the deepest sol reserve, and its (base, quote) reserves
+pub async fn get_pump_swap_pool(
+    rpc_client: Arc<solana_client::rpc_client::RpcClient>,
+    mint: &str,
+) -> Result<(Pubkey, PumpSwapPool, (u64, u64))> {
+    let pools = find_pump_swap_pools(rpc_client.clone(), mint).await?;
+    let mut deepest: Option<(Pubkey, PumpSwapPool, (u64, u64))> = None;
+    for (pool_id, pool) in pools {
+        let reserves = get_pump_swap_reserves(&rpc_client, &pool)?;
+        if deepest
+            .as_ref()
+            .map_or(true, |(_, _, (_, quote))| reserves.1 > *quote)
+        {
+            deepest = Some((pool_id, pool, reserves));
+        }
+    }
+    deepest.ok_or_else(|| pump_swap_pool_not_found(mint))
+}
+
+/// (base_amount, quote_amount_threshold, expected_out) of a constant product swap net of fees,
+/// buys spend `amount_specified` lamports and sells `amount_specified` raw tokens
+pub fn pump_swap_quote(
+    base_reserve: u64,
+    quote_reserve: u64,
+    fees: &PumpSwapFees,
+    amount_specified: u64,
+    swap_direction: &SwapDirection,
+    slippage_bps: u64,
+) -> (u64, u64, u64) {
+    let base_reserve = base_reserve as u128;
+    let quote_reserve = quote_reserve as u128;
+    let fee_bps = fees.total_bps() as u128;
+    let ten_thousand = TEN_THOUSAND as u128;
+    match swap_direction {
+        SwapDirection::Buy => {
+            // the fees are charged on top of the quote the pool takes
+            let quote_in = amount_specified as u128 * ten_thousand / (ten_thousand + fee_bps);
+            let base_out = base_reserve * quote_in / (quote_reserve + quote_in);
+            let max_quote_in = max_amount_with_slippage(amount_specified, slippage_bps);
+            (base_out as u64, max_quote_in, base_out as u64)
+        }
+        SwapDirection::Sell => {
+            let base_in = amount_specified as u128;
+            let quote_out = quote_reserve * base_in / (base_reserve + base_in);
+            let quote_out = (quote_out * (ten_thousand - fee_bps) / ten_thousand) as u64;
+            let min_quote_out = min_amount_with_slippage(quote_out, slippage_bps);
+            (amount_specified, min_quote_out, quote_out)
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct PumpSwapQuote {
+    pub pool_id: Pubkey,
+    pub pool: PumpSwapPool,
+    pub config: PumpSwapConfig,
+    pub reserve_in: u64,
+    pub slippage_bps: u64,
+    /// tokens bought or sold
+    pub base_amount: u64,
+    /// max lamports in for buys, min lamports out for sells
+    pub quote_threshold: u64,
+    pub expected_out: u64,
+}
+
+/// Quote against the deepest pool of `mint`, `min_sol_out` replaces the slippage floor of sells
+pub async fn quote_pump_swap(
+    rpc_client: Arc<solana_client::rpc_client::RpcClient>,
+    mint: &str,
+    amount_specified: u64,
+    swap_direction: &SwapDirection,
+    slippage: Slippage,
+    min_sol_out: Option<u64>,
+) -> Result<PumpSwapQuote> {
+    let (pool_id, pool, (base_reserve, quote_reserve)) =
+        get_pump_swap_pool(rpc_client.clone(), mint).await?;
+    let config = get_pump_swap_config(&rpc_client)?;
+    let (reserve_in, reserve_out) = match swap_direction {
+        SwapDirection::Buy => (quote_reserve, base_reserve),
+        SwapDirection::Sell => (base_reserve, quote_reserve),
+    };
+    check_route(&pool_id, reserve_in, reserve_out)?;
+    let mut slippage_bps = slippage.resolve_bps(amount_specified, reserve_in);
+    let (base_amount, mut quote_threshold, expected_out) = pump_swap_quote(
+        base_reserve,
+        quote_reserve,
+        &config.fees,
+        amount_specified,
+        swap_direction,
+        slippage_bps,
+    );
+    check_quote(&pool_id, expected_out)?;
+    if let (SwapDirection::Sell, Some(min_sol_out)) = (swap_direction, min_sol_out) {
+        (quote_threshold, slippage_bps) = min_out_threshold(expected_out, min_sol_out)?;
+    }
+    Ok(PumpSwapQuote {
+        pool_id,
+        pool,
+        config,
+        reserve_in,
+        slippage_bps,
+        base_amount,
+        quote_threshold,
+        expected_out,
+    })
+}
+
+fn pump_swap_instruction(
+    quote: &PumpSwapQuote,
+    owner: &Pubkey,
+    user_quote_token_account: &Pubkey,
+    swap_direction: &SwapDirection,
+) -> Result<Instruction> {
+    let program_id = Pubkey::from_str(PUMP_SWAP_PROGRAM)?;
+    let native_mint = spl_token::native_mint::ID;
+    let pool = &quote.pool;
+    let method = match swap_direction {
+        SwapDirection::Buy => "global:buy",
+        SwapDirection::Sell => "global:sell",
+    };
+    // buy(base_amount_out, max_quote_amount_in), sell(base_amount_in, min_quote_amount_out)
+    let mut data = anchor_discriminator(method).to_vec();
+    data.extend_from_slice(&quote.base_amount.to_le_bytes());
+    data.extend_from_slice(&quote.quote_threshold.to_le_bytes());
+
+    let (event_authority, _) =
+        Pubkey::find_program_address(&[b"__event_authority".as_ref()], &program_id);
+    let (coin_creator_vault_authority, _) = Pubkey::find_program_address(
+        &[b"creator_vault".as_ref(), pool.coin_creator.as_ref()],
+        &program_id,
+    );
+    let protocol_fee_recipient = quote.config.protocol_fee_recipient;
+    Ok(Instruction::new_with_bytes(
+        program_id,
+        &data,
+        vec![
+            AccountMeta::new(quote.pool_id, false),
+            AccountMeta::new(*owner, true),
+            AccountMeta::new_readonly(quote.config.global_config, false),
+            AccountMeta::new_readonly(pool.base_mint, false),
+            AccountMeta::new_readonly(pool.quote_mint, false),
+            AccountMeta::new(get_associated_token_address(owner, &pool.base_mint), false),
+            AccountMeta::new(*user_quote_token_account, false),
+            AccountMeta::new(pool.pool_base_token_account, false),
+            AccountMeta::new(pool.pool_quote_token_account, false),
+            AccountMeta::new_readonly(protocol_fee_recipient, false),
+            AccountMeta::new(
+                get_associated_token_address(&protocol_fee_recipient, &native_mint),
+                false,
+            ),
+            AccountMeta::new_readonly(spl_token::ID, false),
+            AccountMeta::new_readonly(spl_token::ID, false),
+            AccountMeta::new_readonly(system_program::id(), false),
+            AccountMeta::new_readonly(Pubkey::from_str(ASSOCIATED_TOKEN_PROGRAM)?, false),
+            AccountMeta::new_readonly(event_authority, false),
+            AccountMeta::new_readonly(program_id, false),
+            AccountMeta::new(
+                get_associated_token_address(&coin_creator_vault_authority, &native_mint),
+                false,
+            ),
+            AccountMeta::new_readonly(coin_creator_vault_authority, false),
+        ],
+    ))
+}
+
+impl Pump {
+    /// Price of a graduated coin on its deepest PumpSwap pool
+    pub async fn get_pump_swap_price(&self, mint: &str) -> Result<PumpSwapInfo> {
+        let client = self
+            .client_blocking
+            .clone()
+            .context("failed to get rpc client")?;
+        let (pool_id, pool, (base_reserve, quote_reserve)) =
+            get_pump_swap_pool(client, mint).await?;
+        let base_decimals = self
+            .decimals_cache
+            .get(&self.client, &pool.base_mint)
+            .await?;
+        let base_amount = amount_to_ui_amount(base_reserve, base_decimals);
+        let quote_amount = amount_to_ui_amount(quote_reserve, spl_token::native_mint::DECIMALS);
+        let price = if base_amount > 0.0 {
+            quote_amount / base_amount
+        } else {
+            0.0
+        };
+        debug!(
+            "pumpswap pool[{}]: {}: {}, {}: {}, price: {} sol",
+            pool_id, pool.base_mint, base_amount, pool.quote_mint, quote_amount, price
+        );
+        Ok(PumpSwapInfo {
+            pool_id: pool_id.to_string(),
+            base: pool.base_mint.to_string(),
+            base_amount,
+            quote: pool.quote_mint.to_string(),
+            quote_amount,
+            price,
+        })
+    }
+
+    /// Swap a graduated coin on its deepest PumpSwap pool
+    pub async fn pump_swap(
+        &self,
+        mint_str: &str,
+        amount_in: f64,
+        swap_direction: SwapDirection,
+        in_type: SwapInType,
+        slippage: Slippage,
+        use_jito: bool,
+    ) -> Result<SwapOutcome> {
+        let owner = self.keypair.pubkey();
+        let mint = Pubkey::from_str(mint_str)
+            .map_err(|e| anyhow!("failed to parse mint pubkey: {}", e))?;
+        let program_id = spl_token::ID;
+        let native_mint = spl_token::native_mint::ID;
+        let client = self
+            .client_blocking
+            .clone()
+            .context("failed to get rpc client")?;
+
+        let (token_in, token_out) = match swap_direction {
+            SwapDirection::Buy => (native_mint, mint),
+            SwapDirection::Sell => (mint, native_mint),
+        };
+        let in_ata = get_associated_token_address(&owner, &token_in);
+
+        let mut create_instruction = None;
+        let mut close_instruction = None;
+
+        let (amount_specified, amount_ui_pretty) = match swap_direction {
+            SwapDirection::Buy => {
+                // the idempotent variant is a no-op if the ata already exists
+                create_instruction = Some(create_associated_token_account_idempotent(
+                    &owner,
+                    &owner,
+                    &token_out,
+                    &program_id,
+                ));
+                (
+                    to_base_units(amount_in, spl_token::native_mint::DECIMALS)?,
+                    (amount_in, spl_token::native_mint::DECIMALS),
+                )
+            }
+            SwapDirection::Sell => {
+                let in_account = token::get_account_info(
+                    self.client.clone(),
+                    self.keypair.clone(),
+                    &token_in,
+                    &in_ata,
+                )
+                .await?;
+                let decimals = self.decimals_cache.get(&self.client, &token_in).await?;
+                let amount = match in_type {
+                    SwapInType::Qty => to_base_units(amount_in, decimals)?,
+                    SwapInType::Pct => {
+                        let amount_in_pct = amount_in.min(1.0);
+                        if amount_in_pct == 1.0 {
+                            // sell all, close ata
+                            info!("sell all. will be close ATA for mint {}", token_in);
+                            close_instruction = Some(spl_token::instruction::close_account(
+                                &program_id,
+                                &in_ata,
+                                &owner,
+                                &owner,
+                                &vec![&owner],
+                            )?);
+                            in_account.base.amount
+                        } else {
+                            pct_of(in_account.base.amount, amount_in_pct)?
+                        }
+                    }
+                };
+                (amount, (amount_to_ui_amount(amount, decimals), decimals))
+            }
+        };
+        if amount_specified == 0 {
+            return Err(ApiError::new(ErrorCode::InvalidInput, "nothing to swap").into());
+        }
+
+        let quote = quote_pump_swap(
+            client.clone(),
+            mint_str,
+            amount_specified,
+            &swap_direction,
+            slippage,
+            self.min_sol_out,
+        )
+        .await?;
+        if let SwapDirection::Buy = swap_direction {
+            check_pool_liquidity(&quote.pool_id, quote.reserve_in)?;
+        }
+        let price_impact_pct = price_impact_pct(amount_specified, quote.reserve_in);
+        info!(
+            "pumpswap swap: {}, value: {:?} -> {}, pool: {}, base_amount: {}, quote_threshold: {}, price_impact: {:.4}%, slippage_bps: {}",
+            token_in,
+            amount_ui_pretty,
+            token_out,
+            quote.pool_id,
+            quote.base_amount,
+            quote.quote_threshold,
+            price_impact_pct,
+            quote.slippage_bps
+        );
+
+        // build instructions, sol goes through a tmp wsol account like raydium swaps
+        let mut instructions = vec![];
+        let seed = &format!("{}", Keypair::new().pubkey())[..32];
+        let wsol_account = Pubkey::create_with_seed(&owner, seed, &spl_token::id())?;
+        let rent = self
+            .client
+            .get_minimum_balance_for_rent_exemption(Account::LEN)
+            .await?;
+        let total_amount = match swap_direction {
+            SwapDirection::Buy => rent + quote.quote_threshold,
+            SwapDirection::Sell => rent,
+        };
+        instructions.push(system_instruction::create_account_with_seed(
+            &owner,
+            &wsol_account,
+            &owner,
+            seed,
+            total_amount,
+            Account::LEN as u64,
+            &spl_token::id(),
+        ));
+        instructions.push(spl_token::instruction::initialize_account(
+            &spl_token::id(),
+            &wsol_account,
+            &native_mint,
+            &owner,
+        )?);
+        if let Some(create_instruction) = create_instruction {
+            instructions.push(create_instruction);
+        }
+        instructions.push(pump_swap_instruction(
+            &quote,
+            &owner,
+            &wsol_account,
+            &swap_direction,
+        )?);
+        instructions.push(spl_token::instruction::close_account(
+            &program_id,
+            &wsol_account,
+            &owner,
+            &owner,
+            &vec![&owner],
+        )?);
+        if let Some(close_instruction) = close_instruction {
+            instructions.push(close_instruction);
+        }
+
+        let signatures =
+            tx::new_signed_and_send(&client, &self.keypair, instructions, use_jito).await?;
+        Ok(SwapOutcome {
+            signatures,
+            slippage_bps: quote.slippage_bps,
+            amount_in: amount_specified,
+            expected_out: quote.expected_out,
+            price_impact_pct,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_min_out_threshold() {
+        assert_eq!(
+            min_out_threshold(1_000_000, 950_000).unwrap(),
+            (950_000, 500)
+        );
+        assert_eq!(
+            min_out_threshold(1_000_000, 1_000_000).unwrap(),
+            (1_000_000, 0)
+        );
+        let err = min_out_threshold(900_000, 950_000).unwrap_err();
+        assert_eq!(ApiError::from(&err).code, ErrorCode::SlippageExceeded);
+    }
+
+    #[test]
+    fn test_check_curve_route() {
+        let bonding_curve = Pubkey::new_unique();
+        let mut curve = BondingCurveAccount {
+            discriminator: 0,
+            virtual_token_reserves: INITIAL_VIRTUAL_TOKEN_RESERVES,
+            virtual_sol_reserves: INITIAL_VIRTUAL_SOL_RESERVES,
+            real_token_reserves: INITIAL_REAL_TOKEN_RESERVES,
+            real_sol_reserves: 0,
+            token_total_supply: TOKEN_TOTAL_SUPPLY,
+            complete: false,
+        };
+        assert!(check_curve_route(&bonding_curve, &curve, &SwapDirection::Buy).is_ok());
+        // a fresh curve holds no sol to pay sellers
+        let err = check_curve_route(&bonding_curve, &curve, &SwapDirection::Sell).unwrap_err();
+        assert_eq!(ApiError::from(&err).code, ErrorCode::NoRoute);
+
+        curve.real_sol_reserves = 1_000_000_000;
+        curve.real_token_reserves = 0;
+        assert!(check_curve_route(&bonding_curve, &curve, &SwapDirection::Sell).is_ok());
+        let err = check_curve_route(&bonding_curve, &curve, &SwapDirection::Buy).unwrap_err();
+        assert!(err.to_string().contains(&bonding_curve.to_string()));
+
+        curve.complete = true;
+        let err = check_curve_route(&bonding_curve, &curve, &SwapDirection::Sell).unwrap_err();
+        assert!(err.to_string().contains("is complete"));
+    }
+
+    #[test]
+    fn test_pump_swap_quote() {
+        let fees = PumpSwapFees {
+            lp_fee_bps: 20,
+            protocol_fee_bps: 5,
+            coin_creator_fee_bps: 0,
+        };
+        // 1_000_000 tokens against 100 sol
+        let (base_reserve, quote_reserve) = (1_000_000_000_000, 100_000_000_000);
+
+        let (base_amount, max_quote_in, expected_out) = pump_swap_quote(
+            base_reserve,
+            quote_reserve,
+            &fees,
+            1_000_000_000,
+            &SwapDirection::Buy,
+            100,
+        );
+        assert_eq!(max_quote_in, 1_010_000_000);
+        assert_eq!(base_amount, expected_out);
+        // 1 sol is about 1% of the pool, less the fees and the price impact
+        assert!(base_amount < 9_900_990_099 && base_amount > 9_850_000_000);
+
+        let (base_amount, min_quote_out, expected_out) = pump_swap_quote(
+            base_reserve,
+            quote_reserve,
+            &fees,
+            10_000_000_000,
+            &SwapDirection::Sell,
+            100,
+        );
+        assert_eq!(base_amount, 10_000_000_000);
+        assert_eq!(expected_out, 987_623_761);
+        assert_eq!(min_quote_out, expected_out * 9900 / 10000);
+    }
+}
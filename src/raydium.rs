@@ -2,6 +2,7 @@ use std::env;
 
 use amm_cli::AmmSwapInfoResult;
 use anyhow::{anyhow, Context, Result};
+use common::common_utils;
 use raydium_amm::state::{AmmInfo, Loadable};
 use reqwest::Proxy;
 use serde::{Deserialize, Serialize};
@@ -19,28 +20,56 @@ use solana_sdk::{
     system_instruction,
 };
 use spl_associated_token_account::{
-    get_associated_token_address, instruction::create_associated_token_account,
+    get_associated_token_address, instruction::create_associated_token_account_idempotent,
 };
-use spl_token::{amount_to_ui_amount, ui_amount_to_amount};
-use spl_token_client::token::TokenError;
+use spl_token::amount_to_ui_amount;
 use std::{str::FromStr, sync::Arc};
 
 use crate::{
+    constants::{get_network, Network},
+    error::{ApiError, ErrorCode},
     get_rpc_client_blocking,
-    swap::{SwapDirection, SwapInType},
-    token, tx,
+    ledger::now_millis,
+    pump::TEN_THOUSAND,
+    swap::{
+        check_pool_liquidity, check_quote, check_route, pct_of, price_impact_pct, to_base_units,
+        Slippage, SwapDirection, SwapInType, SwapOutcome,
+    },
+    token::{self, DecimalsCache},
+    tx,
 };
 use spl_token::state::Account;
 
-use tracing::{debug, error, info};
+use tracing::{debug, info};
 
 pub const AMM_PROGRAM: &str = "675kPX9MHTjS2zt1qfr1NYHuzeLXfQM9H24wFSUt1Mp8";
+pub const AMM_PROGRAM_DEVNET: &str = "HWy1jotHpo6UqeQxx49dpYYdQB8wj9Qk9MdxwjLvDHB8";
+
+/// Raydium amm v4 program of the NETWORK cluster
+pub fn amm_program() -> &'static str {
+    match get_network() {
+        Network::Mainnet => AMM_PROGRAM,
+        Network::Devnet => AMM_PROGRAM_DEVNET,
+    }
+}
+
+pub fn amm_program_id() -> Result<Pubkey> {
+    Ok(Pubkey::from_str(amm_program())?)
+}
+
+fn get_raydium_api_url() -> &'static str {
+    match get_network() {
+        Network::Mainnet => "https://api-v3.raydium.io",
+        Network::Devnet => "https://api-v3-devnet.raydium.io",
+    }
+}
 
 pub struct Raydium {
     pub client: Arc<RpcClient>,
     pub keypair: Arc<Keypair>,
     pub client_blocking: Option<Arc<solana_client::rpc_client::RpcClient>>,
     pub pool_id: Option<String>,
+    pub decimals_cache: Arc<DecimalsCache>,
 }
 
 impl Raydium {
@@ -50,9 +79,15 @@ impl Raydium {
             keypair,
             client_blocking: None,
             pool_id: None,
+            decimals_cache: Arc::new(DecimalsCache::default()),
         }
     }
 
+    pub fn with_decimals_cache(&mut self, decimals_cache: Arc<DecimalsCache>) -> &mut Self {
+        self.decimals_cache = decimals_cache;
+        self
+    }
+
     pub fn with_blocking_client(
         &mut self,
         client: Arc<solana_client::rpc_client::RpcClient>,
@@ -72,36 +107,20 @@ impl Raydium {
         amount_in: f64,
         swap_direction: SwapDirection,
         in_type: SwapInType,
-        slippage: u64,
+        slippage: Slippage,
         use_jito: bool,
-    ) -> Result<Vec<String>> {
-        // slippage_bps = 50u64; // 0.5%
-        let slippage_bps = slippage * 100;
+    ) -> Result<SwapOutcome> {
         let owner = self.keypair.pubkey();
         let mint = Pubkey::from_str(mint_str)
             .map_err(|e| anyhow!("failed to parse mint pubkey: {}", e))?;
         let program_id = spl_token::ID;
         let native_mint = spl_token::native_mint::ID;
 
-        let (amm_pool_id, pool_state) = get_pool_state(
-            self.client_blocking.clone().unwrap(),
-            self.pool_id.as_deref(),
-            Some(mint_str),
-        )
-        .await?;
-        // debug!("pool_state: {:#?}", pool_state);
-
-        let (token_in, token_out, user_input_token, swap_base_in) = match (
-            swap_direction.clone(),
-            pool_state.coin_vault_mint == native_mint,
-        ) {
-            (SwapDirection::Buy, true) => (native_mint, mint, pool_state.coin_vault, true),
-            (SwapDirection::Buy, false) => (native_mint, mint, pool_state.pc_vault, true),
-            (SwapDirection::Sell, true) => (mint, native_mint, pool_state.pc_vault, true),
-            (SwapDirection::Sell, false) => (mint, native_mint, pool_state.coin_vault, true),
+        let (token_in, token_out) = match swap_direction {
+            SwapDirection::Buy => (native_mint, mint),
+            SwapDirection::Sell => (mint, native_mint),
         };
-
-        debug!("token_in:{token_in}, token_out:{token_out}, user_input_token:{user_input_token}, swap_base_in:{swap_base_in}");
+        let swap_base_in = true;
 
         let in_ata = get_associated_token_address(&owner, &token_in);
         let out_ata = get_associated_token_address(&owner, &token_out);
@@ -111,40 +130,16 @@ impl Raydium {
 
         let (amount_specified, amount_ui_pretty) = match swap_direction {
             SwapDirection::Buy => {
-                // Create base ATA if it doesn't exist.
-                match token::get_account_info(
-                    self.client.clone(),
-                    self.keypair.clone(),
+                // Create base ATA, the idempotent variant is a no-op if it already exists
+                create_instruction = Some(create_associated_token_account_idempotent(
+                    &owner,
+                    &owner,
                     &token_out,
-                    &out_ata,
-                )
-                .await
-                {
-                    Ok(_) => debug!("base ata exists. skipping creation.."),
-                    Err(TokenError::AccountNotFound) | Err(TokenError::AccountInvalidOwner) => {
-                        info!(
-                            "base ATA for mint {} does not exist. will be create",
-                            token_out
-                        );
-                        // token::create_associated_token_account(
-                        //     self.client.clone(),
-                        //     self.keypair.clone(),
-                        //     &token_out,
-                        //     &owner,
-                        // )
-                        // .await?;
-                        create_instruction = Some(create_associated_token_account(
-                            &owner,
-                            &owner,
-                            &token_out,
-                            &program_id,
-                        ));
-                    }
-                    Err(error) => error!("error retrieving out ATA: {}", error),
-                }
+                    &program_id,
+                ));
 
                 (
-                    ui_amount_to_amount(amount_in, spl_token::native_mint::DECIMALS),
+                    to_base_units(amount_in, spl_token::native_mint::DECIMALS)?,
                     (amount_in, spl_token::native_mint::DECIMALS),
                 )
             }
@@ -156,11 +151,9 @@ impl Raydium {
                     &in_ata,
                 )
                 .await?;
-                let in_mint =
-                    token::get_mint_info(self.client.clone(), self.keypair.clone(), &token_in)
-                        .await?;
+                let decimals = self.decimals_cache.get(&self.client, &token_in).await?;
                 let amount = match in_type {
-                    SwapInType::Qty => ui_amount_to_amount(amount_in, in_mint.base.decimals),
+                    SwapInType::Qty => to_base_units(amount_in, decimals)?,
                     SwapInType::Pct => {
                         let amount_in_pct = amount_in.min(1.0);
                         if amount_in_pct == 1.0 {
@@ -175,33 +168,43 @@ impl Raydium {
                             )?);
                             in_account.base.amount
                         } else {
-                            (amount_in_pct * 100.0) as u64 * in_account.base.amount / 100
+                            pct_of(in_account.base.amount, amount_in_pct)?
                         }
                     }
                 };
-                (
-                    amount,
-                    (
-                        amount_to_ui_amount(amount, in_mint.base.decimals),
-                        in_mint.base.decimals,
-                    ),
-                )
+                (amount, (amount_to_ui_amount(amount, decimals), decimals))
             }
         };
 
-        let amm_program = Pubkey::from_str(AMM_PROGRAM)?;
-        debug!("amm pool id: {amm_pool_id}");
+        let amm_program = amm_program_id()?;
         let client = get_rpc_client_blocking()?;
-        let swap_info_result = amm_cli::calculate_swap_info(
-            &client,
-            amm_program,
+        let quote = quote_swap(
+            self.client_blocking.clone().unwrap(),
+            self.pool_id.as_deref(),
+            mint_str,
+            amount_specified,
+            &swap_direction,
+            slippage,
+        )
+        .await?;
+        if let SwapDirection::Buy = swap_direction {
+            check_pool_liquidity(&quote.amm_pool_id, quote.reserve_in)?;
+        }
+        let price_impact_pct = price_impact_pct(amount_specified, quote.reserve_in);
+        let RaydiumQuote {
             amm_pool_id,
             user_input_token,
-            amount_specified,
             slippage_bps,
-            swap_base_in,
-        )?;
-        let other_amount_threshold = swap_info_result.other_amount_threshold;
+            expected_out,
+            other_amount_threshold,
+            swap_info: swap_info_result,
+            ..
+        } = quote;
+        debug!("token_in:{token_in}, token_out:{token_out}, user_input_token:{user_input_token}, swap_base_in:{swap_base_in}");
+        debug!(
+            "amm pool id: {}, price_impact: {:.4}%, slippage_bps: {}",
+            amm_pool_id, price_impact_pct, slippage_bps
+        );
 
         info!("swap_info_result: {:#?}", swap_info_result);
 
@@ -306,10 +309,134 @@ impl Raydium {
             return Err(anyhow!("instructions is empty, no tx required"));
         }
 
-        tx::new_signed_and_send(&client, &self.keypair, instructions, use_jito).await
+        let signatures = match tx::get_raydium_lookup_table() {
+            Some(key) => {
+                let lookup_table = tx::load_lookup_table(&client, &key)?;
+                let lookup_tables = [lookup_table];
+                let options = tx::SendOptions {
+                    use_jito,
+                    lookup_tables: &lookup_tables,
+                    ..Default::default()
+                };
+                tx::new_signed_and_send_with_options(&client, &self.keypair, instructions, options)
+                    .await?
+            }
+            None => tx::new_signed_and_send(&client, &self.keypair, instructions, use_jito).await?,
+        };
+        Ok(SwapOutcome {
+            signatures,
+            slippage_bps,
+            amount_in: amount_specified,
+            expected_out,
+            price_impact_pct,
+        })
+    }
+}
+
+/// (token_in, token_out, user_input_token) when swapping `mint` against sol in the pool
+pub fn swap_sides(
+    pool_state: &AmmInfo,
+    mint: Pubkey,
+    swap_direction: &SwapDirection,
+) -> (Pubkey, Pubkey, Pubkey) {
+    let native_mint = spl_token::native_mint::ID;
+    match (swap_direction, pool_state.coin_vault_mint == native_mint) {
+        (SwapDirection::Buy, true) => (native_mint, mint, pool_state.coin_vault),
+        (SwapDirection::Buy, false) => (native_mint, mint, pool_state.pc_vault),
+        (SwapDirection::Sell, true) => (mint, native_mint, pool_state.pc_vault),
+        (SwapDirection::Sell, false) => (mint, native_mint, pool_state.coin_vault),
     }
 }
 
+/// Whether a (coin, pc) mint pair is `mint` against wsol
+pub fn pairs_with_sol(coin_mint: &Pubkey, pc_mint: &Pubkey, mint: &Pubkey) -> bool {
+    let native_mint = spl_token::native_mint::ID;
+    (*coin_mint == *mint && *pc_mint == native_mint)
+        || (*coin_mint == native_mint && *pc_mint == *mint)
+}
+
+// threshold = expected * (1 - slippage), reverse it to get the quoted output
+pub fn expected_out_from_threshold(other_amount_threshold: u64, slippage_bps: u64) -> u64 {
+    if slippage_bps < TEN_THOUSAND {
+        (other_amount_threshold as u128 * TEN_THOUSAND as u128
+            / (TEN_THOUSAND - slippage_bps) as u128) as u64
+    } else {
+        other_amount_threshold
+    }
+}
+
+/// Raw (coin, pc) vault balances of an amm pool
+pub fn get_vault_amounts(
+    client: &solana_client::rpc_client::RpcClient,
+    pool_state: &AmmInfo,
+) -> Result<(u64, u64)> {
+    let load_pubkeys = vec![pool_state.coin_vault, pool_state.pc_vault];
+    let rsps = common::rpc::get_multiple_accounts(client, &load_pubkeys)?;
+    let coin_vault = rsps[0]
+        .as_ref()
+        .ok_or_else(|| ApiError::new(ErrorCode::PoolNotFound, "coin vault account not found"))?;
+    let pc_vault = rsps[1]
+        .as_ref()
+        .ok_or_else(|| ApiError::new(ErrorCode::PoolNotFound, "pc vault account not found"))?;
+    let coin_vault = common_utils::unpack_token(&coin_vault.data)?;
+    let pc_vault = common_utils::unpack_token(&pc_vault.data)?;
+    Ok((coin_vault.base.amount, pc_vault.base.amount))
+}
+
+pub struct RaydiumQuote {
+    pub amm_pool_id: Pubkey,
+    /// vault of the input token
+    pub user_input_token: Pubkey,
+    pub reserve_in: u64,
+    pub slippage_bps: u64,
+    pub expected_out: u64,
+    pub other_amount_threshold: u64,
+    pub swap_info: AmmSwapInfoResult,
+}
+
+/// Quote against `pool_id`, or the pool of `mint` when unset
+pub async fn quote_swap(
+    rpc_client: Arc<solana_client::rpc_client::RpcClient>,
+    pool_id: Option<&str>,
+    mint: &str,
+    amount_specified: u64,
+    swap_direction: &SwapDirection,
+    slippage: Slippage,
+) -> Result<RaydiumQuote> {
+    let (amm_pool_id, pool_state) = get_pool_state(rpc_client.clone(), pool_id, Some(mint)).await?;
+    check_pool_status(&amm_pool_id, &pool_state)?;
+    let (_, _, user_input_token) = swap_sides(&pool_state, Pubkey::from_str(mint)?, swap_direction);
+    let (coin_amount, pc_amount) = get_vault_amounts(&rpc_client, &pool_state)?;
+    let (reserve_in, reserve_out) = if user_input_token == pool_state.coin_vault {
+        (coin_amount, pc_amount)
+    } else {
+        (pc_amount, coin_amount)
+    };
+    check_route(&amm_pool_id, reserve_in, reserve_out)?;
+    let slippage_bps = slippage.resolve_bps(amount_specified, reserve_in);
+    let swap_info = amm_cli::calculate_swap_info(
+        &rpc_client,
+        amm_program_id()?,
+        amm_pool_id,
+        user_input_token,
+        amount_specified,
+        slippage_bps,
+        true,
+    )?;
+    let other_amount_threshold = swap_info.other_amount_threshold;
+    let expected_out = expected_out_from_threshold(other_amount_threshold, slippage_bps);
+    check_quote(&amm_pool_id, expected_out)?;
+    Ok(RaydiumQuote {
+        amm_pool_id,
+        user_input_token,
+        reserve_in,
+        slippage_bps,
+        expected_out,
+        other_amount_threshold,
+        swap_info,
+    })
+}
+
 pub fn amm_swap(
     amm_program: &Pubkey,
     result: AmmSwapInfoResult,
@@ -376,10 +503,35 @@ pub async fn get_pool_state(
 ) -> Result<(Pubkey, AmmInfo)> {
     if let Some(pool_id) = pool_id {
         debug!("finding pool state by pool_id: {}", pool_id);
-        let amm_pool_id = Pubkey::from_str(pool_id)?;
+        let amm_pool_id = Pubkey::from_str(pool_id).map_err(|e| {
+            ApiError::new(
+                ErrorCode::InvalidInput,
+                format!("invalid pool id {}: {}", pool_id, e),
+            )
+        })?;
         let pool_state =
             common::rpc::get_account::<raydium_amm::state::AmmInfo>(&rpc_client, &amm_pool_id)?
-                .ok_or(anyhow!("NotFoundPool: pool state not found"))?;
+                .ok_or_else(|| {
+                    ApiError::new(
+                        ErrorCode::PoolNotFound,
+                        format!("pool {} not found", amm_pool_id),
+                    )
+                })?;
+        // a caller supplied pool must trade the requested mint against sol
+        if let Some(mint) = mint {
+            let mint = Pubkey::from_str(mint)?;
+            if !pairs_with_sol(
+                &pool_state.coin_vault_mint,
+                &pool_state.pc_vault_mint,
+                &mint,
+            ) {
+                return Err(ApiError::new(
+                    ErrorCode::InvalidInput,
+                    format!("pool {} does not pair {} with WSOL", amm_pool_id, mint),
+                )
+                .into());
+            }
+        }
         Ok((amm_pool_id, pool_state))
     } else {
         if let Some(mint) = mint {
@@ -390,9 +542,7 @@ pub async fn get_pool_state(
             // find pool by mint via raydium api
             let pool_data = get_pool_info(&spl_token::native_mint::ID.to_string(), mint).await;
             if let Ok(pool_data) = pool_data {
-                let pool = pool_data
-                    .get_pool()
-                    .ok_or(anyhow!("NotFoundPool: pool not found in raydium api"))?;
+                let pool = pool_data.get_pool().ok_or_else(|| pool_not_found(mint))?;
                 let amm_pool_id = Pubkey::from_str(&pool.id)?;
                 debug!(
                     "finding pool state by raydium api: {}, {:#?}",
@@ -402,12 +552,17 @@ pub async fn get_pool_state(
                     &rpc_client,
                     &amm_pool_id,
                 )?
-                .ok_or(anyhow!("NotFoundPool: pool state not found"))?;
+                .ok_or_else(|| {
+                    ApiError::new(
+                        ErrorCode::PoolNotFound,
+                        format!("pool {} not found", amm_pool_id),
+                    )
+                })?;
                 return Ok((amm_pool_id, pool_state));
             }
-            Err(anyhow!("NotFoundPool: pool state not found"))
+            Err(pool_not_found(mint))
         } else {
-            Err(anyhow!("NotFoundPool: pool state not found"))
+            Err(ApiError::new(ErrorCode::InvalidInput, "pool_id or mint is required").into())
         }
     }
 }
@@ -416,65 +571,128 @@ pub async fn get_pool_state_by_mint(
     rpc_client: Arc<solana_client::rpc_client::RpcClient>,
     mint: &str,
 ) -> Result<(Pubkey, AmmInfo)> {
+    find_pools_by_mint(rpc_client, mint)
+        .await?
+        .into_iter()
+        .next()
+        .ok_or_else(|| pool_not_found(mint))
+}
+
+/// Reject swaps on a pool whose status does not take them, or that has not opened yet
+pub fn check_pool_status(pool_id: &Pubkey, pool_state: &AmmInfo) -> Result<()> {
+    check_status(
+        pool_id,
+        pool_state.status,
+        pool_state.state_data.pool_open_time,
+        now_millis() / 1000,
+    )
+}
+
+// amm statuses that take swaps: Initialized, SwapOnly and WaitingTrade once open,
+// Disabled, WithdrawOnly, LiquidityOnly and OrderBookOnly do not
+fn check_status(pool_id: &Pubkey, status: u64, open_time: u64, now: u64) -> Result<()> {
+    match status {
+        1 | 6 => Ok(()),
+        7 if open_time <= now => Ok(()),
+        7 => Err(ApiError::new(
+            ErrorCode::PoolPaused,
+            format!("raydium pool {} opens for swaps at {}", pool_id, open_time),
+        )
+        .into()),
+        _ => Err(ApiError::new(
+            ErrorCode::PoolPaused,
+            format!(
+                "raydium pool {} does not take swaps, status {}",
+                pool_id, status
+            ),
+        )
+        .into()),
+    }
+}
+
+/// `PoolNotFound` for a mint without a sol pool
+pub fn pool_not_found(mint: &str) -> anyhow::Error {
+    ApiError::new(
+        ErrorCode::PoolNotFound,
+        format!("no raydium pool pairs {} with WSOL", mint),
+    )
+    .into()
+}
+
+/// All AMM pools pairing `mint` with sol, in both coin/pc orders
+pub async fn find_pools_by_mint(
+    rpc_client: Arc<solana_client::rpc_client::RpcClient>,
+    mint: &str,
+) -> Result<Vec<(Pubkey, AmmInfo)>> {
     debug!("finding pool state by mint: {}", mint);
-    // (pc_mint, coin_mint)
+    let mint = Pubkey::from_str(mint)?;
+    // (coin_mint, pc_mint)
     let pairs = vec![
         // pump pool
-        (
-            Some(spl_token::native_mint::ID),
-            Pubkey::from_str(mint).ok(),
-        ),
+        (spl_token::native_mint::ID, mint),
         // general pool
-        (
-            Pubkey::from_str(mint).ok(),
-            Some(spl_token::native_mint::ID),
-        ),
+        (mint, spl_token::native_mint::ID),
     ];
 
     let pool_len = core::mem::size_of::<raydium_amm::state::AmmInfo>() as u64;
-    let amm_program = Pubkey::from_str(AMM_PROGRAM)?;
-    // Find matching AMM pool from mint pairs by filter
-    let mut found_pools = None;
+    let amm_program = amm_program_id()?;
+    let mut found_pools = vec![];
     for (coin_mint, pc_mint) in pairs {
         debug!(
-            "get_pool_state_by_mint filter: coin_mint: {:?}, pc_mint: {:?}",
+            "find_pools_by_mint filter: coin_mint: {:?}, pc_mint: {:?}",
             coin_mint, pc_mint
         );
-        let filters = match (coin_mint, pc_mint) {
-            (None, None) => Some(vec![RpcFilterType::DataSize(pool_len)]),
-            (Some(coin_mint), None) => Some(vec![
-                RpcFilterType::Memcmp(Memcmp::new_base58_encoded(400, &coin_mint.to_bytes())),
-                RpcFilterType::DataSize(pool_len),
-            ]),
-            (None, Some(pc_mint)) => Some(vec![
-                RpcFilterType::Memcmp(Memcmp::new_base58_encoded(432, &pc_mint.to_bytes())),
-                RpcFilterType::DataSize(pool_len),
-            ]),
-            (Some(coin_mint), Some(pc_mint)) => Some(vec![
-                RpcFilterType::Memcmp(Memcmp::new_base58_encoded(400, &coin_mint.to_bytes())),
-                RpcFilterType::Memcmp(Memcmp::new_base58_encoded(432, &pc_mint.to_bytes())),
-                RpcFilterType::DataSize(pool_len),
-            ]),
-        };
+        let filters = Some(vec![
+            RpcFilterType::Memcmp(Memcmp::new_base58_encoded(400, &coin_mint.to_bytes())),
+            RpcFilterType::Memcmp(Memcmp::new_base58_encoded(432, &pc_mint.to_bytes())),
+            RpcFilterType::DataSize(pool_len),
+        ]);
         let pools =
-            common::rpc::get_program_accounts_with_filters(&rpc_client, amm_program, filters)
-                .unwrap();
-        if !pools.is_empty() {
-            found_pools = Some(pools);
-            break;
+            common::rpc::get_program_accounts_with_filters(&rpc_client, amm_program, filters)?;
+        for (pool_id, account) in pools {
+            let pool_state = raydium_amm::state::AmmInfo::load_from_bytes(&account.data)?;
+            found_pools.push((pool_id, pool_state.clone()));
         }
     }
+    Ok(found_pools)
+}
 
-    match found_pools {
-        Some(pools) => {
-            let pool = &pools[0];
-            let pool_state = raydium_amm::state::AmmInfo::load_from_bytes(&pools[0].1.data)?;
-            Ok((pool.0, pool_state.clone()))
-        }
-        None => {
-            return Err(anyhow!("NotFoundPool: pool state not found"));
-        }
+/// A pool found for the mint, with what the selection policy ranks on
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct PoolCandidate {
+    pub pool_id: String,
+    pub program_id: String,
+    /// raw lamports in the sol vault
+    pub sol_liquidity: u64,
+    pub fee_bps: u64,
+}
+
+pub async fn get_pool_candidates(
+    rpc_client: Arc<solana_client::rpc_client::RpcClient>,
+    mint: &str,
+) -> Result<Vec<PoolCandidate>> {
+    let pools = find_pools_by_mint(rpc_client.clone(), mint).await?;
+    let mut candidates = vec![];
+    for (pool_id, pool_state) in pools {
+        let (coin_amount, pc_amount) = get_vault_amounts(&rpc_client, &pool_state)?;
+        let sol_liquidity = if pool_state.coin_vault_mint == spl_token::native_mint::ID {
+            coin_amount
+        } else {
+            pc_amount
+        };
+        let fee_bps = if pool_state.fees.swap_fee_denominator == 0 {
+            0
+        } else {
+            pool_state.fees.swap_fee_numerator * TEN_THOUSAND / pool_state.fees.swap_fee_denominator
+        };
+        candidates.push(PoolCandidate {
+            pool_id: pool_id.to_string(),
+            program_id: amm_program().to_string(),
+            sol_liquidity,
+            fee_bps,
+        });
     }
+    Ok(candidates)
 }
 
 // get pool info
@@ -488,7 +706,7 @@ pub async fn get_pool_info(mint1: &str, mint2: &str) -> Result<PoolData> {
     let client = client_builder.build()?;
 
     let result = client
-        .get("https://api-v3.raydium.io/pools/info/mint")
+        .get(format!("{}/pools/info/mint", get_raydium_api_url()))
         .query(&[
             ("mint1", mint1),
             ("mint2", mint2),
@@ -499,10 +717,21 @@ pub async fn get_pool_info(mint1: &str, mint2: &str) -> Result<PoolData> {
             ("page", "1"),
         ])
         .send()
-        .await?
+        .await
+        .map_err(|e| {
+            ApiError::new(
+                ErrorCode::RpcError,
+                format!("raydium api request failed: {}", e),
+            )
+        })?
         .json::<PoolInfo>()
         .await
-        .context("Failed to parse pool info JSON")?;
+        .map_err(|e| {
+            ApiError::new(
+                ErrorCode::RpcError,
+                format!("failed to parse raydium pool info: {}", e),
+            )
+        })?;
     Ok(result.data)
 }
 // get pool info by ids
@@ -516,7 +745,7 @@ pub async fn get_pool_info_by_id(pool_id: &str) -> Result<PoolData> {
     let client = client_builder.build()?;
 
     let result = client
-        .get("https://api-v3.raydium.io/pools/info/ids")
+        .get(format!("{}/pools/info/ids", get_raydium_api_url()))
         .query(&[("ids", pool_id)])
         .send()
         .await?
@@ -570,3 +799,30 @@ pub struct Mint {
     pub name: String,
     pub decimals: u8,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pairs_with_sol() {
+        let mint = Pubkey::new_unique();
+        let native_mint = spl_token::native_mint::ID;
+        assert!(pairs_with_sol(&mint, &native_mint, &mint));
+        assert!(pairs_with_sol(&native_mint, &mint, &mint));
+        assert!(!pairs_with_sol(&mint, &Pubkey::new_unique(), &mint));
+        assert!(!pairs_with_sol(&Pubkey::new_unique(), &native_mint, &mint));
+    }
+
+    #[test]
+    fn test_check_status() {
+        let pool = Pubkey::new_unique();
+        assert!(check_status(&pool, 6, 0, 1_000).is_ok());
+        assert!(check_status(&pool, 7, 1_000, 1_000).is_ok());
+        for (status, open_time) in [(2, 0), (3, 0), (4, 0), (7, 2_000)] {
+            let err = check_status(&pool, status, open_time, 1_000).unwrap_err();
+            assert_eq!(ApiError::from(&err).code, ErrorCode::PoolPaused);
+            assert!(err.to_string().contains(&pool.to_string()));
+        }
+    }
+}
@@ -0,0 +1,111 @@
+use std::{future::Future, time::Instant};
+
+use axum::{
+    extract::Request,
+    http::{HeaderName, HeaderValue},
+    middleware::Next,
+    response::Response,
+};
+use tracing::{info, info_span, Instrument};
+
+pub const REQUEST_ID_HEADER: &str = "x-request-id";
+
+tokio::task_local! {
+    // id of the request the current task serves, see `assign_request_id`
+    static REQUEST_ID: String;
+}
+
+/// Id of the request being served, if any
+pub fn current() -> Option<String> {
+    REQUEST_ID
+        .try_with(|id| id.clone())
+        .ok()
+        .filter(|id| !id.is_empty())
+}
+
+/// Run `f` under the request id of the current task, for work spawned by a handler
+pub fn propagate<F: Future>(f: F) -> impl Future<Output = F::Output> {
+    let id = current().unwrap_or_default();
+    REQUEST_ID.scope(id, f)
+}
+
+/// Random uuid v4
+pub fn new_request_id() -> String {
+    let mut bytes: [u8; 16] = rand::random();
+    bytes[6] = (bytes[6] & 0x0f) | 0x40;
+    bytes[8] = (bytes[8] & 0x3f) | 0x80;
+    let hex: String = bytes.iter().map(|b| format!("{:02x}", b)).collect();
+    format!(
+        "{}-{}-{}-{}-{}",
+        &hex[..8],
+        &hex[8..12],
+        &hex[12..16],
+        &hex[16..20],
+        &hex[20..]
+    )
+}
+
+// ids forwarded by a proxy are kept when they are short and printable
+fn is_valid_request_id(id: &str) -> bool {
+    !id.is_empty()
+        && id.len() <= 64
+        && id
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+}
+
+/// Tag the request with `X-Request-Id`, the incoming one or a new uuid
+///
+/// Handlers run in a `request` span carrying the id, error bodies include it as `request_id`
+/// and it is echoed back in the response header.
+pub async fn assign_request_id(req: Request, next: Next) -> Response {
+    let id = req
+        .headers()
+        .get(REQUEST_ID_HEADER)
+        .and_then(|v| v.to_str().ok())
+        .filter(|id| is_valid_request_id(id))
+        .map(str::to_string)
+        .unwrap_or_else(new_request_id);
+    let span = info_span!(
+        "request",
+        id = %id,
+        method = %req.method(),
+        path = %req.uri().path()
+    );
+    let started = Instant::now();
+    let mut response = REQUEST_ID
+        .scope(id.clone(), next.run(req).instrument(span.clone()))
+        .await;
+    span.in_scope(|| {
+        info!(
+            "{} in {}ms",
+            response.status(),
+            started.elapsed().as_millis()
+        )
+    });
+    if let Ok(value) = HeaderValue::from_str(&id) {
+        response
+            .headers_mut()
+            .insert(HeaderName::from_static(REQUEST_ID_HEADER), value);
+    }
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_request_id() {
+        let id = new_request_id();
+        assert_eq!(id.len(), 36);
+        assert_eq!(&id[14..15], "4");
+        assert!(is_valid_request_id(&id));
+        assert_ne!(id, new_request_id());
+
+        assert!(is_valid_request_id("lb-1234_abc"));
+        assert!(!is_valid_request_id(""));
+        assert!(!is_valid_request_id("id with spaces"));
+        assert!(!is_valid_request_id(&"a".repeat(65)));
+    }
+}
@@ -1,3 +1,6 @@
+use serde::Serialize;
+
+#[derive(Debug, Clone, Serialize)]
 pub struct Signal {
     pub name: String,
     pub value: f64,
@@ -20,14 +23,119 @@ impl Signal {
     }
 }
 
-pub fn process_signal(signal: &Signal) {
-    if signal.should_buy() {
-        println!("Executing buy action for signal: {}", signal.name);
-        // Add buy logic here
-    } else if signal.should_sell() {
-        println!("Executing sell action for signal: {}", signal.name);
-        // Add sell logic here
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Decision {
+    Buy,
+    Sell,
+    Hold,
+}
+
+/// Signals voting on one mint, each buy or sell signal is one vote
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct SignalSet {
+    pub signals: Vec<Signal>,
+}
+
+impl SignalSet {
+    /// Momentum and trend of `prices`, oldest first, moves under `threshold_pct` percent are noise
+    pub fn from_prices(prices: &[f64], threshold_pct: f64) -> Self {
+        SignalSet {
+            signals: vec![
+                momentum(prices, threshold_pct),
+                trend(prices, threshold_pct),
+            ],
+        }
+    }
+
+    /// buy votes minus sell votes
+    pub fn net(&self) -> i32 {
+        self.signals
+            .iter()
+            .map(|signal| {
+                if signal.should_buy() {
+                    1
+                } else if signal.should_sell() {
+                    -1
+                } else {
+                    0
+                }
+            })
+            .sum()
+    }
+
+    pub fn decision(&self) -> Decision {
+        match self.net() {
+            net if net > 0 => Decision::Buy,
+            net if net < 0 => Decision::Sell,
+            _ => Decision::Hold,
+        }
+    }
+}
+
+// percent change of `to` over `from`, 0 when either is not usable
+fn change_pct(from: f64, to: f64) -> f64 {
+    if from > 0.0 && to.is_finite() {
+        (to - from) / from * 100.0
+    } else {
+        0.0
+    }
+}
+
+fn above_threshold(value: f64, threshold_pct: f64) -> f64 {
+    if value.abs() >= threshold_pct {
+        value
     } else {
-        println!("No action for signal: {}", signal.name);
+        0.0
+    }
+}
+
+/// Percent change from the oldest to the latest price
+pub fn momentum(prices: &[f64], threshold_pct: f64) -> Signal {
+    let value = match (prices.first(), prices.last()) {
+        (Some(first), Some(last)) => change_pct(*first, *last),
+        _ => 0.0,
+    };
+    Signal::new("momentum", above_threshold(value, threshold_pct))
+}
+
+/// Percent the average of the latest quarter of `prices` sits above the average of all of them
+pub fn trend(prices: &[f64], threshold_pct: f64) -> Signal {
+    let average = |prices: &[f64]| prices.iter().sum::<f64>() / prices.len().max(1) as f64;
+    let recent = &prices[prices.len() - (prices.len() / 4).max(1).min(prices.len())..];
+    let value = change_pct(average(prices), average(recent));
+    Signal::new("trend", above_threshold(value, threshold_pct))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_signal_set() {
+        let rising = [1.0, 1.0, 1.01, 1.02, 1.05, 1.08, 1.1, 1.12];
+        let signals = SignalSet::from_prices(&rising, 2.0);
+        assert!(signals.signals.iter().all(Signal::should_buy));
+        assert_eq!(signals.decision(), Decision::Buy);
+
+        let falling: Vec<f64> = rising.iter().rev().copied().collect();
+        assert_eq!(
+            SignalSet::from_prices(&falling, 2.0).decision(),
+            Decision::Sell
+        );
+
+        // noise under the threshold
+        let flat = [1.0, 1.005, 0.998, 1.002, 1.001];
+        let signals = SignalSet::from_prices(&flat, 2.0);
+        assert_eq!(signals.net(), 0);
+        assert_eq!(signals.decision(), Decision::Hold);
+
+        // signals disagreeing cancel out
+        let set = SignalSet {
+            signals: vec![Signal::new("a", 1.0), Signal::new("b", -3.0)],
+        };
+        assert_eq!(set.decision(), Decision::Hold);
+
+        assert_eq!(SignalSet::from_prices(&[], 2.0).decision(), Decision::Hold);
     }
 }
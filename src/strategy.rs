@@ -0,0 +1,422 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    str::FromStr,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use solana_sdk::{pubkey::Pubkey, signer::Signer};
+use tokio::{sync::Mutex as AsyncMutex, task::JoinHandle, time::MissedTickBehavior};
+use tracing::{info, warn};
+
+use crate::{
+    api::AppState,
+    error::{ApiError, ErrorCode},
+    extract::JsonBody,
+    ledger::now_millis,
+    price::{self, PriceVenue},
+    signal::{Decision, SignalSet},
+    swap::{self, SwapDirection, SwapInType, SwapParams},
+    telemetry, token,
+};
+
+// bounds the memory of a strategy
+const MAX_WINDOW: usize = 1000;
+
+fn default_sell_pct() -> f64 {
+    1.0
+}
+
+fn default_interval_secs() -> u64 {
+    10
+}
+
+fn default_window() -> usize {
+    30
+}
+
+fn default_threshold_pct() -> f64 {
+    2.0
+}
+
+fn default_cooldown_secs() -> u64 {
+    300
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StrategyConfig {
+    pub mint: String,
+    /// sol spent on a buy
+    pub buy_sol: f64,
+    /// share of the held tokens sold on a sell, 0 to 1
+    #[serde(default = "default_sell_pct")]
+    pub sell_pct: f64,
+    /// seconds between two price samples
+    #[serde(default = "default_interval_secs")]
+    pub interval_secs: u64,
+    /// price samples in the rolling window, no trade before it is full
+    #[serde(default = "default_window")]
+    pub window: usize,
+    /// price moves under this percentage are noise
+    #[serde(default = "default_threshold_pct")]
+    pub threshold_pct: f64,
+    /// seconds after a trade during which flips are ignored
+    #[serde(default = "default_cooldown_secs")]
+    pub cooldown_secs: u64,
+    #[serde(default)]
+    pub venue: PriceVenue,
+    pub slippage: Option<u64>,
+    #[serde(default)]
+    pub jito: bool,
+}
+
+impl JsonBody for StrategyConfig {
+    fn example() -> Option<Value> {
+        Some(json!({
+            "mint": "EKpQGSJtjMFqKZ9KQanSqYXRcF8fBopzLHYxdM65zcjm",
+            "buy_sol": 0.1,
+            "sell_pct": 1.0,
+            "interval_secs": 10,
+            "window": 30,
+            "threshold_pct": 2.0,
+            "cooldown_secs": 300,
+        }))
+    }
+
+    fn allowed_values(field: &str) -> Option<&'static [&'static str]> {
+        match field {
+            "venue" => Some(&["auto", "raydium", "pump", "meteora", "pumpswap"]),
+            _ => None,
+        }
+    }
+}
+
+impl StrategyConfig {
+    fn validate(&self) -> Result<()> {
+        Pubkey::from_str(&self.mint).map_err(|e| {
+            ApiError::new(
+                ErrorCode::InvalidInput,
+                format!("failed to parse mint pubkey: {}", e),
+            )
+        })?;
+        if !self.buy_sol.is_finite() || self.buy_sol <= 0.0 {
+            return Err(
+                ApiError::new(ErrorCode::InvalidInput, "buy_sol must be greater than 0").into(),
+            );
+        }
+        if !self.sell_pct.is_finite() || self.sell_pct <= 0.0 || self.sell_pct > 1.0 {
+            return Err(ApiError::new(
+                ErrorCode::InvalidInput,
+                "sell_pct must be above 0 and at most 1",
+            )
+            .into());
+        }
+        if !self.threshold_pct.is_finite() || self.threshold_pct <= 0.0 {
+            return Err(ApiError::new(
+                ErrorCode::InvalidInput,
+                "threshold_pct must be greater than 0",
+            )
+            .into());
+        }
+        if self.interval_secs == 0 {
+            return Err(
+                ApiError::new(ErrorCode::InvalidInput, "interval_secs must be at least 1").into(),
+            );
+        }
+        if !(2..=MAX_WINDOW).contains(&self.window) {
+            return Err(ApiError::new(
+                ErrorCode::InvalidInput,
+                format!("window must be between 2 and {} samples", MAX_WINDOW),
+            )
+            .into());
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct PricePoint {
+    /// sol per token
+    pub price: f64,
+    /// unix timestamp in milliseconds
+    pub timestamp: u64,
+}
+
+#[derive(Debug, Default)]
+struct StrategyState {
+    prices: VecDeque<PricePoint>,
+    signals: SignalSet,
+    decision: Option<Decision>,
+    // last trade that went through, the one a new decision has to flip
+    last_action: Option<Decision>,
+    last_trade_at: Option<u64>,
+    trades: u64,
+    failed: u64,
+    last_error: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct StrategyInfo {
+    pub config: StrategyConfig,
+    /// unix timestamp in milliseconds
+    pub started_at: u64,
+    /// rolling window, oldest first
+    pub prices: Vec<PricePoint>,
+    pub signals: SignalSet,
+    /// decision of the latest signals, `null` before the first price
+    pub decision: Option<Decision>,
+    pub last_action: Option<Decision>,
+    /// unix timestamp in milliseconds of the last trade attempt
+    pub last_trade_at: Option<u64>,
+    pub trades: u64,
+    pub failed: u64,
+    pub last_error: Option<String>,
+}
+
+struct StrategyTask {
+    config: StrategyConfig,
+    started_at: u64,
+    state: Arc<Mutex<StrategyState>>,
+    handle: JoinHandle<()>,
+}
+
+impl StrategyTask {
+    fn info(&self) -> StrategyInfo {
+        let state = self.state.lock().unwrap();
+        StrategyInfo {
+            config: self.config.clone(),
+            started_at: self.started_at,
+            prices: state.prices.iter().copied().collect(),
+            signals: state.signals.clone(),
+            decision: state.decision,
+            last_action: state.last_action,
+            last_trade_at: state.last_trade_at,
+            trades: state.trades,
+            failed: state.failed,
+            last_error: state.last_error.clone(),
+        }
+    }
+}
+
+/// Running signal strategies keyed by mint
+#[derive(Default)]
+pub struct Strategies {
+    tasks: AsyncMutex<HashMap<String, StrategyTask>>,
+}
+
+impl Strategies {
+    pub async fn start(&self, state: &AppState, config: StrategyConfig) -> Result<StrategyInfo> {
+        config.validate()?;
+        let mut tasks = self.tasks.lock().await;
+        if tasks.contains_key(&config.mint) {
+            return Err(ApiError::new(
+                ErrorCode::InvalidInput,
+                format!("a strategy already runs on {}, stop it first", config.mint),
+            )
+            .into());
+        }
+        let shared = Arc::new(Mutex::new(StrategyState::default()));
+        let handle = tokio::spawn(run(state.clone(), config.clone(), shared.clone()));
+        let task = StrategyTask {
+            config: config.clone(),
+            started_at: now_millis(),
+            state: shared,
+            handle,
+        };
+        let info = task.info();
+        tasks.insert(config.mint, task);
+        Ok(info)
+    }
+
+    pub async fn stop(&self, mint: &str) -> Result<StrategyInfo> {
+        let task = self.tasks.lock().await.remove(mint).ok_or_else(|| {
+            ApiError::new(ErrorCode::NotFound, format!("no strategy runs on {}", mint))
+        })?;
+        task.handle.abort();
+        info!("strategy stopped: {}", mint);
+        Ok(task.info())
+    }
+
+    pub async fn get(&self, mint: &str) -> Result<StrategyInfo> {
+        self.tasks
+            .lock()
+            .await
+            .get(mint)
+            .map(StrategyTask::info)
+            .ok_or_else(|| {
+                ApiError::new(ErrorCode::NotFound, format!("no strategy runs on {}", mint)).into()
+            })
+    }
+
+    pub async fn list(&self) -> Vec<StrategyInfo> {
+        self.tasks
+            .lock()
+            .await
+            .values()
+            .map(StrategyTask::info)
+            .collect()
+    }
+}
+
+/// Trade to make on `decision`, only a flip from the last trade once the cooldown is over
+fn next_action(
+    decision: Decision,
+    last_action: Option<Decision>,
+    last_trade_at: Option<u64>,
+    now: u64,
+    cooldown_ms: u64,
+) -> Option<Decision> {
+    if decision == Decision::Hold || last_action == Some(decision) {
+        return None;
+    }
+    match last_trade_at {
+        Some(at) if now.saturating_sub(at) < cooldown_ms => None,
+        _ => Some(decision),
+    }
+}
+
+async fn run(state: AppState, config: StrategyConfig, shared: Arc<Mutex<StrategyState>>) {
+    let _guard = telemetry::MonitorGuard::start();
+    info!("strategy started: {:?}", config);
+    let mut interval = tokio::time::interval(Duration::from_secs(config.interval_secs));
+    interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
+    loop {
+        interval.tick().await;
+        if let Err(err) = step(&state, &config, &shared).await {
+            warn!("strategy {} err: {}", config.mint, err);
+            shared.lock().unwrap().last_error = Some(err.to_string());
+        }
+    }
+}
+
+// samples the price, recomputes the signals and trades on a flip
+async fn step(
+    state: &AppState,
+    config: &StrategyConfig,
+    shared: &Arc<Mutex<StrategyState>>,
+) -> Result<()> {
+    let tick = price::get_token_price(state, &config.mint, config.venue).await?;
+    let action = {
+        let mut strategy = shared.lock().unwrap();
+        strategy.prices.push_back(PricePoint {
+            price: tick.price,
+            timestamp: tick.timestamp,
+        });
+        while strategy.prices.len() > config.window {
+            strategy.prices.pop_front();
+        }
+        let prices: Vec<f64> = strategy.prices.iter().map(|point| point.price).collect();
+        strategy.signals = SignalSet::from_prices(&prices, config.threshold_pct);
+        let decision = strategy.signals.decision();
+        strategy.decision = Some(decision);
+        if prices.len() < config.window {
+            None
+        } else {
+            next_action(
+                decision,
+                strategy.last_action,
+                strategy.last_trade_at,
+                now_millis(),
+                config.cooldown_secs * 1000,
+            )
+        }
+    };
+    let Some(action) = action else {
+        return Ok(());
+    };
+
+    let (direction, in_type, amount_in) = match action {
+        Decision::Buy => (SwapDirection::Buy, SwapInType::Qty, config.buy_sol),
+        _ => {
+            let mint = Pubkey::from_str(&config.mint)?;
+            let held = token::token_account(&state.client, &state.wallet().pubkey(), mint)
+                .await
+                .map_or(0.0, |account| account.ui_amount);
+            if held <= 0.0 {
+                // nothing to sell, wait for the next buy
+                info!("strategy {}: sell signal without a position", config.mint);
+                shared.lock().unwrap().last_action = Some(action);
+                return Ok(());
+            }
+            (SwapDirection::Sell, SwapInType::Pct, config.sell_pct)
+        }
+    };
+    info!(
+        "strategy {} {}, amount_in: {} ({:?})",
+        direction.as_str(),
+        config.mint,
+        amount_in,
+        in_type
+    );
+    let params = SwapParams::market(
+        config.mint.clone(),
+        direction,
+        in_type,
+        amount_in,
+        config.slippage,
+        config.jito,
+    );
+    let result = swap::swap_with_timeout(state.clone(), params).await;
+    let mut strategy = shared.lock().unwrap();
+    // failed attempts count for the cooldown too, so a failing swap is not retried every tick
+    strategy.last_trade_at = Some(now_millis());
+    match result {
+        Ok(_) => {
+            strategy.last_action = Some(action);
+            strategy.trades += 1;
+            strategy.last_error = None;
+            Ok(())
+        }
+        Err(err) => {
+            strategy.failed += 1;
+            Err(err)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_next_action() {
+        let cooldown = 300_000;
+        assert_eq!(
+            next_action(Decision::Buy, None, None, 1_000, cooldown),
+            Some(Decision::Buy)
+        );
+        assert_eq!(
+            next_action(Decision::Hold, None, None, 1_000, cooldown),
+            None
+        );
+        // no flip
+        assert_eq!(
+            next_action(Decision::Buy, Some(Decision::Buy), None, 1_000, cooldown),
+            None
+        );
+        // flip inside the cooldown
+        assert_eq!(
+            next_action(
+                Decision::Sell,
+                Some(Decision::Buy),
+                Some(1_000),
+                100_000,
+                cooldown
+            ),
+            None
+        );
+        assert_eq!(
+            next_action(
+                Decision::Sell,
+                Some(Decision::Buy),
+                Some(1_000),
+                301_000,
+                cooldown
+            ),
+            Some(Decision::Sell)
+        );
+    }
+}
@@ -1,22 +1,55 @@
-use anyhow::Result;
+use anyhow::{anyhow, Result};
 use clap::ValueEnum;
-use serde::Deserialize;
-use tracing::{info, warn};
+use metrics::{counter, histogram};
+use rust_decimal::{prelude::ToPrimitive, Decimal};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use solana_sdk::{
+    commitment_config::CommitmentConfig, native_token::lamports_to_sol, pubkey::Pubkey,
+    signer::Signer, transaction::VersionedTransaction,
+};
+use spl_associated_token_account::get_associated_token_address;
+use spl_token::amount_to_ui_amount;
+use std::{
+    env,
+    str::FromStr,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+use tokio::time::Instant;
+use tracing::{debug, info, warn, Instrument, Span};
 
 use crate::{
     api::AppState,
-    get_rpc_client, get_rpc_client_blocking,
+    error::{ApiError, ErrorCode},
+    get_rpc_client_blocking, get_rpc_client_blocking_with_commitment,
+    get_rpc_client_with_commitment,
+    helper::{explorer_tx_url, get_solana_price},
+    jito,
+    ledger::{now_millis, Ledger, TradeRecord},
+    meteora,
     pump::{self, get_pump_info},
-    raydium,
+    raydium::{self, PoolCandidate},
+    request_id, telemetry, token,
+    tx::{self, SimulatedTransaction},
+    webhook::{self, SwapEvent, SwapStatus},
 };
 
-#[derive(ValueEnum, Debug, Clone, Deserialize)]
+#[derive(ValueEnum, Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub enum SwapDirection {
     #[serde(rename = "buy")]
     Buy,
     #[serde(rename = "sell")]
     Sell,
 }
+impl SwapDirection {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            SwapDirection::Buy => "buy",
+            SwapDirection::Sell => "sell",
+        }
+    }
+}
 impl From<SwapDirection> for u8 {
     fn from(value: SwapDirection) -> Self {
         match value {
@@ -25,7 +58,7 @@ impl From<SwapDirection> for u8 {
         }
     }
 }
-#[derive(ValueEnum, Debug, Clone, Deserialize)]
+#[derive(ValueEnum, Debug, Clone, Serialize, Deserialize)]
 pub enum SwapInType {
     /// Quantity
     #[serde(rename = "qty")]
@@ -35,42 +68,1764 @@ pub enum SwapInType {
     Pct,
 }
 
-pub async fn swap(
-    state: AppState,
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Venue {
+    Pump,
+    Raydium,
+    Meteora,
+    PumpSwap,
+}
+impl Venue {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Venue::Pump => "pump",
+            Venue::Raydium => "raydium",
+            Venue::Meteora => "meteora",
+            Venue::PumpSwap => "pumpswap",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SlippageMode {
+    /// use the requested percentage as is
+    #[default]
+    Fixed,
+    /// derive from the pool depth relative to the swap size
+    Depth,
+}
+
+fn get_depth_slippage_buffer_bps() -> u64 {
+    env::var("DEPTH_SLIPPAGE_BUFFER_BPS")
+        .ok()
+        .and_then(|v| v.parse::<u64>().ok())
+        .unwrap_or(100)
+}
+
+fn get_depth_slippage_max_bps() -> u64 {
+    env::var("DEPTH_SLIPPAGE_MAX_BPS")
+        .ok()
+        .and_then(|v| v.parse::<u64>().ok())
+        .unwrap_or(5000)
+        .min(9900)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Slippage {
+    /// percentage
+    Fixed(u64),
+    Depth {
+        buffer_bps: u64,
+        max_bps: u64,
+    },
+}
+
+impl Slippage {
+    pub fn new(mode: SlippageMode, slippage: u64) -> Self {
+        match mode {
+            SlippageMode::Fixed => Slippage::Fixed(slippage),
+            SlippageMode::Depth => Slippage::Depth {
+                buffer_bps: get_depth_slippage_buffer_bps(),
+                max_bps: get_depth_slippage_max_bps(),
+            },
+        }
+    }
+
+    pub fn mode(&self) -> SlippageMode {
+        match self {
+            Slippage::Fixed(_) => SlippageMode::Fixed,
+            Slippage::Depth { .. } => SlippageMode::Depth,
+        }
+    }
+
+    /// slippage in bps for trading amount_in against reserve_in
+    pub fn resolve_bps(&self, amount_in: u64, reserve_in: u64) -> u64 {
+        match *self {
+            Slippage::Fixed(slippage) => slippage * 100,
+            Slippage::Depth {
+                buffer_bps,
+                max_bps,
+            } => {
+                let impact_bps = (price_impact_pct(amount_in, reserve_in) * 100.0).ceil() as u64;
+                (impact_bps + buffer_bps).min(max_bps)
+            }
+        }
+    }
+
+    /// `step` percent more slippage, at most `cap` percent, `None` once the cap is reached
+    pub fn bumped(&self, step: u64, cap: u64) -> Option<Self> {
+        match *self {
+            Slippage::Fixed(slippage) if slippage < cap => {
+                Some(Slippage::Fixed((slippage + step).min(cap)))
+            }
+            Slippage::Depth {
+                buffer_bps,
+                max_bps,
+            } if max_bps < cap * 100 => Some(Slippage::Depth {
+                buffer_bps: buffer_bps + step * 100,
+                max_bps: (max_bps + step * 100).min(cap * 100),
+            }),
+            _ => None,
+        }
+    }
+}
+
+// auto_bump_slippage: percent added per retry, the cap in percent and the number of retries
+fn get_slippage_bump_step() -> u64 {
+    env::var("SLIPPAGE_BUMP_STEP")
+        .ok()
+        .and_then(|v| v.parse::<u64>().ok())
+        .unwrap_or(5)
+}
+
+fn get_slippage_bump_max() -> u64 {
+    env::var("SLIPPAGE_BUMP_MAX")
+        .ok()
+        .and_then(|v| v.parse::<u64>().ok())
+        .unwrap_or(30)
+        .min(99)
+}
+
+fn get_slippage_bump_retries() -> u32 {
+    env::var("SLIPPAGE_BUMP_RETRIES")
+        .ok()
+        .and_then(|v| v.parse::<u32>().ok())
+        .unwrap_or(2)
+}
+
+/// What a venue reports back after sending a swap
+#[derive(Debug, Clone, Serialize)]
+pub struct SwapOutcome {
+    pub signatures: Vec<String>,
+    /// slippage applied to the output threshold
+    pub slippage_bps: u64,
+    /// raw amount of the input token
+    pub amount_in: u64,
+    /// quoted raw amount of the output token before slippage
+    pub expected_out: u64,
+    pub price_impact_pct: f64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SwapResult {
+    pub venue: Venue,
+    pub slippage_mode: SlippageMode,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pool_selection: Option<PoolSelection>,
+    /// explorer url of each signature
+    pub explorer_urls: Vec<String>,
+    pub summary: SwapSummary,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub usd_conversion: Option<UsdConversion>,
+    /// with `async` the signatures are submitted, not yet confirmed
+    pub mode: tx::ConfirmMode,
+    /// retries after slippage failures, set with auto_bump_slippage, `slippage_bps` is the last one
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub slippage_retries: Option<u32>,
+    /// fees, compute units and jito tip read from the confirmed transactions
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub costs: Option<tx::TxCosts>,
+    /// the costs could not be read yet, with `async` or a transaction not queryable in time,
+    /// the trade ledger gets them once it is
+    pub costs_pending: bool,
+    #[serde(flatten)]
+    pub outcome: SwapOutcome,
+}
+
+/// Human readable view of a swap, amounts are ui amounts and `amount_out` is the quote
+#[derive(Debug, Clone, Serialize)]
+pub struct SwapSummary {
+    pub direction: SwapDirection,
+    pub mint: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub symbol: Option<String>,
+    pub amount_in: f64,
+    pub amount_out: f64,
+    pub text: String,
+}
+
+/// Raw units of `amount` of a token with `decimals`, scaled in decimal so that e.g. 0.1 sol
+/// is exactly 100_000_000 lamports, a float multiply then `as u64` can be a unit short
+pub fn to_base_units(amount: f64, decimals: u8) -> Result<u64> {
+    let ui_amount = decimal_amount(amount)?;
+    let scaled = 10u64
+        .checked_pow(decimals.into())
+        .and_then(|unit| ui_amount.checked_mul(Decimal::from(unit)))
+        .and_then(|scaled| scaled.trunc().to_u64())
+        .ok_or_else(|| {
+            ApiError::new(
+                ErrorCode::InvalidInput,
+                format!("amount {} is too large", amount),
+            )
+        })?;
+    if scaled == 0 {
+        return Err(ApiError::new(
+            ErrorCode::InvalidInput,
+            format!(
+                "amount {} is below the smallest unit of a {} decimals token",
+                amount, decimals
+            ),
+        )
+        .into());
+    }
+    Ok(scaled)
+}
+
+/// `pct` (0 to 1) of `balance` in raw units, rounded down
+pub fn pct_of(balance: u64, pct: f64) -> Result<u64> {
+    let pct = decimal_amount(pct)?;
+    if pct > Decimal::ONE {
+        return Err(ApiError::new(ErrorCode::InvalidInput, "pct must be between 0 and 1").into());
+    }
+    // below u64::MAX since pct is at most 1
+    Ok((Decimal::from(balance) * pct)
+        .trunc()
+        .to_u64()
+        .unwrap_or(balance))
+}
+
+// the shortest decimal that round trips to `amount`, i.e. what was typed
+fn decimal_amount(amount: f64) -> Result<Decimal> {
+    if !amount.is_finite() || amount <= 0.0 {
+        return Err(ApiError::new(
+            ErrorCode::InvalidInput,
+            format!("amount must be a positive number, got {}", amount),
+        )
+        .into());
+    }
+    Decimal::from_str(&amount.to_string()).map_err(|_| {
+        ApiError::new(
+            ErrorCode::InvalidInput,
+            format!("amount {} is out of range", amount),
+        )
+        .into()
+    })
+}
+
+// constant product price impact of trading amount_in against reserve_in
+pub fn price_impact_pct(amount_in: u64, reserve_in: u64) -> f64 {
+    let total = amount_in as f64 + reserve_in as f64;
+    if total == 0.0 {
+        return 0.0;
+    }
+    amount_in as f64 / total * 100.0
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PoolSelectionPolicy {
+    /// deepest sol vault first
+    #[default]
+    Liquidity,
+    /// lowest swap fee first, liquidity breaks ties
+    Fee,
+    /// pools of POOL_PREFERRED_PROGRAM first, liquidity breaks ties
+    Program,
+}
+
+impl FromStr for PoolSelectionPolicy {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "liquidity" => Ok(PoolSelectionPolicy::Liquidity),
+            "fee" => Ok(PoolSelectionPolicy::Fee),
+            "program" => Ok(PoolSelectionPolicy::Program),
+            _ => Err(anyhow!("unknown pool selection policy: {}", s)),
+        }
+    }
+}
+
+pub fn get_pool_selection_policy() -> PoolSelectionPolicy {
+    env::var("POOL_SELECTION")
+        .ok()
+        .and_then(|v| PoolSelectionPolicy::from_str(&v).ok())
+        .unwrap_or_default()
+}
+
+fn get_pool_preferred_program() -> Option<String> {
+    env::var("POOL_PREFERRED_PROGRAM")
+        .ok()
+        .filter(|v| !v.is_empty())
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct PoolSelection {
+    pub policy: PoolSelectionPolicy,
+    pub chosen: PoolCandidate,
+    /// remaining candidates, best first
+    pub runner_ups: Vec<PoolCandidate>,
+}
+
+/// Rank the candidates by policy, ties are broken by pool id so the pick is deterministic
+pub fn select_pool(
+    policy: PoolSelectionPolicy,
+    preferred_program: Option<&str>,
+    mut candidates: Vec<PoolCandidate>,
+) -> Option<PoolSelection> {
+    candidates.sort_by(|a, b| {
+        let by_liquidity = b.sol_liquidity.cmp(&a.sol_liquidity);
+        let ranked = match policy {
+            PoolSelectionPolicy::Liquidity => by_liquidity,
+            PoolSelectionPolicy::Fee => a.fee_bps.cmp(&b.fee_bps).then(by_liquidity),
+            PoolSelectionPolicy::Program => {
+                let preferred =
+                    |c: &PoolCandidate| Some(c.program_id.as_str()) == preferred_program;
+                preferred(b).cmp(&preferred(a)).then(by_liquidity)
+            }
+        };
+        ranked.then_with(|| a.pool_id.cmp(&b.pool_id))
+    });
+    if candidates.is_empty() {
+        return None;
+    }
+    let chosen = candidates.remove(0);
+    Some(PoolSelection {
+        policy,
+        chosen,
+        runner_ups: candidates,
+    })
+}
+
+// None falls back to the single pool lookup of `raydium::get_pool_state`
+async fn select_raydium_pool(
+    client_blocking: Arc<solana_client::rpc_client::RpcClient>,
     mint: &str,
-    amount_in: f64,
-    swap_direction: SwapDirection,
-    in_type: SwapInType,
-    slippage: u64,
-    use_jito: bool,
-) -> Result<Vec<String>> {
-    let client = get_rpc_client()?;
-    let client_blocking = get_rpc_client_blocking()?;
-    let wallet = state.wallet;
-
-    let swap_in_pump = get_pump_info(client_blocking.clone(), mint)
+    policy: PoolSelectionPolicy,
+) -> Option<PoolSelection> {
+    let candidates = match raydium::get_pool_candidates(client_blocking, mint).await {
+        Ok(candidates) => candidates,
+        Err(err) => {
+            warn!("failed to discover raydium pools: {}", err);
+            return None;
+        }
+    };
+    let selection = select_pool(policy, get_pool_preferred_program().as_deref(), candidates);
+    if let Some(selection) = &selection {
+        info!(
+            "selected pool {} by {:?}, {} runner ups",
+            selection.chosen.pool_id,
+            policy,
+            selection.runner_ups.len()
+        );
+    }
+    selection
+}
+
+/// A swap request after defaults are applied
+#[derive(Debug, Clone)]
+pub struct SwapParams {
+    pub mint: String,
+    pub direction: SwapDirection,
+    pub amount_in: f64,
+    /// buy size in usd, replaces amount_in once converted
+    pub amount_in_usd: Option<f64>,
+    pub in_type: SwapInType,
+    pub slippage: Slippage,
+    pub use_jito: bool,
+    /// skip the MAX_SOL_PER_SWAP guard
+    pub confirm_large: bool,
+    pub pool_selection: PoolSelectionPolicy,
+    /// overrides WEBHOOK_URL for this swap
+    pub webhook_url: Option<String>,
+    /// lamports floor of a pump sell, used as the threshold instead of the slippage
+    pub min_sol_out: Option<u64>,
+    /// trade on this raydium pool, skipping venue and pool discovery
+    pub pool_id: Option<String>,
+    /// of the blockhash fetch and the confirmation
+    pub commitment: CommitmentConfig,
+    /// return once confirmed, or once submitted
+    pub confirm_mode: tx::ConfirmMode,
+    /// retry slippage failures with more slippage, see `Slippage::bumped`
+    pub auto_bump_slippage: bool,
+}
+
+impl SwapParams {
+    /// A confirmed swap with the default pool selection, `slippage` in percent, SLIPPAGE if unset
+    pub fn market(
+        mint: String,
+        direction: SwapDirection,
+        in_type: SwapInType,
+        amount_in: f64,
+        slippage: Option<u64>,
+        use_jito: bool,
+    ) -> Self {
+        Self {
+            mint,
+            direction,
+            amount_in,
+            amount_in_usd: None,
+            in_type,
+            slippage: Slippage::Fixed(slippage.unwrap_or_else(get_default_slippage)),
+            use_jito,
+            confirm_large: false,
+            pool_selection: get_pool_selection_policy(),
+            webhook_url: None,
+            min_sol_out: None,
+            pool_id: None,
+            commitment: CommitmentConfig::confirmed(),
+            confirm_mode: tx::ConfirmMode::Sync,
+            auto_bump_slippage: false,
+        }
+    }
+}
+
+/// The swap the daemon would execute for a `SwapParams`, see `resolve`
+#[derive(Debug, Clone, Serialize)]
+pub struct ResolvedSwap {
+    pub wallet: String,
+    pub mint: String,
+    pub direction: SwapDirection,
+    pub in_type: SwapInType,
+    /// as requested, ui amount or fraction of the balance
+    pub amount_in_requested: f64,
+    pub venue: Venue,
+    /// raydium amm id or pump bonding curve
+    pub pool: String,
+    pub slippage_mode: SlippageMode,
+    pub slippage_bps: u64,
+    /// raw amount of the input token
+    pub amount_in: u64,
+    /// quoted raw amount of the output token before slippage
+    pub expected_out: u64,
+    /// min output, or max sol input for pump buys
+    pub other_amount_threshold: u64,
+    pub price_impact_pct: f64,
+    pub jito: bool,
+    pub fee: tx::FeeEstimate,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pool_selection: Option<PoolSelection>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub usd_conversion: Option<UsdConversion>,
+}
+
+/// Rate a usd denominated buy was converted to sol with
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct UsdConversion {
+    pub amount_in_usd: f64,
+    /// usd per sol
+    pub sol_price: f64,
+    pub amount_in_sol: f64,
+}
+
+/// Replace `amount_in_usd` with the sol amount at the current sol price
+///
+/// Runs after `check_params`, the converted amount is checked here.
+pub async fn convert_usd_amount(params: &mut SwapParams) -> Result<Option<UsdConversion>> {
+    let Some(amount_in_usd) = params.amount_in_usd.take() else {
+        return Ok(None);
+    };
+    let sol_price = get_solana_price().await.map_err(|e| {
+        ApiError::new(
+            ErrorCode::RpcError,
+            format!("failed to get the sol price: {}", e),
+        )
+    })?;
+    if !sol_price.is_finite() || sol_price <= 0.0 {
+        return Err(ApiError::new(
+            ErrorCode::RpcError,
+            format!("invalid sol price {}", sol_price),
+        )
+        .into());
+    }
+    params.amount_in = amount_in_usd / sol_price;
+    info!(
+        "amount in ${} at {} usd/sol: {} sol",
+        amount_in_usd, sol_price, params.amount_in
+    );
+    check_amount(params)?;
+    Ok(Some(UsdConversion {
+        amount_in_usd,
+        sol_price,
+        amount_in_sol: params.amount_in,
+    }))
+}
+
+// fat finger guard on the sol spent by a single buy, unset disables it
+fn get_max_sol_per_swap() -> Option<f64> {
+    env::var("MAX_SOL_PER_SWAP")
+        .ok()
+        .and_then(|v| v.parse::<f64>().ok())
+        .filter(|v| *v > 0.0)
+}
+
+// exit liquidity guard on buys, unset disables it
+fn get_min_pool_liquidity_sol() -> Option<f64> {
+    env::var("MIN_POOL_LIQUIDITY_SOL")
+        .ok()
+        .and_then(|v| v.parse::<f64>().ok())
+        .filter(|v| *v > 0.0)
+}
+
+/// SLIPPAGE in percent, 5 if unset
+pub fn get_default_slippage() -> u64 {
+    let slippage = env::var("SLIPPAGE").unwrap_or("5".to_string());
+    slippage.parse::<u64>().unwrap_or(5)
+}
+
+/// Reject a buy of `amount_sol` above MAX_SOL_PER_SWAP unless `confirm_large`
+pub fn check_spend_limit(amount_sol: f64, confirm_large: bool) -> Result<()> {
+    if let Some(max_sol) = get_max_sol_per_swap() {
+        if amount_sol > max_sol && !confirm_large {
+            return Err(ApiError::new(
+                ErrorCode::SpendLimitExceeded,
+                format!(
+                    "buy of {} sol is above MAX_SOL_PER_SWAP {} sol, resend with confirm_large to proceed",
+                    amount_sol, max_sol
+                ),
+            )
+            .into());
+        }
+    }
+    Ok(())
+}
+
+/// Reject a buy into `pool` when the `liquidity` lamports of sol it holds are below
+/// MIN_POOL_LIQUIDITY_SOL, venues call it with the reserves read for the quote
+pub fn check_pool_liquidity(pool: &Pubkey, liquidity: u64) -> Result<()> {
+    match get_min_pool_liquidity_sol() {
+        Some(min_sol) => check_min_liquidity(pool, liquidity, min_sol),
+        None => Ok(()),
+    }
+}
+
+fn check_min_liquidity(pool: &Pubkey, liquidity: u64, min_sol: f64) -> Result<()> {
+    let liquidity_sol = lamports_to_sol(liquidity);
+    if liquidity_sol < min_sol {
+        return Err(ApiError::new(
+            ErrorCode::InsufficientLiquidity,
+            format!(
+                "pool {} holds {} sol, below MIN_POOL_LIQUIDITY_SOL {} sol",
+                pool, liquidity_sol, min_sol
+            ),
+        )
+        .into());
+    }
+    Ok(())
+}
+
+/// Reject a swap against `pool` when either side of it holds nothing
+pub fn check_route(pool: &Pubkey, reserve_in: u64, reserve_out: u64) -> Result<()> {
+    if reserve_in == 0 || reserve_out == 0 {
+        return Err(ApiError::new(
+            ErrorCode::NoRoute,
+            format!(
+                "pool {} has one sided liquidity, reserve_in {} reserve_out {}",
+                pool, reserve_in, reserve_out
+            ),
+        )
+        .into());
+    }
+    Ok(())
+}
+
+/// Reject a swap whose quote against `pool` returns nothing
+pub fn check_quote(pool: &Pubkey, expected_out: u64) -> Result<()> {
+    if expected_out == 0 {
+        return Err(ApiError::new(
+            ErrorCode::NoRoute,
+            format!("pool {} quotes no output for the swap", pool),
+        )
+        .into());
+    }
+    Ok(())
+}
+
+fn check_amount(params: &SwapParams) -> Result<()> {
+    if !params.amount_in.is_finite() || params.amount_in <= 0.0 {
+        return Err(ApiError::new(
+            ErrorCode::InvalidInput,
+            format!(
+                "amount_in must be a finite number above 0, got {}",
+                params.amount_in
+            ),
+        )
+        .into());
+    }
+    if let SwapInType::Pct = params.in_type {
+        if params.amount_in > 1.0 {
+            return Err(ApiError::new(
+                ErrorCode::InvalidInput,
+                "pct amount_in must be between 0 and 1",
+            )
+            .into());
+        }
+    }
+    if let SwapDirection::Buy = params.direction {
+        check_spend_limit(params.amount_in, params.confirm_large)?;
+    }
+    Ok(())
+}
+
+// checks that need no rpc, shared by `swap` and `resolve`
+fn check_params(state: &AppState, params: &SwapParams) -> Result<Pubkey> {
+    state.breaker.check()?;
+    if let SwapDirection::Buy = params.direction {
+        if state.is_sell_only() {
+            return Err(ApiError::new(
+                ErrorCode::BuysDisabled,
+                "sell only mode is enabled, buys are rejected",
+            )
+            .into());
+        }
+        // sells stay allowed so a position can be exited after its mint is blocked
+        state.mint_filters.check_buy(&params.mint)?;
+    }
+    let mint = Pubkey::from_str(&params.mint).map_err(|e| {
+        ApiError::new(
+            ErrorCode::InvalidInput,
+            format!("failed to parse mint pubkey: {}", e),
+        )
+    })?;
+    // a usd amount is only known once converted, see `convert_usd_amount`
+    if params.amount_in_usd.is_none() {
+        check_amount(params)?;
+    }
+    if params.use_jito {
+        jito::check_network()?;
+    }
+    tx::check_confirm_mode(params.confirm_mode, tx::get_nonce_account())?;
+    if let Slippage::Fixed(slippage) = params.slippage {
+        if slippage >= 100 {
+            return Err(
+                ApiError::new(ErrorCode::InvalidInput, "slippage must be below 100%").into(),
+            );
+        }
+    }
+    if let Some(pool_id) = &params.pool_id {
+        Pubkey::from_str(pool_id).map_err(|e| {
+            ApiError::new(
+                ErrorCode::InvalidInput,
+                format!("failed to parse pool_id: {}", e),
+            )
+        })?;
+    }
+    if let Some(min_sol_out) = params.min_sol_out {
+        if params.direction != SwapDirection::Sell {
+            return Err(ApiError::new(
+                ErrorCode::InvalidInput,
+                "min_sol_out is only supported for sells",
+            )
+            .into());
+        }
+        if min_sol_out == 0 {
+            return Err(ApiError::new(
+                ErrorCode::InvalidInput,
+                "min_sol_out must be greater than 0",
+            )
+            .into());
+        }
+    }
+    Ok(mint)
+}
+
+fn check_venue(params: &SwapParams, venue: Venue) -> Result<()> {
+    if params.min_sol_out.is_some() && !matches!(venue, Venue::Pump | Venue::PumpSwap) {
+        return Err(ApiError::new(
+            ErrorCode::InvalidInput,
+            format!(
+                "min_sol_out is only supported for pump.fun sells, {} trades on {}",
+                params.mint,
+                venue.as_str()
+            ),
+        )
+        .into());
+    }
+    Ok(())
+}
+
+// a given pool id is always a raydium pool
+async fn params_venue(
+    client_blocking: Arc<solana_client::rpc_client::RpcClient>,
+    params: &SwapParams,
+) -> Venue {
+    match params.pool_id {
+        Some(_) => Venue::Raydium,
+        None => pick_venue(client_blocking, &params.mint).await,
+    }
+}
+
+// bonding curves that are not complete yet trade on pump, graduated coins on their pumpswap
+// pool, everything else on raydium, or on meteora dlmm when raydium has no pool for the mint
+async fn pick_venue(
+    client_blocking: Arc<solana_client::rpc_client::RpcClient>,
+    mint: &str,
+) -> Venue {
+    let graduated = match get_pump_info(client_blocking.clone(), mint).await {
+        Ok(pump_info) if !pump_info.complete => return Venue::Pump,
+        Ok(_) => true,
+        Err(err) => {
+            warn!("failed to get_pump_info: {}", err);
+            false
+        }
+    };
+    // coins graduated before pumpswap migrated to raydium
+    if graduated {
+        match pump::find_pump_swap_pools(client_blocking.clone(), mint).await {
+            Ok(pools) if !pools.is_empty() => return Venue::PumpSwap,
+            Ok(_) => {}
+            Err(err) => warn!("failed to find pumpswap pools: {}", err),
+        }
+    }
+    let on_raydium = raydium::find_pools_by_mint(client_blocking.clone(), mint)
         .await
-        .map_or_else(
-            |err| {
-                warn!("failed to get_pump_info: {}", err);
-                false
+        .map_or(true, |pools| !pools.is_empty());
+    if on_raydium {
+        return Venue::Raydium;
+    }
+    match meteora::find_pairs_by_mint(client_blocking, mint).await {
+        Ok(pairs) if !pairs.is_empty() => Venue::Meteora,
+        Ok(_) => Venue::Raydium,
+        Err(err) => {
+            warn!("failed to find meteora pairs: {}", err);
+            Venue::Raydium
+        }
+    }
+}
+
+/// Run the guards and quote of a swap without sending anything
+pub async fn resolve(state: &AppState, params: &SwapParams) -> Result<ResolvedSwap> {
+    let mint = check_params(state, params)?;
+    let mut params = params.clone();
+    let usd_conversion = convert_usd_amount(&mut params).await?;
+    let params = &params;
+    let owner = state.wallet().pubkey();
+
+    // amount and balance of the input token
+    let (amount_in, balance) = match params.direction {
+        SwapDirection::Buy => (
+            to_base_units(params.amount_in, spl_token::native_mint::DECIMALS)?,
+            state.client.get_balance(&owner).await?,
+        ),
+        SwapDirection::Sell => {
+            let in_ata = get_associated_token_address(&owner, &mint);
+            let in_account =
+                token::get_account_info(state.client.clone(), state.wallet(), &mint, &in_ata)
+                    .await
+                    .map_err(|e| {
+                        ApiError::new(
+                            ErrorCode::InsufficientFunds,
+                            format!("no token account for mint: {}", e),
+                        )
+                    })?;
+            let decimals = state.decimals_cache.get(&state.client, &mint).await?;
+            let amount = match params.in_type {
+                SwapInType::Qty => to_base_units(params.amount_in, decimals)?,
+                SwapInType::Pct if params.amount_in == 1.0 => in_account.base.amount,
+                SwapInType::Pct => pct_of(in_account.base.amount, params.amount_in)?,
+            };
+            (amount, in_account.base.amount)
+        }
+    };
+    let fee = tx::estimate_fee(params.use_jito).await?;
+    let required = match params.direction {
+        SwapDirection::Buy => amount_in + fee.total_lamports,
+        SwapDirection::Sell => amount_in,
+    };
+    if balance < required {
+        return Err(ApiError::new(
+            ErrorCode::InsufficientFunds,
+            format!("balance {} is below the required {}", balance, required),
+        )
+        .into());
+    }
+
+    let venue = params_venue(state.client_blocking.clone(), params).await;
+    check_venue(params, venue)?;
+    let mut pool_selection = None;
+    let (pool, reserve_in, slippage_bps, expected_out, other_amount_threshold) = match venue {
+        Venue::Pump => {
+            let quote = pump::quote_curve_swap(
+                state.client_blocking.clone(),
+                &mint,
+                amount_in,
+                &params.direction,
+                params.slippage,
+                params.min_sol_out,
+            )
+            .await?;
+            (
+                quote.bonding_curve,
+                quote.reserve_in,
+                quote.slippage_bps,
+                quote.expected_out,
+                quote.sol_amount_threshold,
+            )
+        }
+        Venue::Raydium => {
+            if params.pool_id.is_none() {
+                pool_selection = select_raydium_pool(
+                    state.client_blocking.clone(),
+                    &params.mint,
+                    params.pool_selection,
+                )
+                .await;
+            }
+            let pool_id = params
+                .pool_id
+                .as_deref()
+                .or(pool_selection.as_ref().map(|s| s.chosen.pool_id.as_str()));
+            let quote = raydium::quote_swap(
+                state.client_blocking.clone(),
+                pool_id,
+                &params.mint,
+                amount_in,
+                &params.direction,
+                params.slippage,
+            )
+            .await?;
+            (
+                quote.amm_pool_id,
+                quote.reserve_in,
+                quote.slippage_bps,
+                quote.expected_out,
+                quote.other_amount_threshold,
+            )
+        }
+        Venue::Meteora => {
+            let quote = meteora::quote_swap(
+                state.client_blocking.clone(),
+                None,
+                &params.mint,
+                amount_in,
+                &params.direction,
+                params.slippage,
+            )
+            .await?;
+            (
+                quote.pair_id,
+                quote.reserve_in,
+                quote.slippage_bps,
+                quote.expected_out,
+                quote.min_out,
+            )
+        }
+        Venue::PumpSwap => {
+            let quote = pump::quote_pump_swap(
+                state.client_blocking.clone(),
+                &params.mint,
+                amount_in,
+                &params.direction,
+                params.slippage,
+                params.min_sol_out,
+            )
+            .await?;
+            (
+                quote.pool_id,
+                quote.reserve_in,
+                quote.slippage_bps,
+                quote.expected_out,
+                quote.quote_threshold,
+            )
+        }
+    };
+    if params.direction == SwapDirection::Buy && venue != Venue::Pump {
+        // pump curves were checked against their real sol reserves above
+        check_pool_liquidity(&pool, reserve_in)?;
+    }
+    debug!("resolved swap of {} against pool {}", params.mint, pool);
+
+    Ok(ResolvedSwap {
+        wallet: owner.to_string(),
+        mint: params.mint.clone(),
+        direction: params.direction.clone(),
+        in_type: params.in_type.clone(),
+        amount_in_requested: params.amount_in,
+        venue,
+        pool: pool.to_string(),
+        slippage_mode: params.slippage.mode(),
+        slippage_bps,
+        amount_in,
+        expected_out,
+        other_amount_threshold,
+        price_impact_pct: price_impact_pct(amount_in, reserve_in),
+        jito: params.use_jito,
+        fee,
+        pool_selection,
+        usd_conversion,
+    })
+}
+
+// size the compute unit limit of swaps from a simulation instead of UNIT_LIMIT
+fn get_cu_estimate() -> bool {
+    env::var("CU_ESTIMATE").ok() == Some("true".to_string())
+}
+
+fn get_swap_timeout() -> Duration {
+    let timeout_ms = env::var("SWAP_TIMEOUT_MS")
+        .ok()
+        .and_then(|v| v.parse::<u64>().ok())
+        .unwrap_or(60_000);
+    Duration::from_millis(timeout_ms)
+}
+
+/// `swap` bounded by SWAP_TIMEOUT_MS
+///
+/// The swap keeps running in the background after the deadline, blocking rpc calls can't be
+/// cancelled, and any transaction it already submitted is reported in the error.
+pub async fn swap_with_timeout(state: AppState, params: SwapParams) -> Result<SwapResult> {
+    let timeout = get_swap_timeout();
+    let submitted = Arc::new(Mutex::new(vec![]));
+    // the spawned swap keeps logging under the request span and id
+    let handle = tokio::spawn(
+        request_id::propagate(tx::track_submitted(submitted.clone(), swap(state, params)))
+            .instrument(Span::current()),
+    );
+    match tokio::time::timeout(timeout, handle).await {
+        Ok(Ok(result)) => result,
+        // the task panicked or was cancelled, a coded error comes back as `Ok(Err(..))`
+        Ok(Err(err)) => {
+            Err(ApiError::new(ErrorCode::Internal, format!("swap task failed: {}", err)).into())
+        }
+        Err(_) => {
+            let signatures = submitted.lock().unwrap().clone();
+            warn!(
+                "swap timed out after {:?}, submitted: {:?}",
+                timeout, signatures
+            );
+            let message = if signatures.is_empty() {
+                format!("swap did not complete within {}ms", timeout.as_millis())
+            } else {
+                format!(
+                    "swap did not complete within {}ms, transaction {} was submitted and may still land",
+                    timeout.as_millis(),
+                    signatures.join(", ")
+                )
+            };
+            Err(ApiError::new(ErrorCode::Timeout, message)
+                .with_data(json!({ "signatures": signatures }))
+                .into())
+        }
+    }
+}
+
+/// Jito bundles hold at most 5 transactions, one is kept for the tip
+pub const MAX_BUNDLE_SWAPS: usize = 4;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct BundleSimulation {
+    /// no transaction reverted
+    pub success: bool,
+    pub total_units_consumed: u64,
+    pub transactions: Vec<BundleTransaction>,
+    /// the tip transaction, left out when a swap could not be built
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tip: Option<SimulatedTransaction>,
+}
+
+impl BundleSimulation {
+    /// Why the first reverted transaction failed
+    pub fn failure(&self) -> Option<String> {
+        self.transactions
+            .iter()
+            .find(|transaction| !transaction.simulation.success)
+            .map(|transaction| {
+                format!(
+                    "swap {} ({} {}) reverted: {}",
+                    transaction.index,
+                    transaction.direction.as_str(),
+                    transaction.mint,
+                    transaction
+                        .simulation
+                        .err
+                        .as_deref()
+                        .unwrap_or("unknown error")
+                )
+            })
+            .or_else(|| match &self.tip {
+                Some(tip) if !tip.success => Some(format!(
+                    "tip transaction reverted: {}",
+                    tip.err.as_deref().unwrap_or("unknown error")
+                )),
+                _ => None,
+            })
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct BundleTransaction {
+    /// position of the swap in the request
+    pub index: usize,
+    pub mint: String,
+    pub direction: SwapDirection,
+    pub venue: Venue,
+    #[serde(flatten)]
+    pub simulation: SimulatedTransaction,
+}
+
+/// A bundle that landed, its swaps went through together
+#[derive(Debug, Clone, Serialize)]
+pub struct BundleSubmission {
+    pub simulation: BundleSimulation,
+    /// every transaction of the bundle in order, the tip last
+    pub signatures: Vec<String>,
+    pub explorer_urls: Vec<String>,
+    /// quote of each swap, in request order
+    pub swaps: Vec<SwapOutcome>,
+}
+
+// signed transactions of the swaps of a bundle followed by the tip, not sent
+struct BuiltBundle {
+    transactions: Vec<VersionedTransaction>,
+    // (swap index, venue) of each transaction but the tip
+    sources: Vec<(usize, Venue)>,
+    outcomes: Vec<SwapOutcome>,
+}
+
+// the bundle of `swaps`, or the swap that could not be built, e.g. failing to quote
+async fn build_bundle(
+    state: &AppState,
+    swaps: &[SwapParams],
+) -> Result<Result<BuiltBundle, BundleTransaction>> {
+    if swaps.is_empty() || swaps.len() > MAX_BUNDLE_SWAPS {
+        return Err(ApiError::new(
+            ErrorCode::InvalidInput,
+            format!(
+                "a bundle takes 1 to {} swaps, got {}",
+                MAX_BUNDLE_SWAPS,
+                swaps.len()
+            ),
+        )
+        .into());
+    }
+    jito::check_network()?;
+    let built = Arc::new(Mutex::new(vec![]));
+    let mut sources = vec![];
+    let mut outcomes = vec![];
+    for (index, params) in swaps.iter().enumerate() {
+        let mut params = params.clone();
+        // the tip pays for the bundle, no priority fee
+        params.use_jito = true;
+        check_params(state, &params)?;
+        convert_usd_amount(&mut params).await?;
+        let params = &params;
+        let client_blocking = get_rpc_client_blocking_with_commitment(params.commitment)?;
+        let venue = params_venue(client_blocking.clone(), params).await;
+        check_venue(params, venue)?;
+
+        let before = built.lock().unwrap().len();
+        let result = tx::build_only(
+            built.clone(),
+            venue_swap(
+                state,
+                client_blocking,
+                venue,
+                params,
+                params.pool_id.clone(),
+            ),
+        )
+        .await;
+        match result {
+            Ok(outcome) => {
+                let count = built.lock().unwrap().len() - before;
+                sources.extend(std::iter::repeat((index, venue)).take(count));
+                outcomes.push(outcome);
+            }
+            Err(err) => {
+                return Ok(Err(BundleTransaction {
+                    index,
+                    mint: params.mint.clone(),
+                    direction: params.direction.clone(),
+                    venue,
+                    simulation: SimulatedTransaction {
+                        success: false,
+                        err: Some(err.to_string()),
+                        logs: vec![],
+                        units_consumed: None,
+                    },
+                }))
+            }
+        }
+    }
+    let mut transactions = std::mem::take(&mut *built.lock().unwrap());
+    if transactions.len() > MAX_BUNDLE_SWAPS {
+        return Err(ApiError::new(
+            ErrorCode::InvalidInput,
+            format!(
+                "the swaps take {} transactions, a bundle holds {} next to the tip",
+                transactions.len(),
+                MAX_BUNDLE_SWAPS
+            ),
+        )
+        .into());
+    }
+    let recent_blockhash = get_rpc_client_blocking()?.get_latest_blockhash()?;
+    transactions.push(tx::tip_transaction(&state.wallet(), recent_blockhash).await?);
+    Ok(Ok(BuiltBundle {
+        transactions,
+        sources,
+        outcomes,
+    }))
+}
+
+async fn simulate_built_bundle(
+    bundle: &BuiltBundle,
+    swaps: &[SwapParams],
+) -> Result<BundleSimulation> {
+    let mut results = jito::simulate_bundle(&bundle.transactions).await?;
+    let tip = results.pop();
+    let transactions: Vec<BundleTransaction> = bundle
+        .sources
+        .iter()
+        .zip(results)
+        .map(|((index, venue), simulation)| BundleTransaction {
+            index: *index,
+            mint: swaps[*index].mint.clone(),
+            direction: swaps[*index].direction.clone(),
+            venue: *venue,
+            simulation,
+        })
+        .collect();
+    Ok(BundleSimulation {
+        success: transactions
+            .iter()
+            .all(|transaction| transaction.simulation.success)
+            && tip.as_ref().is_some_and(|tip| tip.success),
+        total_units_consumed: transactions
+            .iter()
+            .filter_map(|transaction| transaction.simulation.units_consumed)
+            .chain(tip.as_ref().and_then(|tip| tip.units_consumed))
+            .sum(),
+        transactions,
+        tip,
+    })
+}
+
+fn unbuilt_simulation(failed: BundleTransaction) -> BundleSimulation {
+    BundleSimulation {
+        success: false,
+        total_units_consumed: 0,
+        transactions: vec![failed],
+        tip: None,
+    }
+}
+
+/// Build the transaction of every swap plus the jito tip and simulate them as one bundle,
+/// nothing is sent
+///
+/// `simulateBundle` runs each transaction on the state the previous ones left, so selling what
+/// an earlier swap of the bundle buys is chained. Swaps are quoted on the current state though,
+/// such a sell needs a token amount, not a percentage of a balance it does not hold yet.
+pub async fn simulate_bundle(state: &AppState, swaps: &[SwapParams]) -> Result<BundleSimulation> {
+    match build_bundle(state, swaps).await? {
+        Ok(bundle) => simulate_built_bundle(&bundle, swaps).await,
+        Err(failed) => Ok(unbuilt_simulation(failed)),
+    }
+}
+
+/// Simulate the bundle, then send exactly those transactions as one jito bundle if none
+/// reverted: the swaps land together or not at all
+pub async fn submit_bundle(state: AppState, swaps: Vec<SwapParams>) -> Result<BundleSubmission> {
+    let simulation_failed = |simulation: BundleSimulation| -> anyhow::Error {
+        let reason = simulation.failure().unwrap_or_default();
+        ApiError::new(ErrorCode::SimulationFailed, reason)
+            .with_data(json!(simulation))
+            .into()
+    };
+    let bundle = match build_bundle(&state, &swaps).await? {
+        Ok(bundle) => bundle,
+        Err(failed) => return Err(simulation_failed(unbuilt_simulation(failed))),
+    };
+    let simulation = simulate_built_bundle(&bundle, &swaps).await?;
+    if simulation.failure().is_some() {
+        return Err(simulation_failed(simulation));
+    }
+    let client = get_rpc_client_blocking()?;
+    let result = tx::send_bundle(&client, bundle.transactions).await;
+    state.breaker.record(&result);
+    let signatures = result?;
+    Ok(BundleSubmission {
+        simulation,
+        explorer_urls: signatures
+            .iter()
+            .map(|signature| explorer_tx_url(signature))
+            .collect(),
+        signatures,
+        swaps: bundle.outcomes,
+    })
+}
+
+pub async fn swap(state: AppState, mut params: SwapParams) -> Result<SwapResult> {
+    check_params(&state, &params)?;
+    let usd_conversion = convert_usd_amount(&mut params).await?;
+    let client_blocking = get_rpc_client_blocking_with_commitment(params.commitment)?;
+    let venue = params_venue(client_blocking.clone(), &params).await;
+    check_venue(&params, venue)?;
+    let SwapParams {
+        mint,
+        direction: swap_direction,
+        amount_in,
+        slippage,
+        pool_selection: pool_selection_policy,
+        webhook_url,
+        pool_id,
+        ..
+    } = params.clone();
+    let mint = mint.as_str();
+    let wallet = state.wallet();
+
+    let labels = [
+        ("venue", venue.as_str()),
+        ("direction", swap_direction.as_str()),
+    ];
+    counter!(telemetry::SWAPS_ATTEMPTED, &labels).increment(1);
+    let start_time = Instant::now();
+
+    let pool_selection = match (venue, &pool_id) {
+        (Venue::Raydium, None) => {
+            select_raydium_pool(client_blocking.clone(), mint, pool_selection_policy).await
+        }
+        _ => None,
+    };
+    let pool_id = pool_id.or(pool_selection.as_ref().map(|s| s.chosen.pool_id.clone()));
+
+    // min_sol_out is an explicit floor, it is never loosened
+    let auto_bump_slippage = params.auto_bump_slippage && params.min_sol_out.is_none();
+    let mut attempt = params.clone();
+    let mut slippage_retries = 0;
+    let result = loop {
+        let result = send_swap(
+            &state,
+            client_blocking.clone(),
+            venue,
+            &attempt,
+            pool_id.clone(),
+        )
+        .await;
+        match result {
+            Err(err)
+                if auto_bump_slippage
+                    && slippage_retries < get_slippage_bump_retries()
+                    && ApiError::from(&err).code == ErrorCode::SlippageExceeded =>
+            {
+                let Some(slippage) = attempt
+                    .slippage
+                    .bumped(get_slippage_bump_step(), get_slippage_bump_max())
+                else {
+                    break Err(err);
+                };
+                warn!(
+                    "slippage exceeded with {:?}, retrying with {:?}: {}",
+                    attempt.slippage, slippage, err
+                );
+                attempt.slippage = slippage;
+                slippage_retries += 1;
+            }
+            result => break result,
+        }
+    };
+
+    state.breaker.record(&result);
+    let elapsed = start_time.elapsed();
+    histogram!(telemetry::SWAP_DURATION, &labels).record(elapsed.as_secs_f64());
+    match &result {
+        Ok(_) => counter!(telemetry::SWAPS_SUCCEEDED, &labels).increment(1),
+        Err(_) => counter!(telemetry::SWAPS_FAILED, &labels).increment(1),
+    }
+
+    let record = TradeRecord {
+        id: 0,
+        timestamp: now_millis(),
+        wallet: wallet.pubkey().to_string(),
+        mint: mint.to_string(),
+        venue,
+        direction: swap_direction.clone(),
+        success: result.is_ok(),
+        signatures: result
+            .as_ref()
+            .map(|outcome| outcome.signatures.clone())
+            .unwrap_or_default(),
+        error: result.as_ref().err().map(|err| err.to_string()),
+        duration_ms: elapsed.as_millis() as u64,
+        amount_in: result.as_ref().ok().map(|outcome| outcome.amount_in),
+        expected_out: result.as_ref().ok().map(|outcome| outcome.expected_out),
+        price_impact_pct: result.as_ref().ok().map(|outcome| outcome.price_impact_pct),
+        amount_out: None,
+        realized_slippage_pct: None,
+        sol_price: None,
+        costs: None,
+    };
+    let record_id = state.ledger.record(record).await;
+    if let Some(url) = webhook_url.or_else(webhook::get_webhook_url) {
+        webhook::notify(
+            url,
+            SwapEvent {
+                mint: mint.to_string(),
+                direction: swap_direction.clone(),
+                amount: amount_in,
+                signature: result
+                    .as_ref()
+                    .ok()
+                    .and_then(|outcome| outcome.signatures.first().cloned()),
+                status: if result.is_ok() {
+                    SwapStatus::Success
+                } else {
+                    SwapStatus::Failed
+                },
+                error: result.as_ref().err().map(|err| err.to_string()),
             },
-            |pump_info| !pump_info.complete,
         );
+    }
+    if let Ok(outcome) = &result {
+        if let Some(signature) = outcome.signatures.first() {
+            tokio::spawn(record_fill(
+                state.ledger.clone(),
+                client_blocking.clone(),
+                record_id,
+                signature.clone(),
+                wallet.pubkey(),
+                mint.to_string(),
+                swap_direction.clone(),
+                outcome.clone(),
+            ));
+        }
+    }
 
-    if swap_in_pump {
-        info!("swap in pump fun");
-        let mut swapx = pump::Pump::new(client, wallet);
-        swapx.with_blocking_client(client_blocking);
-        swapx
-            .swap(mint, amount_in, swap_direction, in_type, slippage, use_jito)
-            .await
+    let outcome = result?;
+    let costs = match params.confirm_mode {
+        tx::ConfirmMode::Sync => read_costs(&state, &client_blocking, record_id, &outcome).await,
+        tx::ConfirmMode::Async => None,
+    };
+    let summary = summarize(&state, mint, &swap_direction, &outcome).await;
+    Ok(SwapResult {
+        venue,
+        slippage_mode: slippage.mode(),
+        pool_selection,
+        explorer_urls: outcome
+            .signatures
+            .iter()
+            .map(|signature| explorer_tx_url(signature))
+            .collect(),
+        summary,
+        usd_conversion,
+        mode: params.confirm_mode,
+        slippage_retries: params.auto_bump_slippage.then_some(slippage_retries),
+        costs_pending: costs.is_none(),
+        costs,
+        outcome,
+    })
+}
+
+// costs of a confirmed swap, stored on its ledger record
+async fn read_costs(
+    state: &AppState,
+    client: &solana_client::rpc_client::RpcClient,
+    record_id: u64,
+    outcome: &SwapOutcome,
+) -> Option<tx::TxCosts> {
+    let costs = tx::get_tx_costs(client, &outcome.signatures)
+        .await
+        .inspect_err(|err| debug!("failed to read swap costs: {}", err))
+        .ok()?;
+    state
+        .ledger
+        .update(record_id, |record| record.costs = Some(costs))
+        .await;
+    Some(costs)
+}
+
+// `venue_swap` keeping its transactions for bumps and status lookups
+async fn send_swap(
+    state: &AppState,
+    client_blocking: Arc<solana_client::rpc_client::RpcClient>,
+    venue: Venue,
+    params: &SwapParams,
+    pool_id: Option<String>,
+) -> Result<SwapOutcome> {
+    let swap = tx::remember_sent(
+        state.sent_transactions.clone(),
+        tx::track_status(
+            state.tx_statuses.clone(),
+            params.confirm_mode,
+            venue_swap(state, client_blocking, venue, params, pool_id),
+        ),
+    );
+    if get_cu_estimate() {
+        let mut swap_type = format!("{}:{}", venue.as_str(), params.direction.as_str());
+        // buys always carry the idempotent create, which costs more when it creates the account
+        if params.direction == SwapDirection::Buy {
+            let wallet = state.wallet().pubkey();
+            let out_ata = Pubkey::from_str(&params.mint)
+                .map(|mint| get_associated_token_address(&wallet, &mint))?;
+            if state.client.get_account(&out_ata).await.is_err() {
+                swap_type.push_str(":new_ata");
+            }
+        }
+        tx::estimate_compute_units(swap_type, swap).await
     } else {
-        info!("swap in raydium");
-        let mut swapx = raydium::Raydium::new(client, wallet);
-        swapx.with_blocking_client(client_blocking);
-        swapx
-            .swap(mint, amount_in, swap_direction, in_type, slippage, use_jito)
+        swap.await
+    }
+}
+
+// builds and sends the swap on `venue`, shared by real swaps and bundle simulation
+async fn venue_swap(
+    state: &AppState,
+    client_blocking: Arc<solana_client::rpc_client::RpcClient>,
+    venue: Venue,
+    params: &SwapParams,
+    pool_id: Option<String>,
+) -> Result<SwapOutcome> {
+    let client = get_rpc_client_with_commitment(params.commitment)?;
+    match venue {
+        Venue::Pump => {
+            info!("swap in pump fun");
+            let mut swapx = pump::Pump::new(client, state.wallet());
+            swapx
+                .with_blocking_client(client_blocking.clone())
+                .with_decimals_cache(state.decimals_cache.clone())
+                .with_min_sol_out(params.min_sol_out);
+            swapx
+                .swap(
+                    &params.mint,
+                    params.amount_in,
+                    params.direction.clone(),
+                    params.in_type.clone(),
+                    params.slippage,
+                    params.use_jito,
+                )
+                .await
+        }
+        Venue::Raydium => {
+            info!("swap in raydium");
+            let mut swapx = raydium::Raydium::new(client, state.wallet());
+            swapx
+                .with_blocking_client(client_blocking.clone())
+                .with_decimals_cache(state.decimals_cache.clone())
+                .with_pool_id(pool_id);
+            swapx
+                .swap(
+                    &params.mint,
+                    params.amount_in,
+                    params.direction.clone(),
+                    params.in_type.clone(),
+                    params.slippage,
+                    params.use_jito,
+                )
+                .await
+        }
+        Venue::Meteora => {
+            info!("swap in meteora dlmm");
+            let mut swapx = meteora::Meteora::new(client, state.wallet());
+            swapx
+                .with_blocking_client(client_blocking.clone())
+                .with_decimals_cache(state.decimals_cache.clone());
+            swapx
+                .swap(
+                    &params.mint,
+                    params.amount_in,
+                    params.direction.clone(),
+                    params.in_type.clone(),
+                    params.slippage,
+                    params.use_jito,
+                )
+                .await
+        }
+        Venue::PumpSwap => {
+            info!("swap in pumpswap");
+            let mut swapx = pump::Pump::new(client, state.wallet());
+            swapx
+                .with_blocking_client(client_blocking.clone())
+                .with_decimals_cache(state.decimals_cache.clone())
+                .with_min_sol_out(params.min_sol_out);
+            swapx
+                .pump_swap(
+                    &params.mint,
+                    params.amount_in,
+                    params.direction.clone(),
+                    params.in_type.clone(),
+                    params.slippage,
+                    params.use_jito,
+                )
+                .await
+        }
+    }
+}
+
+// amounts fall back to raw units if the mint cannot be read
+async fn summarize(
+    state: &AppState,
+    mint: &str,
+    direction: &SwapDirection,
+    outcome: &SwapOutcome,
+) -> SwapSummary {
+    let mint_pubkey = Pubkey::from_str(mint).ok();
+    let mut symbol = None;
+    let mut decimals = None;
+    if let Some(mint_pubkey) = mint_pubkey {
+        if let Ok(info) = state
+            .metadata_cache
+            .get(&state.client, &state.decimals_cache, &mint_pubkey)
             .await
+            .inspect_err(|err| debug!("no token info for {}: {}", mint, err))
+        {
+            symbol = info.symbol;
+            decimals = Some(info.decimals);
+        }
+    }
+    let token_label = symbol
+        .clone()
+        .unwrap_or_else(|| format!("{}..{}", &mint[..4], &mint[mint.len() - 4..]));
+    let token_ui = |amount: u64| match decimals {
+        Some(decimals) => amount_to_ui_amount(amount, decimals),
+        None => amount as f64,
+    };
+    let sol_ui = |amount: u64| amount_to_ui_amount(amount, spl_token::native_mint::DECIMALS);
+    let (amount_in, amount_out, text) = match direction {
+        SwapDirection::Buy => {
+            let (amount_in, amount_out) =
+                (sol_ui(outcome.amount_in), token_ui(outcome.expected_out));
+            let text = format!("buy {} SOL -> ~{} {}", amount_in, amount_out, token_label);
+            (amount_in, amount_out, text)
+        }
+        SwapDirection::Sell => {
+            let (amount_in, amount_out) =
+                (token_ui(outcome.amount_in), sol_ui(outcome.expected_out));
+            let text = format!("sell {} {} -> ~{} SOL", amount_in, token_label, amount_out);
+            (amount_in, amount_out, text)
+        }
+    };
+    SwapSummary {
+        direction: direction.clone(),
+        mint: mint.to_string(),
+        symbol,
+        amount_in,
+        amount_out,
+        text,
+    }
+}
+
+// read the confirmed transaction to fill in the realized output and slippage
+#[allow(clippy::too_many_arguments)]
+async fn record_fill(
+    ledger: Arc<Ledger>,
+    client: Arc<solana_client::rpc_client::RpcClient>,
+    record_id: u64,
+    signature: String,
+    owner: Pubkey,
+    mint: String,
+    swap_direction: SwapDirection,
+    outcome: SwapOutcome,
+) {
+    let Ok(mint) = Pubkey::from_str(&mint) else {
+        return;
+    };
+    let meta = match tx::get_transaction_meta(&client, &signature).await {
+        Ok(meta) => meta,
+        Err(err) => {
+            debug!("failed to read balance changes of {}: {}", signature, err);
+            return;
+        }
+    };
+    let changes = tx::balance_changes(&meta, &owner, &mint);
+    let costs = tx::tx_costs(&client, &meta, &outcome.signatures)
+        .await
+        .inspect_err(|err| debug!("failed to read costs of {}: {}", signature, err))
+        .ok();
+    let (actual_in, actual_out) = match swap_direction {
+        SwapDirection::Buy => (
+            (-changes.trade_sol_delta()).max(0) as u64,
+            changes.token_delta.max(0) as u64,
+        ),
+        SwapDirection::Sell => (
+            (-changes.token_delta).max(0) as u64,
+            changes.trade_sol_delta().max(0) as u64,
+        ),
+    };
+    let realized_slippage_pct = realized_slippage_pct(
+        outcome.amount_in,
+        outcome.expected_out,
+        actual_in,
+        actual_out,
+    );
+    let sol_price = get_solana_price()
+        .await
+        .inspect_err(|err| debug!("no sol price for {}: {}", signature, err))
+        .ok();
+    ledger
+        .update(record_id, |record| {
+            record.amount_out = Some(actual_out);
+            record.realized_slippage_pct = realized_slippage_pct;
+            record.sol_price = sol_price;
+            record.costs = record.costs.or(costs);
+        })
+        .await;
+}
+
+// positive when the fill price was worse than the quote
+pub fn realized_slippage_pct(
+    quoted_in: u64,
+    quoted_out: u64,
+    actual_in: u64,
+    actual_out: u64,
+) -> Option<f64> {
+    if quoted_in == 0 || quoted_out == 0 || actual_in == 0 || actual_out == 0 {
+        return None;
+    }
+    let quoted_price = quoted_in as f64 / quoted_out as f64;
+    let actual_price = actual_in as f64 / actual_out as f64;
+    Some((actual_price / quoted_price - 1.0) * 100.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_price_impact_pct() {
+        assert_eq!(price_impact_pct(0, 0), 0.0);
+        assert_eq!(price_impact_pct(100, 900), 10.0);
+    }
+
+    #[test]
+    fn test_depth_slippage() {
+        let slippage = Slippage::Depth {
+            buffer_bps: 100,
+            max_bps: 5000,
+        };
+        // 1% of the pool -> ~0.99% impact, rounded up, plus the buffer
+        assert_eq!(slippage.resolve_bps(1_000, 100_000), 200);
+        // tiny trade on a deep pool only needs the buffer
+        assert_eq!(slippage.resolve_bps(1, 1_000_000_000_000), 101);
+        // half the pool hits the cap
+        assert_eq!(slippage.resolve_bps(100_000, 100_000), 5000);
+        assert_eq!(Slippage::Fixed(5).resolve_bps(1_000, 100_000), 500);
+    }
+
+    fn candidate(
+        pool_id: &str,
+        program_id: &str,
+        sol_liquidity: u64,
+        fee_bps: u64,
+    ) -> PoolCandidate {
+        PoolCandidate {
+            pool_id: pool_id.to_string(),
+            program_id: program_id.to_string(),
+            sol_liquidity,
+            fee_bps,
+        }
+    }
+
+    #[test]
+    fn test_slippage_bumped() {
+        assert_eq!(Slippage::Fixed(10).bumped(5, 30), Some(Slippage::Fixed(15)));
+        assert_eq!(Slippage::Fixed(28).bumped(5, 30), Some(Slippage::Fixed(30)));
+        assert_eq!(Slippage::Fixed(30).bumped(5, 30), None);
+        let depth = Slippage::Depth {
+            buffer_bps: 100,
+            max_bps: 2000,
+        };
+        assert_eq!(
+            depth.bumped(5, 22),
+            Some(Slippage::Depth {
+                buffer_bps: 600,
+                max_bps: 2200,
+            })
+        );
+        assert_eq!(depth.bumped(5, 20), None);
+    }
+
+    #[test]
+    fn test_to_base_units() {
+        assert_eq!(to_base_units(0.1, 9).unwrap(), 100_000_000);
+        // 1.001 * 1e9 as a float is 1000999999.9999999, `ui_amount_to_amount` gives 1000999999
+        assert_eq!(to_base_units(1.001, 9).unwrap(), 1_001_000_000);
+        assert_eq!(to_base_units(1.005, 6).unwrap(), 1_005_000);
+        assert_eq!(to_base_units(0.3, 9).unwrap(), 300_000_000);
+        assert_eq!(to_base_units(1.1, 9).unwrap(), 1_100_000_000);
+        assert_eq!(to_base_units(4.35, 6).unwrap(), 4_350_000);
+        assert_eq!(to_base_units(0.0000001, 9).unwrap(), 100);
+        assert_eq!(
+            to_base_units(123456789.123456, 6).unwrap(),
+            123_456_789_123_456
+        );
+        // shorter than the smallest unit is cut, not rounded
+        assert_eq!(to_base_units(1.0000000009, 9).unwrap(), 1_000_000_000);
+
+        for amount in [0.0, -1.0, f64::NAN, f64::INFINITY, 0.0000000001] {
+            let err = to_base_units(amount, 9).unwrap_err();
+            assert_eq!(ApiError::from(&err).code, ErrorCode::InvalidInput);
+        }
+        assert!(to_base_units(1e30, 9).is_err());
+    }
+
+    #[test]
+    fn test_pct_of() {
+        assert_eq!(pct_of(1_000_000, 1.0).unwrap(), 1_000_000);
+        assert_eq!(pct_of(1_000_000, 0.555).unwrap(), 555_000);
+        assert_eq!(pct_of(3, 0.5).unwrap(), 1);
+        assert_eq!(pct_of(u64::MAX, 1.0).unwrap(), u64::MAX);
+        assert!(pct_of(1_000_000, 1.5).is_err());
+        assert!(pct_of(1_000_000, 0.0).is_err());
+    }
+
+    #[test]
+    fn test_min_liquidity() {
+        let pool = Pubkey::new_unique();
+        assert!(check_min_liquidity(&pool, 5_000_000_000, 5.0).is_ok());
+        let err = ApiError::from(check_min_liquidity(&pool, 1_500_000_000, 5.0).unwrap_err());
+        assert_eq!(err.code, ErrorCode::InsufficientLiquidity);
+        assert!(err.message.contains("holds 1.5 sol"));
+    }
+
+    #[test]
+    fn test_check_route() {
+        let pool = Pubkey::new_unique();
+        assert!(check_route(&pool, 1_000, 2_000).is_ok());
+        for (reserve_in, reserve_out) in [(0, 2_000), (1_000, 0), (0, 0)] {
+            let err = check_route(&pool, reserve_in, reserve_out).unwrap_err();
+            assert_eq!(ApiError::from(&err).code, ErrorCode::NoRoute);
+            assert!(err.to_string().contains(&pool.to_string()));
+        }
+        assert!(check_quote(&pool, 1).is_ok());
+        let err = check_quote(&pool, 0).unwrap_err();
+        assert_eq!(ApiError::from(&err).code, ErrorCode::NoRoute);
+    }
+
+    #[test]
+    fn test_select_pool() {
+        let candidates = vec![
+            candidate("a", "amm", 100, 25),
+            candidate("b", "amm", 300, 30),
+            candidate("c", "cpmm", 200, 10),
+            candidate("d", "cpmm", 200, 10),
+        ];
+        assert!(select_pool(PoolSelectionPolicy::Liquidity, None, vec![]).is_none());
+
+        let selection =
+            select_pool(PoolSelectionPolicy::Liquidity, None, candidates.clone()).unwrap();
+        assert_eq!(selection.chosen.pool_id, "b");
+        let runner_ups: Vec<&str> = selection
+            .runner_ups
+            .iter()
+            .map(|c| c.pool_id.as_str())
+            .collect();
+        assert_eq!(runner_ups, vec!["c", "d", "a"]);
+
+        let selection = select_pool(PoolSelectionPolicy::Fee, None, candidates.clone()).unwrap();
+        assert_eq!(selection.chosen.pool_id, "c");
+
+        let selection = select_pool(
+            PoolSelectionPolicy::Program,
+            Some("amm"),
+            candidates.clone(),
+        )
+        .unwrap();
+        assert_eq!(selection.chosen.pool_id, "b");
+        assert_eq!(selection.runner_ups[0].pool_id, "a");
+        // no preference configured ranks by liquidity
+        let selection = select_pool(PoolSelectionPolicy::Program, None, candidates).unwrap();
+        assert_eq!(selection.chosen.pool_id, "b");
+    }
+
+    #[test]
+    fn test_realized_slippage_pct() {
+        // filled exactly at the quote
+        assert_eq!(realized_slippage_pct(100, 200, 100, 200), Some(0.0));
+        // received 10% less than quoted for the same input
+        let slippage = realized_slippage_pct(100, 200, 100, 180).unwrap();
+        assert!((slippage - 11.111111).abs() < 1e-4);
+        assert_eq!(realized_slippage_pct(100, 200, 100, 0), None);
     }
 }
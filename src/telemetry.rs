@@ -0,0 +1,72 @@
+use std::sync::OnceLock;
+
+use anyhow::Result;
+use metrics::{describe_counter, describe_gauge, describe_histogram, gauge};
+use metrics_exporter_prometheus::{Matcher, PrometheusBuilder, PrometheusHandle};
+
+pub const SWAPS_ATTEMPTED: &str = "raytx_swaps_attempted_total";
+pub const SWAPS_SUCCEEDED: &str = "raytx_swaps_succeeded_total";
+pub const SWAPS_FAILED: &str = "raytx_swaps_failed_total";
+pub const SWAP_DURATION: &str = "raytx_swap_duration_seconds";
+pub const JITO_BUNDLES: &str = "raytx_jito_bundles_total";
+pub const JITO_BUNDLE_DURATION: &str = "raytx_jito_bundle_duration_seconds";
+pub const ACTIVE_MONITORS: &str = "raytx_active_monitors";
+pub const SELL_ONLY: &str = "raytx_sell_only";
+
+const LATENCY_BUCKETS: &[f64] = &[0.1, 0.25, 0.5, 1.0, 2.0, 5.0, 10.0, 20.0, 30.0, 60.0];
+
+static PROMETHEUS: OnceLock<PrometheusHandle> = OnceLock::new();
+
+/// install the prometheus recorder, metrics recorded before this are dropped
+pub fn init() -> Result<()> {
+    let handle = PrometheusBuilder::new()
+        .set_buckets_for_metric(
+            Matcher::Suffix("_duration_seconds".to_string()),
+            LATENCY_BUCKETS,
+        )?
+        .install_recorder()?;
+    let _ = PROMETHEUS.set(handle);
+
+    describe_counter!(SWAPS_ATTEMPTED, "Swaps attempted, by venue and direction");
+    describe_counter!(SWAPS_SUCCEEDED, "Swaps succeeded, by venue and direction");
+    describe_counter!(SWAPS_FAILED, "Swaps failed, by venue and direction");
+    describe_histogram!(SWAP_DURATION, "End to end swap latency in seconds");
+    describe_counter!(JITO_BUNDLES, "Jito bundles, by status");
+    describe_histogram!(
+        JITO_BUNDLE_DURATION,
+        "Jito bundle submission to confirmation latency in seconds"
+    );
+    describe_gauge!(ACTIVE_MONITORS, "Active background position monitors");
+    describe_gauge!(SELL_ONLY, "1 while sell only mode is on");
+    gauge!(ACTIVE_MONITORS).set(0.0);
+    Ok(())
+}
+
+pub fn render() -> Option<String> {
+    PROMETHEUS.get().map(|handle| handle.render())
+}
+
+// call in pairs from long-running tasks that watch a position
+pub fn monitor_started() {
+    gauge!(ACTIVE_MONITORS).increment(1.0);
+}
+
+pub fn monitor_stopped() {
+    gauge!(ACTIVE_MONITORS).decrement(1.0);
+}
+
+/// Counts a monitor while alive, keeps the gauge right when its task is aborted
+pub struct MonitorGuard;
+
+impl MonitorGuard {
+    pub fn start() -> Self {
+        monitor_started();
+        Self
+    }
+}
+
+impl Drop for MonitorGuard {
+    fn drop(&mut self) {
+        monitor_stopped();
+    }
+}
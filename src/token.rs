@@ -1,10 +1,23 @@
-use std::sync::Arc;
+use std::{
+    collections::HashMap,
+    env,
+    future::Future,
+    str::FromStr,
+    sync::Arc,
+    time::{Duration, Instant},
+};
 
 use anyhow::{anyhow, Result};
 use serde::{Deserialize, Serialize};
 use solana_account_decoder::UiAccountData;
 use solana_client::{nonblocking::rpc_client::RpcClient, rpc_request::TokenAccountsFilter};
-use solana_sdk::{pubkey::Pubkey, signature::Keypair};
+use solana_sdk::{
+    native_token::lamports_to_sol, pubkey::Pubkey, signature::Keypair, signer::Signer,
+    system_instruction,
+};
+use spl_associated_token_account::{
+    get_associated_token_address, instruction::create_associated_token_account_idempotent,
+};
 use spl_token_2022::{
     extension::StateWithExtensionsOwned,
     state::{Account, Mint},
@@ -13,7 +26,17 @@ use spl_token_client::{
     client::{ProgramClient, ProgramRpcClient, ProgramRpcClientSendTransaction},
     token::{TokenError, TokenResult},
 };
-use tracing::{trace, warn};
+use tokio::sync::RwLock;
+use tracing::{debug, info, trace, warn};
+
+use crate::{
+    constants::Symbol,
+    error::{ApiError, ErrorCode},
+    get_client_build, tx,
+};
+
+// close_account references 3 accounts, 20 per tx keeps well under the packet size limit
+pub const CLOSE_ACCOUNTS_PER_TX: usize = 20;
 
 pub type TokenAccounts = Vec<TokenAccount>;
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -22,6 +45,21 @@ pub struct TokenAccount {
     pub mint: String,
     pub amount: String,
     pub ui_amount: f64,
+    pub lamports: u64,
+}
+
+#[derive(Debug, Serialize, Deserialize, Default)]
+pub struct CloseAccountsResult {
+    pub closed: Vec<String>,
+    // requested mints whose account still holds tokens
+    pub skipped: Vec<String>,
+    pub reclaimed_sol: f64,
+    pub signatures: Vec<String>,
+}
+#[derive(Debug, Serialize, Deserialize)]
+pub struct WsolResult {
+    pub signatures: Vec<String>,
+    pub wsol_balance: f64,
 }
 #[derive(Debug, Serialize, Deserialize)]
 struct ParsedAccount {
@@ -56,6 +94,101 @@ struct Amount {
     ui_amount_string: String,
 }
 
+// page size of token account listings
+pub const TOKEN_ACCOUNTS_LIMIT: usize = 100;
+pub const TOKEN_ACCOUNTS_MAX_LIMIT: usize = 500;
+
+#[derive(Debug, Serialize, Clone)]
+pub struct Holding {
+    #[serde(flatten)]
+    pub account: TokenAccount,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub value_usd: Option<f64>,
+}
+
+/// Thresholds below which a token account is dust
+#[derive(Debug, Default, Clone, Copy)]
+pub struct DustFilter {
+    pub min_amount: Option<f64>,
+    pub min_value_usd: Option<f64>,
+}
+
+#[derive(Debug, Default, Serialize)]
+pub struct HoldingsSummary {
+    /// token accounts of the wallet before filtering
+    pub accounts: usize,
+    /// accounts with a non zero balance
+    pub holdings: usize,
+    /// accounts left after the dust filter, across all pages
+    pub matched: usize,
+    /// value of every priced holding, when priced
+    pub total_value_usd: Option<f64>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct HoldingsPage {
+    pub accounts: Vec<Holding>,
+    pub offset: usize,
+    pub limit: usize,
+    pub summary: HoldingsSummary,
+}
+
+/// Drop accounts below `filter` and return one page of the rest
+///
+/// `prices_usd` is the usd price per token keyed by mint, unpriced accounts never pass
+/// `min_value_usd`. Priced pages are sorted by value, unpriced ones by mint.
+pub fn filter_holdings(
+    accounts: TokenAccounts,
+    prices_usd: Option<&HashMap<String, f64>>,
+    filter: DustFilter,
+    offset: usize,
+    limit: usize,
+) -> HoldingsPage {
+    let mut summary = HoldingsSummary {
+        accounts: accounts.len(),
+        holdings: accounts
+            .iter()
+            .filter(|account| account.ui_amount > 0.0)
+            .count(),
+        ..Default::default()
+    };
+    let mut holdings: Vec<Holding> = accounts
+        .into_iter()
+        .map(|account| Holding {
+            value_usd: prices_usd
+                .and_then(|prices| prices.get(&account.mint))
+                .map(|price| price * account.ui_amount),
+            account,
+        })
+        .collect();
+    if prices_usd.is_some() {
+        summary.total_value_usd = Some(holdings.iter().filter_map(|h| h.value_usd).sum());
+    }
+    holdings.retain(|holding| {
+        filter
+            .min_amount
+            .map_or(true, |min| holding.account.ui_amount >= min)
+            && filter.min_value_usd.map_or(true, |min| {
+                holding.value_usd.is_some_and(|value| value >= min)
+            })
+    });
+    match prices_usd {
+        Some(_) => holdings.sort_by(|a, b| {
+            b.value_usd
+                .unwrap_or(0.0)
+                .total_cmp(&a.value_usd.unwrap_or(0.0))
+        }),
+        None => holdings.sort_by(|a, b| a.account.mint.cmp(&b.account.mint)),
+    }
+    summary.matched = holdings.len();
+    HoldingsPage {
+        accounts: holdings.into_iter().skip(offset).take(limit).collect(),
+        offset,
+        limit,
+        summary,
+    }
+}
+
 pub async fn token_account(
     client: &RpcClient,
     owner: &Pubkey,
@@ -66,7 +199,7 @@ pub async fn token_account(
     token_accounts
         .first()
         .cloned()
-        .ok_or(anyhow!("NotFound: token account not found"))
+        .ok_or_else(|| ApiError::new(ErrorCode::NotFound, "token account not found").into())
 }
 
 pub async fn token_accounts(client: &RpcClient, owner: &Pubkey) -> Result<TokenAccounts> {
@@ -100,6 +233,7 @@ async fn token_accounts_filter(
                     mint: parsed.info.mint,
                     amount: parsed.info.token_amount.amount,
                     ui_amount: parsed.info.token_amount.ui_amount,
+                    lamports: token_account.account.lamports,
                 });
             }
             UiAccountData::LegacyBinary(_) | UiAccountData::Binary(_, _) => {
@@ -111,6 +245,136 @@ async fn token_accounts_filter(
     Ok(tas)
 }
 
+/// Close zero-balance token accounts to reclaim their rent, all empty accounts when `mints` is None
+pub async fn close_empty_accounts(
+    client: &RpcClient,
+    client_blocking: &solana_client::rpc_client::RpcClient,
+    keypair: &Keypair,
+    mints: Option<Vec<Pubkey>>,
+) -> Result<CloseAccountsResult> {
+    let owner = keypair.pubkey();
+    let accounts = token_accounts(client, &owner).await?;
+
+    let mut result = CloseAccountsResult::default();
+    let mut empty_accounts = vec![];
+    for account in accounts.into_iter() {
+        if let Some(mints) = &mints {
+            if !mints.iter().any(|mint| mint.to_string() == account.mint) {
+                continue;
+            }
+        }
+        if account.amount == "0" {
+            empty_accounts.push(account);
+        } else if mints.is_some() {
+            result.skipped.push(account.mint);
+        }
+    }
+
+    if empty_accounts.is_empty() {
+        return Ok(result);
+    }
+
+    for chunk in empty_accounts.chunks(CLOSE_ACCOUNTS_PER_TX) {
+        let mut instructions = vec![];
+        for account in chunk {
+            let pubkey = Pubkey::from_str(&account.pubkey)?;
+            instructions.push(spl_token::instruction::close_account(
+                &spl_token::ID,
+                &pubkey,
+                &owner,
+                &owner,
+                &[&owner],
+            )?);
+        }
+        let txs = tx::new_signed_and_send(client_blocking, keypair, instructions, false).await?;
+        let lamports: u64 = chunk.iter().map(|account| account.lamports).sum();
+        info!(
+            "closed {} empty token accounts, reclaimed {} sol",
+            chunk.len(),
+            lamports_to_sol(lamports)
+        );
+        result.reclaimed_sol += lamports_to_sol(lamports);
+        result
+            .closed
+            .extend(chunk.iter().map(|account| account.pubkey.clone()));
+        result.signatures.extend(txs);
+    }
+
+    Ok(result)
+}
+
+/// Move `lamports` of native sol into the wallet WSOL ATA, creating it if needed
+pub async fn wrap_sol(
+    client: &RpcClient,
+    client_blocking: &solana_client::rpc_client::RpcClient,
+    keypair: &Keypair,
+    lamports: u64,
+) -> Result<WsolResult> {
+    if lamports == 0 {
+        return Err(
+            ApiError::new(ErrorCode::InvalidInput, "lamports must be greater than 0").into(),
+        );
+    }
+    let owner = keypair.pubkey();
+    let wsol_mint = Pubkey::from_str(Symbol::WSOL_TOKEN)?;
+    let wsol_ata = get_associated_token_address(&owner, &wsol_mint);
+
+    let instructions = vec![
+        create_associated_token_account_idempotent(&owner, &owner, &wsol_mint, &spl_token::ID),
+        system_instruction::transfer(&owner, &wsol_ata, lamports),
+        spl_token::instruction::sync_native(&spl_token::ID, &wsol_ata)?,
+    ];
+    let signatures = tx::new_signed_and_send(client_blocking, keypair, instructions, false).await?;
+    info!(
+        "wrapped {} sol into {}",
+        lamports_to_sol(lamports),
+        wsol_ata
+    );
+
+    let wsol_balance = client
+        .get_token_account_balance(&wsol_ata)
+        .await?
+        .ui_amount
+        .unwrap_or_default();
+    Ok(WsolResult {
+        signatures,
+        wsol_balance,
+    })
+}
+
+/// Close the wallet WSOL ATA, returning its whole balance as native sol
+pub async fn unwrap_sol(
+    client: &RpcClient,
+    client_blocking: &solana_client::rpc_client::RpcClient,
+    keypair: &Keypair,
+) -> Result<WsolResult> {
+    let owner = keypair.pubkey();
+    let wsol_mint = Pubkey::from_str(Symbol::WSOL_TOKEN)?;
+    let wsol_ata = get_associated_token_address(&owner, &wsol_mint);
+
+    if client.get_account(&wsol_ata).await.is_err() {
+        return Err(ApiError::new(
+            ErrorCode::NotFound,
+            format!("wsol account {} not found", wsol_ata),
+        )
+        .into());
+    }
+    let instructions = vec![spl_token::instruction::close_account(
+        &spl_token::ID,
+        &wsol_ata,
+        &owner,
+        &owner,
+        &[&owner],
+    )?];
+    let signatures = tx::new_signed_and_send(client_blocking, keypair, instructions, false).await?;
+    info!("unwrapped wsol account {}", wsol_ata);
+
+    Ok(WsolResult {
+        signatures,
+        wsol_balance: 0.0,
+    })
+}
+
 pub async fn get_account_info(
     client: Arc<RpcClient>,
     _keypair: Arc<Keypair>,
@@ -207,8 +471,340 @@ pub async fn get_mint_info(
 //     token_client.get_mint_info().await
 // }
 
+/// Decimals by mint, filled on first use, decimals of a mint never change
+#[derive(Default)]
+pub struct DecimalsCache {
+    decimals: RwLock<HashMap<Pubkey, u8>>,
+}
+
+impl DecimalsCache {
+    pub async fn get(&self, client: &RpcClient, mint: &Pubkey) -> Result<u8> {
+        self.get_or_fetch(mint, || async {
+            let account = client.get_account(mint).await?;
+            let mint_state = StateWithExtensionsOwned::<Mint>::unpack(account.data)?;
+            Ok(mint_state.base.decimals)
+        })
+        .await
+    }
+
+    pub async fn get_or_fetch<F, Fut>(&self, mint: &Pubkey, fetch: F) -> Result<u8>
+    where
+        F: FnOnce() -> Fut,
+        Fut: Future<Output = Result<u8>>,
+    {
+        if *mint == spl_token::native_mint::ID {
+            return Ok(spl_token::native_mint::DECIMALS);
+        }
+        if let Some(decimals) = self.decimals.read().await.get(mint) {
+            return Ok(*decimals);
+        }
+        let decimals = fetch().await?;
+        self.decimals.write().await.insert(*mint, decimals);
+        Ok(decimals)
+    }
+}
+
+pub const METADATA_PROGRAM: &str = "metaqbxxUerdq28cj1RbAWkYQm3ybzjb6a8bt518x1s";
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct Metadata {
+    pub name: String,
+    pub symbol: String,
+    pub uri: String,
+}
+
+pub fn get_metadata_pda(mint: &Pubkey) -> Result<Pubkey> {
+    let program_id = Pubkey::from_str(METADATA_PROGRAM)?;
+    let (metadata, _bump) = Pubkey::find_program_address(
+        &[b"metadata", program_id.as_ref(), mint.as_ref()],
+        &program_id,
+    );
+    Ok(metadata)
+}
+
+fn read_string(data: &mut &[u8]) -> Option<String> {
+    let current: &[u8] = data;
+    let len = u32::from_le_bytes(current.get(..4)?.try_into().ok()?) as usize;
+    let value = current.get(4..4 + len)?;
+    *data = &current[4 + len..];
+    Some(
+        String::from_utf8_lossy(value)
+            .trim_end_matches('\0')
+            .to_string(),
+    )
+}
+
+// key(1) + update_authority(32) + mint(32), then borsh strings padded with \0
+fn parse_metadata(data: &[u8]) -> Option<Metadata> {
+    let mut rest = data.get(65..)?;
+    Some(Metadata {
+        name: read_string(&mut rest)?,
+        symbol: read_string(&mut rest)?,
+        uri: read_string(&mut rest)?,
+    })
+}
+
+/// Metaplex metadata of a mint, `NotFound` if the mint has none
+pub async fn get_metadata(client: &RpcClient, mint: &Pubkey) -> Result<Metadata> {
+    let metadata = get_metadata_pda(mint)?;
+    let data = client.get_account_data(&metadata).await.map_err(|_| {
+        ApiError::new(
+            ErrorCode::NotFound,
+            format!("no metadata account for {}", mint),
+        )
+    })?;
+    parse_metadata(&data).ok_or(anyhow!("failed to parse metadata account {}", metadata))
+}
+
+const METADATA_URI_TIMEOUT: Duration = Duration::from_secs(3);
+
+/// Display info of a mint, only `decimals` is set for mints without a metadata account
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct TokenInfo {
+    pub mint: String,
+    pub decimals: u8,
+    pub name: Option<String>,
+    pub symbol: Option<String>,
+    pub uri: Option<String>,
+    /// `image` of the json at `uri`
+    pub image: Option<String>,
+}
+
+// the off-chain json is best effort, pump.fun coins point it to ipfs
+async fn get_uri_image(uri: &str) -> Option<String> {
+    let fetch = async {
+        let json = get_client_build()?
+            .get(uri)
+            .timeout(METADATA_URI_TIMEOUT)
+            .send()
+            .await?
+            .error_for_status()?
+            .json::<serde_json::Value>()
+            .await?;
+        Ok::<Option<String>, anyhow::Error>(json["image"].as_str().map(str::to_string))
+    };
+    fetch
+        .await
+        .inspect_err(|err| debug!("failed to fetch metadata uri {}: {}", uri, err))
+        .ok()
+        .flatten()
+}
+
+/// Decimals, metaplex metadata and image of a mint
+pub async fn get_token_info(
+    client: &RpcClient,
+    decimals_cache: &DecimalsCache,
+    mint: &Pubkey,
+) -> Result<TokenInfo> {
+    let decimals = decimals_cache.get(client, mint).await.map_err(|e| {
+        ApiError::new(
+            ErrorCode::NotFound,
+            format!("{} is not a token mint: {}", mint, e),
+        )
+    })?;
+    let mut info = TokenInfo {
+        mint: mint.to_string(),
+        decimals,
+        name: None,
+        symbol: None,
+        uri: None,
+        image: None,
+    };
+    match get_metadata(client, mint).await {
+        Ok(metadata) => {
+            if !metadata.uri.is_empty() {
+                info.image = get_uri_image(&metadata.uri).await;
+                info.uri = Some(metadata.uri);
+            }
+            info.name = Some(metadata.name).filter(|name| !name.is_empty());
+            info.symbol = Some(metadata.symbol).filter(|symbol| !symbol.is_empty());
+        }
+        Err(err) => debug!("no metadata for {}: {}", mint, err),
+    }
+    Ok(info)
+}
+
+fn get_metadata_cache_ttl() -> Duration {
+    let ttl_ms = env::var("METADATA_CACHE_TTL_MS")
+        .ok()
+        .and_then(|v| v.parse::<u64>().ok())
+        .unwrap_or(3_600_000);
+    Duration::from_millis(ttl_ms)
+}
+
+/// Token info by mint, metadata rarely changes so entries live for METADATA_CACHE_TTL_MS
+pub struct MetadataCache {
+    ttl: Duration,
+    entries: RwLock<HashMap<Pubkey, (Instant, TokenInfo)>>,
+}
+
+impl Default for MetadataCache {
+    fn default() -> Self {
+        Self::new(get_metadata_cache_ttl())
+    }
+}
+
+impl MetadataCache {
+    pub fn new(ttl: Duration) -> Self {
+        Self {
+            ttl,
+            entries: RwLock::new(HashMap::new()),
+        }
+    }
+
+    pub async fn get(
+        &self,
+        client: &RpcClient,
+        decimals_cache: &DecimalsCache,
+        mint: &Pubkey,
+    ) -> Result<TokenInfo> {
+        self.get_or_fetch(mint, || get_token_info(client, decimals_cache, mint))
+            .await
+    }
+
+    pub async fn get_or_fetch<F, Fut>(&self, mint: &Pubkey, fetch: F) -> Result<TokenInfo>
+    where
+        F: FnOnce() -> Fut,
+        Fut: Future<Output = Result<TokenInfo>>,
+    {
+        if let Some((fetched_at, info)) = self.entries.read().await.get(mint) {
+            if fetched_at.elapsed() < self.ttl {
+                return Ok(info.clone());
+            }
+        }
+        let info = fetch().await?;
+        let mut entries = self.entries.write().await;
+        entries.retain(|_, (fetched_at, _)| fetched_at.elapsed() < self.ttl);
+        entries.insert(*mint, (Instant::now(), info.clone()));
+        Ok(info)
+    }
+}
+
 #[cfg(test)]
 mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_decimals_cache() {
+        let cache = DecimalsCache::default();
+        let mint = Pubkey::new_unique();
+        let fetches = std::sync::atomic::AtomicU32::new(0);
+        for _ in 0..2 {
+            let decimals = cache
+                .get_or_fetch(&mint, || async {
+                    fetches.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+                    Ok(6)
+                })
+                .await
+                .unwrap();
+            assert_eq!(decimals, 6);
+        }
+        // the second swap on the mint is served from the cache
+        assert_eq!(fetches.load(std::sync::atomic::Ordering::Relaxed), 1);
+
+        let decimals = cache
+            .get_or_fetch(&spl_token::native_mint::ID, || async { Ok(0) })
+            .await
+            .unwrap();
+        assert_eq!(decimals, 9);
+    }
+
+    #[test]
+    fn test_filter_holdings() {
+        let account = |mint: &str, ui_amount: f64| TokenAccount {
+            pubkey: Pubkey::new_unique().to_string(),
+            mint: mint.to_string(),
+            amount: ui_amount.to_string(),
+            ui_amount,
+            lamports: 2_039_280,
+        };
+        let accounts = vec![account("A", 10.0), account("B", 0.5), account("C", 0.0)];
+        let prices = HashMap::from([("A".to_string(), 2.0), ("B".to_string(), 100.0)]);
+
+        let page = filter_holdings(
+            accounts.clone(),
+            None,
+            DustFilter {
+                min_amount: Some(1.0),
+                min_value_usd: None,
+            },
+            0,
+            TOKEN_ACCOUNTS_LIMIT,
+        );
+        assert_eq!(page.summary.accounts, 3);
+        assert_eq!(page.summary.holdings, 2);
+        assert_eq!(page.summary.matched, 1);
+        assert_eq!(page.summary.total_value_usd, None);
+
+        let page = filter_holdings(
+            accounts,
+            Some(&prices),
+            DustFilter {
+                min_amount: None,
+                min_value_usd: Some(1.0),
+            },
+            0,
+            1,
+        );
+        assert_eq!(page.summary.matched, 2);
+        assert_eq!(page.summary.total_value_usd, Some(70.0));
+        // highest value first
+        assert_eq!(page.accounts.len(), 1);
+        assert_eq!(page.accounts[0].account.mint, "B");
+    }
+
+    #[tokio::test]
+    async fn test_metadata_cache() {
+        let mint = Pubkey::new_unique();
+        let info = TokenInfo {
+            mint: mint.to_string(),
+            decimals: 6,
+            name: None,
+            symbol: None,
+            uri: None,
+            image: None,
+        };
+        let fetches = std::sync::atomic::AtomicU32::new(0);
+        let fetch = || async {
+            fetches.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+            Ok(info.clone())
+        };
+
+        let cache = MetadataCache::new(Duration::from_secs(60));
+        for _ in 0..2 {
+            assert_eq!(cache.get_or_fetch(&mint, fetch).await.unwrap(), info);
+        }
+        assert_eq!(fetches.load(std::sync::atomic::Ordering::Relaxed), 1);
+
+        // expired entries are fetched again
+        let cache = MetadataCache::new(Duration::ZERO);
+        for _ in 0..2 {
+            cache.get_or_fetch(&mint, fetch).await.unwrap();
+        }
+        assert_eq!(fetches.load(std::sync::atomic::Ordering::Relaxed), 3);
+    }
+
+    #[test]
+    fn test_parse_metadata() {
+        let mut data = vec![4u8];
+        data.extend_from_slice(&[0u8; 64]);
+        for (value, padded) in [
+            ("Raytx", 32),
+            ("RTX", 10),
+            ("https://example.com/m.json", 200),
+        ] {
+            let mut bytes = value.as_bytes().to_vec();
+            bytes.resize(padded, 0);
+            data.extend_from_slice(&(padded as u32).to_le_bytes());
+            data.extend_from_slice(&bytes);
+        }
+        let metadata = parse_metadata(&data).unwrap();
+        assert_eq!(metadata.name, "Raytx");
+        assert_eq!(metadata.symbol, "RTX");
+        assert_eq!(metadata.uri, "https://example.com/m.json");
+        assert_eq!(parse_metadata(&data[..80]), None);
+    }
+
     #[cfg(feature = "slow_tests")]
     mod slow_tests {
         use crate::{get_rpc_client, token::token_account};
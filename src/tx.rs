@@ -1,22 +1,376 @@
-use std::{env, sync::Arc, time::Duration};
+use std::{
+    collections::{hash_map::DefaultHasher, HashMap},
+    env,
+    future::Future,
+    hash::{Hash as _, Hasher},
+    sync::{Arc, Mutex, OnceLock},
+    time::Duration,
+};
 
 use anyhow::{anyhow, Result};
 use jito_json_rpc_client::jsonrpc_client::rpc_client::RpcClient as JitoRpcClient;
-use solana_client::rpc_client::RpcClient;
+use metrics::{counter, histogram};
+use solana_address_lookup_table_program::state::AddressLookupTable;
+use solana_client::{
+    client_error::ClientError,
+    nonce_utils,
+    rpc_client::RpcClient,
+    rpc_config::{RpcSimulateTransactionConfig, RpcTransactionConfig},
+};
 use solana_sdk::{
-    instruction::Instruction,
-    signature::Keypair,
+    address_lookup_table_account::AddressLookupTableAccount,
+    commitment_config::CommitmentConfig,
+    hash::Hash,
+    instruction::{Instruction, InstructionError},
+    message::{v0, Message, VersionedMessage},
+    native_token::lamports_to_sol,
+    packet::PACKET_DATA_SIZE,
+    pubkey::Pubkey,
+    signature::{Keypair, Signature},
     signer::Signer,
-    system_transaction,
-    transaction::{Transaction, VersionedTransaction},
+    system_instruction, system_transaction,
+    transaction::{Transaction, TransactionError, VersionedTransaction},
+};
+use solana_transaction_status::{
+    option_serializer::OptionSerializer, TransactionStatus, UiTransactionEncoding,
+    UiTransactionStatusMeta, UiTransactionTokenBalance,
 };
 use spl_token::ui_amount_to_amount;
 
+use serde::{Deserialize, Serialize};
 use std::str::FromStr;
-use tokio::time::Instant;
-use tracing::{error, info};
+use tokio::{
+    sync::Mutex as AsyncMutex,
+    time::{sleep, Instant},
+};
+use tracing::{debug, error, info, warn};
+
+use crate::{
+    error::{ApiError, ErrorCode},
+    get_rpc_client_blocking_with_commitment,
+    jito::{self, get_tip_account, get_tip_value, wait_for_bundle_confirmation},
+    telemetry,
+};
+tokio::task_local! {
+    // signatures sent from the current task, read back when a swap times out
+    static SUBMITTED: Arc<Mutex<Vec<String>>>;
+}
+
+/// Run `f` recording the signature of every transaction it submits into `submitted`
+pub async fn track_submitted<F: Future>(submitted: Arc<Mutex<Vec<String>>>, f: F) -> F::Output {
+    SUBMITTED.scope(submitted, f).await
+}
+
+/// Outcome of simulating one transaction instead of sending it
+#[derive(Debug, Clone, Serialize)]
+pub struct SimulatedTransaction {
+    pub success: bool,
+    pub err: Option<String>,
+    pub logs: Vec<String>,
+    pub units_consumed: Option<u64>,
+}
+
+tokio::task_local! {
+    // set while the transactions of the current task are collected into a bundle, never sent
+    static BUNDLE: Arc<Mutex<Vec<VersionedTransaction>>>;
+}
+
+/// Run `f` building every transaction it would send into `bundle` instead of sending it
+///
+/// The transactions are signed with a recent blockhash and no durable nonce, the transactions
+/// of a bundle can't all advance the same nonce.
+pub async fn build_only<F: Future>(
+    bundle: Arc<Mutex<Vec<VersionedTransaction>>>,
+    f: F,
+) -> F::Output {
+    BUNDLE.scope(bundle, f).await
+}
+
+fn record_submitted(txn: &VersionedTransaction) {
+    if let Some(signature) = txn.signatures.first() {
+        let _ = SUBMITTED.try_with(|submitted| {
+            submitted.lock().unwrap().push(signature.to_string());
+        });
+    }
+}
+
+// how long a sent transaction can be bumped, past the lifetime of its blockhash
+const SENT_TRANSACTION_TTL: Duration = Duration::from_secs(600);
+
+/// What a sent transaction was built from, enough to rebuild it with another fee
+#[derive(Clone)]
+pub struct SentTransaction {
+    /// fee payer and signer
+    payer: Pubkey,
+    /// without the compute budget and advance nonce instructions
+    instructions: Vec<Instruction>,
+    lookup_tables: Vec<AddressLookupTableAccount>,
+    unit_limit: u32,
+    unit_price: u64,
+    use_jito: bool,
+    recent_blockhash: Hash,
+    nonce_account: Option<Pubkey>,
+    sent_at: Instant,
+}
+
+/// Transactions sent within SENT_TRANSACTION_TTL by signature, see `bump`
+#[derive(Default)]
+pub struct SentTransactions {
+    entries: Mutex<HashMap<String, SentTransaction>>,
+}
+
+impl SentTransactions {
+    pub fn get(&self, signature: &str) -> Option<SentTransaction> {
+        self.entries
+            .lock()
+            .unwrap()
+            .get(signature)
+            .filter(|sent| sent.sent_at.elapsed() < SENT_TRANSACTION_TTL)
+            .cloned()
+    }
+
+    fn insert(&self, signature: String, sent: SentTransaction) {
+        let mut entries = self.entries.lock().unwrap();
+        entries.retain(|_, sent| sent.sent_at.elapsed() < SENT_TRANSACTION_TTL);
+        entries.insert(signature, sent);
+    }
+}
+
+tokio::task_local! {
+    // where transactions sent from the current task are kept for `bump`
+    static SENT: Arc<SentTransactions>;
+}
+
+/// Run `f` keeping every transaction it sends in `sent`, so a stuck one can be bumped
+pub async fn remember_sent<F: Future>(sent: Arc<SentTransactions>, f: F) -> F::Output {
+    SENT.scope(sent, f).await
+}
+
+/// Whether sending waits for the transaction to confirm
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ConfirmMode {
+    /// return once confirmed
+    #[default]
+    Sync,
+    /// return once submitted, confirmation is watched in the background
+    Async,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TxState {
+    Pending,
+    Confirmed,
+    Failed,
+    /// not confirmed within CONFIRM_TIMEOUT, its blockhash most likely expired
+    Unconfirmed,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct TxStatus {
+    pub signature: String,
+    pub status: TxState,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub slot: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub err: Option<String>,
+    #[serde(skip)]
+    updated_at: Instant,
+}
+
+impl TxStatus {
+    fn new(signature: String, status: TxState) -> Self {
+        Self {
+            signature,
+            status,
+            slot: None,
+            err: None,
+            updated_at: Instant::now(),
+        }
+    }
+
+    fn from_rpc(signature: String, status: &TransactionStatus) -> Self {
+        Self {
+            status: match status.err {
+                Some(_) => TxState::Failed,
+                None => TxState::Confirmed,
+            },
+            slot: Some(status.slot),
+            err: status.err.as_ref().map(|err| err.to_string()),
+            ..Self::new(signature, TxState::Pending)
+        }
+    }
+}
+
+// how long statuses of sent transactions are kept
+const TX_STATUS_TTL: Duration = Duration::from_secs(3600);
+// how long a background confirmation is watched, past the lifetime of a blockhash
+const CONFIRM_TIMEOUT: Duration = Duration::from_secs(90);
+
+/// Statuses of transactions sent by swaps, by signature, see `get_tx_status`
+#[derive(Default)]
+pub struct TxStatuses {
+    entries: Mutex<HashMap<String, TxStatus>>,
+}
+
+impl TxStatuses {
+    pub fn get(&self, signature: &str) -> Option<TxStatus> {
+        self.entries.lock().unwrap().get(signature).cloned()
+    }
+
+    fn set(&self, status: TxStatus) {
+        let mut entries = self.entries.lock().unwrap();
+        entries.retain(|_, status| status.updated_at.elapsed() < TX_STATUS_TTL);
+        entries.insert(status.signature.clone(), status);
+    }
+}
+
+tokio::task_local! {
+    // where statuses of transactions sent from the current task go, and whether to wait
+    static STATUS_TRACKING: (Arc<TxStatuses>, ConfirmMode);
+}
+
+/// Run `f` recording the status of the transactions it sends into `statuses`
+///
+/// With `ConfirmMode::Async` sending returns as soon as a transaction is submitted and a
+/// spawned task polls it until it confirms.
+pub async fn track_status<F: Future>(
+    statuses: Arc<TxStatuses>,
+    mode: ConfirmMode,
+    f: F,
+) -> F::Output {
+    STATUS_TRACKING.scope((statuses, mode), f).await
+}
+
+fn status_tracking() -> Option<(Arc<TxStatuses>, ConfirmMode)> {
+    STATUS_TRACKING.try_with(|tracking| tracking.clone()).ok()
+}
+
+// poll `signature` until it reaches the commitment of `client` or CONFIRM_TIMEOUT passes
+async fn watch_confirmation(
+    client: Arc<RpcClient>,
+    statuses: Arc<TxStatuses>,
+    signature: Signature,
+) {
+    let started = Instant::now();
+    while started.elapsed() < CONFIRM_TIMEOUT {
+        match client.get_signature_statuses(&[signature]) {
+            Ok(response) => {
+                if let Some(status) = response.value[0]
+                    .as_ref()
+                    .filter(|status| status.satisfies_commitment(client.commitment()))
+                {
+                    info!("{} landed in slot {}", signature, status.slot);
+                    statuses.set(TxStatus::from_rpc(signature.to_string(), status));
+                    return;
+                }
+            }
+            Err(err) => debug!("failed to get status of {}: {}", signature, err),
+        }
+        sleep(Duration::from_secs(1)).await;
+    }
+    warn!("{} not confirmed within {:?}", signature, CONFIRM_TIMEOUT);
+    statuses.set(TxStatus::new(signature.to_string(), TxState::Unconfirmed));
+}
+
+// record `signature` as pending and watch it in the background
+fn confirm_in_background(
+    client: &RpcClient,
+    statuses: Arc<TxStatuses>,
+    signature: Signature,
+) -> Result<()> {
+    let client = get_rpc_client_blocking_with_commitment(client.commitment())?;
+    statuses.set(TxStatus::new(signature.to_string(), TxState::Pending));
+    tokio::spawn(watch_confirmation(client, statuses, signature));
+    Ok(())
+}
+
+/// Status of `signature`, as last recorded or from the rpc
+pub async fn get_tx_status(
+    client: &RpcClient,
+    statuses: &TxStatuses,
+    signature: &str,
+) -> Result<TxStatus> {
+    let parsed = Signature::from_str(signature).map_err(|e| {
+        ApiError::new(
+            ErrorCode::InvalidInput,
+            format!("failed to parse signature: {}", e),
+        )
+    })?;
+    let recorded = statuses.get(signature);
+    if let Some(status) = recorded.as_ref().filter(|s| s.status != TxState::Pending) {
+        return Ok(status.clone());
+    }
+    let response = client.get_signature_statuses_with_history(&[parsed])?;
+    match (response.value[0].as_ref(), recorded) {
+        (Some(status), _) if status.satisfies_commitment(client.commitment()) => {
+            Ok(TxStatus::from_rpc(signature.to_string(), status))
+        }
+        (Some(status), _) => Ok(TxStatus {
+            slot: Some(status.slot),
+            ..TxStatus::new(signature.to_string(), TxState::Pending)
+        }),
+        (None, Some(recorded)) => Ok(recorded),
+        (None, None) => Err(ApiError::new(
+            ErrorCode::NotFound,
+            format!("transaction {} not found", signature),
+        )
+        .into()),
+    }
+}
+
+// last blockhash each wallet signed with, locked while a transaction of the wallet picks its own
+static WALLET_BLOCKHASHES: OnceLock<Mutex<HashMap<Pubkey, Arc<AsyncMutex<Hash>>>>> =
+    OnceLock::new();
+
+fn wallet_lock(wallet: &Pubkey) -> Arc<AsyncMutex<Hash>> {
+    let locks = WALLET_BLOCKHASHES.get_or_init(Default::default);
+    locks.lock().unwrap().entry(*wallet).or_default().clone()
+}
+
+/// Durable nonce account used instead of recent blockhashes, its authority must be the wallet
+pub fn get_nonce_account() -> Option<Pubkey> {
+    env::var("NONCE_ACCOUNT")
+        .ok()
+        .and_then(|v| Pubkey::from_str(&v).ok())
+}
+
+/// Async confirmation is refused in nonce mode, its swap would release the wallet before the
+/// nonce advances and the next swap would send with the same nonce
+pub fn check_confirm_mode(mode: ConfirmMode, nonce_account: Option<Pubkey>) -> Result<()> {
+    if let (ConfirmMode::Async, Some(nonce_account)) = (mode, nonce_account) {
+        return Err(ApiError::new(
+            ErrorCode::InvalidInput,
+            format!(
+                "async mode is not supported in nonce mode, nonce account: {}",
+                nonce_account
+            ),
+        )
+        .into());
+    }
+    Ok(())
+}
+
+// concurrent swaps fetching the latest blockhash at once would otherwise share it
+fn fresh_blockhash(client: &RpcClient, last: &mut Hash) -> Result<Hash> {
+    let mut blockhash = client.get_latest_blockhash()?;
+    if blockhash == *last {
+        blockhash = client.get_new_latest_blockhash(&blockhash)?;
+    }
+    *last = blockhash;
+    Ok(blockhash)
+}
+
+fn nonce_blockhash(client: &RpcClient, nonce_account: &Pubkey) -> Result<Hash> {
+    let account = client.get_account(nonce_account)?;
+    let data = nonce_utils::data_from_account(&account)?;
+    Ok(data.blockhash())
+}
+
+// lamports per signature
+const SIGNATURE_FEE: u64 = 5000;
 
-use crate::jito::{self, get_tip_account, get_tip_value, wait_for_bundle_confirmation};
 // prioritization fee = UNIT_PRICE * UNIT_LIMIT
 fn get_unit_price() -> u64 {
     env::var("UNIT_PRICE")
@@ -32,13 +386,320 @@ fn get_unit_limit() -> u32 {
         .unwrap_or(200_000)
 }
 
+// the runtime max per transaction
+const MAX_COMPUTE_UNIT_LIMIT: u32 = 1_400_000;
+
+fn get_cu_estimate_margin_bps() -> u64 {
+    env::var("CU_ESTIMATE_MARGIN_BPS")
+        .ok()
+        .and_then(|v| u64::from_str(&v).ok())
+        .unwrap_or(2000)
+}
+
+fn get_cu_estimate_ttl() -> Duration {
+    let ttl_ms = env::var("CU_ESTIMATE_TTL_MS")
+        .ok()
+        .and_then(|v| u64::from_str(&v).ok())
+        .unwrap_or(600_000);
+    Duration::from_millis(ttl_ms)
+}
+
+tokio::task_local! {
+    // swap type, e.g. `raydium:buy`, whose compute unit limit is estimated before sending
+    static CU_ESTIMATE_KEY: String;
+}
+
+/// Run `f` sizing the compute unit limit of its transactions from a simulation
+///
+/// The estimate is cached for CU_ESTIMATE_TTL_MS under `swap_type` and the accounts of the
+/// transaction, so only the first swap of each type on a pool, with the token account in the
+/// same state, pays for the extra simulation. A send that runs out of compute units drops it.
+pub async fn estimate_compute_units<F: Future>(swap_type: String, f: F) -> F::Output {
+    CU_ESTIMATE_KEY.scope(swap_type, f).await
+}
+
+// compute unit limit and time of the estimate per `cu_estimate_key`
+static CU_ESTIMATES: OnceLock<Mutex<HashMap<String, (Instant, u32)>>> = OnceLock::new();
+
+// `swap_type` and the programs and accounts of `instructions`, amounts left out: another pool,
+// or creating or closing the token account, is estimated on its own
+fn cu_estimate_key(swap_type: &str, instructions: &[Instruction]) -> String {
+    let mut hasher = DefaultHasher::new();
+    for instruction in instructions {
+        instruction.program_id.hash(&mut hasher);
+        for account in &instruction.accounts {
+            account.pubkey.hash(&mut hasher);
+        }
+    }
+    format!("{}:{:016x}", swap_type, hasher.finish())
+}
+
+fn forget_cu_estimate(key: &str) {
+    if let Some(estimates) = CU_ESTIMATES.get() {
+        estimates.lock().unwrap().remove(key);
+    }
+}
+
+// the send ran out of compute units, preflight or on chain
+fn is_compute_budget_exceeded(err: &anyhow::Error) -> bool {
+    let transaction_error = err
+        .chain()
+        .find_map(|e| e.downcast_ref::<ClientError>())
+        .and_then(|e| e.get_transaction_error());
+    // programs that exhaust the meter fail to complete
+    matches!(
+        transaction_error,
+        Some(TransactionError::InstructionError(
+            _,
+            InstructionError::ComputationalBudgetExceeded
+                | InstructionError::ProgramFailedToComplete
+        ))
+    ) || format!("{:#}", err).contains("exceeded CUs meter")
+}
+
+/// Units consumed plus `margin_bps`, within the runtime max
+pub fn unit_limit_with_margin(units_consumed: u64, margin_bps: u64) -> u32 {
+    let limit = units_consumed as u128 * (10_000 + margin_bps as u128) / 10_000;
+    limit.min(MAX_COMPUTE_UNIT_LIMIT as u128) as u32
+}
+
+// the cached estimate under `key`, or simulate `instructions` at the max limit
+fn estimated_unit_limit(
+    client: &RpcClient,
+    keypair: &Keypair,
+    options: &SendOptions<'_>,
+    instructions: &[Instruction],
+    key: &str,
+) -> Option<u32> {
+    let estimates = CU_ESTIMATES.get_or_init(Default::default);
+    if let Some((estimated_at, limit)) = estimates.lock().unwrap().get(key) {
+        if estimated_at.elapsed() < get_cu_estimate_ttl() {
+            return Some(*limit);
+        }
+    }
+    let simulate = || -> Result<u64> {
+        let mut simulated = instructions.to_vec();
+        simulated.insert(
+            0,
+            solana_sdk::compute_budget::ComputeBudgetInstruction::set_compute_unit_limit(
+                MAX_COMPUTE_UNIT_LIMIT,
+            ),
+        );
+        let txn = build_transaction(
+            keypair,
+            options.extra_signers,
+            &simulated,
+            options.lookup_tables,
+            Hash::default(),
+        )?;
+        let config = RpcSimulateTransactionConfig {
+            sig_verify: false,
+            replace_recent_blockhash: true,
+            commitment: Some(client.commitment()),
+            ..Default::default()
+        };
+        let result = client.simulate_transaction_with_config(&txn, config)?.value;
+        if let Some(err) = result.err {
+            return Err(anyhow!("simulation failed: {}", err));
+        }
+        result
+            .units_consumed
+            .ok_or(anyhow!("simulation did not report units consumed"))
+    };
+    match simulate() {
+        Ok(units_consumed) => {
+            let limit = unit_limit_with_margin(units_consumed, get_cu_estimate_margin_bps());
+            info!(
+                "estimated compute units of {}: {} consumed, limit {}",
+                key, units_consumed, limit
+            );
+            let ttl = get_cu_estimate_ttl();
+            let mut estimates = estimates.lock().unwrap();
+            estimates.retain(|_, (estimated_at, _)| estimated_at.elapsed() < ttl);
+            estimates.insert(key.to_string(), (Instant::now(), limit));
+            Some(limit)
+        }
+        Err(err) => {
+            warn!(
+                "compute unit estimation of {} failed, using UNIT_LIMIT: {}",
+                key, err
+            );
+            None
+        }
+    }
+}
+
+// guard against pathological routes, the runtime rejects more than 64 account locks anyway
+fn get_max_tx_accounts() -> usize {
+    env::var("MAX_TX_ACCOUNTS")
+        .ok()
+        .and_then(|v| usize::from_str(&v).ok())
+        .unwrap_or(64)
+}
+
+fn check_account_count(instructions: &[Instruction], payer: &Keypair) -> Result<usize> {
+    let account_count = Message::new(instructions, Some(&payer.pubkey()))
+        .account_keys
+        .len();
+    let max_accounts = get_max_tx_accounts();
+    if account_count > max_accounts {
+        return Err(ApiError::new(
+            ErrorCode::TooManyAccounts,
+            format!(
+                "transaction would reference {} accounts, max allowed {}",
+                account_count, max_accounts
+            ),
+        )
+        .into());
+    }
+    Ok(account_count)
+}
+
+// jito tip, the upper limit is 0.1
+async fn get_tip_lamports() -> Result<u64> {
+    let tip = get_tip_value().await?.min(0.1);
+    Ok(ui_amount_to_amount(tip, spl_token::native_mint::DECIMALS))
+}
+
+/// Fees `new_signed_and_send` would attach on top of the base signature fee
+#[derive(Debug, Clone, Serialize)]
+pub struct FeeEstimate {
+    pub compute_unit_price: Option<u64>,
+    pub compute_unit_limit: Option<u32>,
+    pub jito_tip_lamports: Option<u64>,
+    /// upper bound in lamports, priority fee or jito tip plus signature fees
+    pub total_lamports: u64,
+}
+
+pub async fn estimate_fee(use_jito: bool) -> Result<FeeEstimate> {
+    if use_jito {
+        let tip_lamports = get_tip_lamports().await?;
+        // swap tx and tip tx are signed separately
+        Ok(FeeEstimate {
+            compute_unit_price: None,
+            compute_unit_limit: None,
+            jito_tip_lamports: Some(tip_lamports),
+            total_lamports: tip_lamports + 2 * SIGNATURE_FEE,
+        })
+    } else {
+        let (unit_price, unit_limit) = (get_unit_price(), get_unit_limit());
+        // unit price is in micro-lamports
+        let priority_lamports = (unit_price as u128 * unit_limit as u128 / 1_000_000) as u64;
+        Ok(FeeEstimate {
+            compute_unit_price: Some(unit_price),
+            compute_unit_limit: Some(unit_limit),
+            jito_tip_lamports: None,
+            total_lamports: priority_lamports + SIGNATURE_FEE,
+        })
+    }
+}
+
+// swaps through pools with many accounts need a lookup table to fit in a packet
+pub fn get_raydium_lookup_table() -> Option<Pubkey> {
+    env::var("RAYDIUM_LOOKUP_TABLE")
+        .ok()
+        .and_then(|v| Pubkey::from_str(&v).ok())
+}
+
+pub fn load_lookup_table(client: &RpcClient, key: &Pubkey) -> Result<AddressLookupTableAccount> {
+    let account = client.get_account(key)?;
+    let table = AddressLookupTable::deserialize(&account.data)
+        .map_err(|e| anyhow!("failed to deserialize lookup table {}: {}", key, e))?;
+    Ok(AddressLookupTableAccount {
+        key: *key,
+        addresses: table.addresses.to_vec(),
+    })
+}
+
+/// Legacy transaction without lookup tables, v0 otherwise
+pub fn build_transaction(
+    keypair: &Keypair,
+    extra_signers: &[&Keypair],
+    instructions: &[Instruction],
+    lookup_tables: &[AddressLookupTableAccount],
+    recent_blockhash: Hash,
+) -> Result<VersionedTransaction> {
+    let mut signers = vec![keypair];
+    signers.extend_from_slice(extra_signers);
+    let txn = if lookup_tables.is_empty() {
+        VersionedTransaction::from(Transaction::new_signed_with_payer(
+            instructions,
+            Some(&keypair.pubkey()),
+            &signers,
+            recent_blockhash,
+        ))
+    } else {
+        let message = v0::Message::try_compile(
+            &keypair.pubkey(),
+            instructions,
+            lookup_tables,
+            recent_blockhash,
+        )?;
+        VersionedTransaction::try_new(VersionedMessage::V0(message), &signers)?
+    };
+    let size = bincode::serialized_size(&txn)? as usize;
+    if size > PACKET_DATA_SIZE {
+        return Err(ApiError::new(
+            ErrorCode::TransactionTooLarge,
+            format!(
+                "transaction is {} bytes, max {}, configure a lookup table",
+                size, PACKET_DATA_SIZE
+            ),
+        )
+        .into());
+    }
+    Ok(txn)
+}
+
+#[derive(Default)]
+pub struct SendOptions<'a> {
+    pub use_jito: bool,
+    pub lookup_tables: &'a [AddressLookupTableAccount],
+    /// signers besides the fee payer, e.g. a new mint
+    pub extra_signers: &'a [&'a Keypair],
+}
+
 pub async fn new_signed_and_send(
     client: &RpcClient,
     keypair: &Keypair,
-    mut instructions: Vec<Instruction>,
+    instructions: Vec<Instruction>,
     use_jito: bool,
 ) -> Result<Vec<String>> {
-    let unit_limit = get_unit_limit();
+    let options = SendOptions {
+        use_jito,
+        ..Default::default()
+    };
+    new_signed_and_send_with_options(client, keypair, instructions, options).await
+}
+
+pub async fn new_signed_and_send_with_options(
+    client: &RpcClient,
+    keypair: &Keypair,
+    mut instructions: Vec<Instruction>,
+    options: SendOptions<'_>,
+) -> Result<Vec<String>> {
+    let bundling = BUNDLE.try_with(|_| ()).is_ok();
+    // transactions that are only simulated keep the configured limit
+    let simulating = bundling || env::var("TX_SIMULATE").ok() == Some("true".to_string());
+    let estimate_key = match CU_ESTIMATE_KEY.try_with(|swap_type| swap_type.clone()) {
+        Ok(swap_type) if !options.use_jito && !simulating => {
+            Some(cu_estimate_key(&swap_type, &instructions))
+        }
+        _ => None,
+    };
+    let unit_limit = estimate_key
+        .as_deref()
+        .and_then(|key| estimated_unit_limit(client, keypair, &options, &instructions, key))
+        .unwrap_or_else(get_unit_limit);
+    let SendOptions {
+        use_jito,
+        lookup_tables,
+        extra_signers,
+    } = options;
+    if use_jito {
+        jito::check_network()?;
+    }
+    let original_len = instructions.len();
     let unit_price = get_unit_price();
     // If not using Jito, manually set the compute unit price and limit
     if !use_jito {
@@ -53,14 +714,48 @@ pub async fn new_signed_and_send(
         instructions.insert(0, modify_compute_units);
         instructions.insert(1, add_priority_fee);
     }
+    let nonce_account = get_nonce_account().filter(|_| !bundling);
+    if let Some(nonce_account) = nonce_account {
+        // must be the first instruction of a durable nonce transaction
+        instructions.insert(
+            0,
+            system_instruction::advance_nonce_account(&nonce_account, &keypair.pubkey()),
+        );
+    }
+    let account_count = check_account_count(&instructions, keypair)?;
+    debug!("transaction accounts: {}", account_count);
     // send init tx
-    let recent_blockhash = client.get_latest_blockhash()?;
-    let txn = Transaction::new_signed_with_payer(
+    let mut last_blockhash = wallet_lock(&keypair.pubkey()).lock_owned().await;
+    let recent_blockhash = match nonce_account {
+        Some(nonce_account) => {
+            debug!("nonce mode, nonce account: {}", nonce_account);
+            nonce_blockhash(client, &nonce_account)?
+        }
+        None => fresh_blockhash(client, &mut last_blockhash)?,
+    };
+    // a nonce can only be used once, keep the wallet locked until it is advanced on chain
+    let _nonce_guard = match nonce_account {
+        Some(_) => Some(last_blockhash),
+        None => {
+            drop(last_blockhash);
+            None
+        }
+    };
+    // the original instructions, before compute budget and nonce ones were added
+    let swap_instructions = instructions[instructions.len() - original_len..].to_vec();
+    let txn = build_transaction(
+        keypair,
+        extra_signers,
         &instructions,
-        Some(&keypair.pubkey()),
-        &vec![&*keypair],
+        lookup_tables,
         recent_blockhash,
-    );
+    )?;
+
+    if let Ok(bundle) = BUNDLE.try_with(|bundle| bundle.clone()) {
+        let signature = txn.signatures[0].to_string();
+        bundle.lock().unwrap().push(txn);
+        return Ok(vec![signature]);
+    }
 
     if env::var("TX_SIMULATE").ok() == Some("true".to_string()) {
         let simulate_result = client.simulate_transaction(&txn)?;
@@ -75,58 +770,608 @@ pub async fn new_signed_and_send(
         };
     }
 
+    // transactions with extra signers can't be signed again later
+    if extra_signers.is_empty() {
+        let _ = SENT.try_with(|sent| {
+            sent.insert(
+                txn.signatures[0].to_string(),
+                SentTransaction {
+                    payer: keypair.pubkey(),
+                    instructions: swap_instructions,
+                    lookup_tables: lookup_tables.to_vec(),
+                    unit_limit,
+                    unit_price,
+                    use_jito,
+                    recent_blockhash,
+                    nonce_account,
+                    sent_at: Instant::now(),
+                },
+            )
+        });
+    }
+    // the tip has no advance nonce instruction, it needs a recent blockhash
+    let tip_blockhash = match (use_jito, nonce_account) {
+        (true, Some(_)) => client.get_latest_blockhash()?,
+        _ => recent_blockhash,
+    };
+    let result = send_transaction(client, keypair, txn, use_jito, tip_blockhash).await;
+    if let (Err(err), Some(key)) = (&result, &estimate_key) {
+        if is_compute_budget_exceeded(err) {
+            warn!("compute unit estimate of {} was too low, dropping it", key);
+            forget_cu_estimate(key);
+        }
+    }
+    result
+}
+
+/// Jito tip transfer from the wallet, the last transaction of a bundle
+pub async fn tip_transaction(
+    keypair: &Keypair,
+    recent_blockhash: Hash,
+) -> Result<VersionedTransaction> {
+    let tip_account = get_tip_account().await?;
+    let tip_lamports = get_tip_lamports().await?;
+    info!(
+        "tip account: {}, tip(sol): {}, lamports: {}",
+        tip_account,
+        lamports_to_sol(tip_lamports),
+        tip_lamports
+    );
+    Ok(VersionedTransaction::from(system_transaction::transfer(
+        keypair,
+        &tip_account,
+        tip_lamports,
+        recent_blockhash,
+    )))
+}
+
+/// Send `bundle`, ending with its tip transaction, as one jito bundle and wait for it to land
+///
+/// Returns the signature of every transaction of the bundle in order, the tip last.
+pub async fn send_bundle(
+    client: &RpcClient,
+    bundle: Vec<VersionedTransaction>,
+) -> Result<Vec<String>> {
+    jito::check_network()?;
     let start_time = Instant::now();
-    let mut txs = vec![];
-    if use_jito {
-        // jito
-        let tip_account = get_tip_account().await?;
-        // jito tip, the upper limit is 0.1
-        let mut tip = get_tip_value().await?;
-        tip = tip.min(0.1);
-        let tip_lamports = ui_amount_to_amount(tip, spl_token::native_mint::DECIMALS);
-        info!(
-            "tip account: {}, tip(sol): {}, lamports: {}",
-            tip_account, tip, tip_lamports
-        );
+    let jito_client = Arc::new(JitoRpcClient::new(format!(
+        "{}/api/v1/bundles",
+        jito::BLOCK_ENGINE_URL.to_string()
+    )));
+    for txn in &bundle[..bundle.len().saturating_sub(1)] {
+        record_submitted(txn);
+    }
+    let bundle_id = jito_client
+        .send_bundle(&bundle)
+        .await
+        .inspect_err(|_| counter!(telemetry::JITO_BUNDLES, "status" => "rejected").increment(1))?;
+    info!("bundle_id: {}", bundle_id);
+    counter!(telemetry::JITO_BUNDLES, "status" => "submitted").increment(1);
+    if let Some((statuses, ConfirmMode::Async)) = status_tracking() {
+        confirm_in_background(client, statuses, bundle[0].signatures[0])?;
+        // the tip signature too, as a landed bundle reports it, see `get_tx_costs`
+        return Ok(bundle
+            .iter()
+            .map(|txn| txn.signatures[0].to_string())
+            .collect());
+    }
 
-        let jito_client = Arc::new(JitoRpcClient::new(format!(
-            "{}/api/v1/bundles",
-            jito::BLOCK_ENGINE_URL.to_string()
-        )));
-        // tip tx
-        let mut bundle: Vec<VersionedTransaction> = vec![];
-        bundle.push(VersionedTransaction::from(txn));
-        bundle.push(VersionedTransaction::from(system_transaction::transfer(
-            &keypair,
-            &tip_account,
-            tip_lamports,
-            recent_blockhash,
-        )));
-        let bundle_id = jito_client.send_bundle(&bundle).await?;
-        info!("bundle_id: {}", bundle_id);
-
-        txs = wait_for_bundle_confirmation(
-            move |id: String| {
-                let client = Arc::clone(&jito_client);
-                async move {
-                    let response = client.get_bundle_statuses(&[id]).await;
-                    let statuses = response.inspect_err(|err| {
-                        error!("Error fetching bundle status: {:?}", err);
-                    })?;
-                    Ok(statuses.value)
-                }
-            },
-            bundle_id,
-            Duration::from_millis(1000),
-            Duration::from_secs(10),
-        )
-        .await?;
-    } else {
-        let sig = common::rpc::send_txn(&client, &txn, true)?;
-        info!("signature: {:?}", sig);
-        txs.push(sig.to_string());
+    let confirmation = wait_for_bundle_confirmation(
+        move |id: String| {
+            let client = Arc::clone(&jito_client);
+            async move {
+                let response = client.get_bundle_statuses(&[id]).await;
+                let statuses = response.inspect_err(|err| {
+                    error!("Error fetching bundle status: {:?}", err);
+                })?;
+                Ok(statuses.value)
+            }
+        },
+        bundle_id,
+        Duration::from_millis(1000),
+        Duration::from_secs(10),
+    )
+    .await;
+    histogram!(telemetry::JITO_BUNDLE_DURATION).record(start_time.elapsed().as_secs_f64());
+    let txs = match confirmation {
+        Ok(bundle_txs) => {
+            counter!(telemetry::JITO_BUNDLES, "status" => "landed").increment(1);
+            bundle_txs
+        }
+        Err(err) => {
+            counter!(telemetry::JITO_BUNDLES, "status" => "failed").increment(1);
+            return Err(err);
+        }
+    };
+    if let Some((statuses, _)) = status_tracking() {
+        for signature in &txs {
+            statuses.set(TxStatus::new(signature.clone(), TxState::Confirmed));
+        }
+    }
+    info!("bundle elapsed: {:?}", start_time.elapsed());
+    Ok(txs)
+}
+
+// send `txn` to the rpc, or as a jito bundle with a tip transaction, and wait for it to land
+async fn send_transaction(
+    client: &RpcClient,
+    keypair: &Keypair,
+    txn: VersionedTransaction,
+    use_jito: bool,
+    tip_blockhash: Hash,
+) -> Result<Vec<String>> {
+    if use_jito {
+        let tip = tip_transaction(keypair, tip_blockhash).await?;
+        return send_bundle(client, vec![txn, tip]).await;
+    }
+    let start_time = Instant::now();
+    record_submitted(&txn);
+    if let Some((statuses, ConfirmMode::Async)) = status_tracking() {
+        let sig = client.send_transaction(&txn)?;
+        info!("signature: {:?}, confirming in the background", sig);
+        confirm_in_background(client, statuses, sig)?;
+        return Ok(vec![sig.to_string()]);
+    }
+    let sig = match txn.into_legacy_transaction() {
+        Some(txn) => common::rpc::send_txn(&client, &txn, true)?,
+        None => client.send_and_confirm_transaction(&txn)?,
+    };
+    info!("signature: {:?}", sig);
+    let txs = vec![sig.to_string()];
+    if let Some((statuses, _)) = status_tracking() {
+        for signature in &txs {
+            statuses.set(TxStatus::new(signature.clone(), TxState::Confirmed));
+        }
     }
 
     info!("tx elapsed: {:?}", start_time.elapsed());
     Ok(txs)
 }
+
+/// Result of re-sending a stuck transaction with a higher fee
+#[derive(Debug, Clone, Serialize)]
+pub struct BumpOutcome {
+    pub replaced: String,
+    pub signatures: Vec<String>,
+    pub previous_unit_price: u64,
+    pub unit_price: u64,
+    pub jito: bool,
+}
+
+// twice the previous price unless one is requested, which must be higher
+fn bumped_unit_price(previous: u64, requested: Option<u64>) -> Result<u64> {
+    let unit_price = requested.unwrap_or(previous.saturating_mul(2).max(1));
+    if unit_price <= previous {
+        return Err(ApiError::new(
+            ErrorCode::InvalidInput,
+            format!(
+                "unit_price {} must be above the previous {}",
+                unit_price, previous
+            ),
+        )
+        .into());
+    }
+    Ok(unit_price)
+}
+
+/// Rebuild the transaction sent as `signature` with a higher compute unit price and send it
+///
+/// With a durable nonce the replacement uses the same nonce, so at most one of them lands.
+/// Without one it is refused while the original blockhash is valid, as both could land, and
+/// uses a fresh blockhash once the original can no longer land.
+pub async fn bump(
+    client: &RpcClient,
+    keypair: &Keypair,
+    sent_transactions: &SentTransactions,
+    signature: &str,
+    unit_price: Option<u64>,
+    use_jito: Option<bool>,
+) -> Result<BumpOutcome> {
+    let parsed = Signature::from_str(signature).map_err(|e| {
+        ApiError::new(
+            ErrorCode::InvalidInput,
+            format!("failed to parse signature: {}", e),
+        )
+    })?;
+    let sent = sent_transactions.get(signature).ok_or_else(|| {
+        ApiError::new(
+            ErrorCode::NotFound,
+            format!(
+                "no transaction {} sent in the last {} seconds",
+                signature,
+                SENT_TRANSACTION_TTL.as_secs()
+            ),
+        )
+    })?;
+    if sent.payer != keypair.pubkey() {
+        return Err(ApiError::new(
+            ErrorCode::InvalidInput,
+            format!(
+                "transaction {} was paid by {}, the loaded wallet is {}",
+                signature,
+                sent.payer,
+                keypair.pubkey()
+            ),
+        )
+        .into());
+    }
+    if let Some(status) = client.get_signature_statuses(&[parsed])?.value[0].as_ref() {
+        return Err(ApiError::new(
+            ErrorCode::AlreadyConfirmed,
+            format!(
+                "transaction {} already landed in slot {}",
+                signature, status.slot
+            ),
+        )
+        .into());
+    }
+    let unit_price = bumped_unit_price(sent.unit_price, unit_price)?;
+    let use_jito = use_jito.unwrap_or(sent.use_jito);
+    if use_jito {
+        jito::check_network()?;
+    }
+
+    let recent_blockhash = match sent.nonce_account {
+        Some(nonce_account) => {
+            let blockhash = nonce_blockhash(client, &nonce_account)?;
+            if blockhash != sent.recent_blockhash {
+                return Err(ApiError::new(
+                    ErrorCode::AlreadyConfirmed,
+                    format!(
+                        "nonce {} advanced, the transaction or a replacement landed",
+                        nonce_account
+                    ),
+                )
+                .into());
+            }
+            blockhash
+        }
+        None if client.is_blockhash_valid(&sent.recent_blockhash, client.commitment())? => {
+            return Err(ApiError::new(
+                ErrorCode::InvalidInput,
+                format!(
+                    "transaction {} can still land until its blockhash expires, a replacement could land too: wait for it to expire or enable nonce mode with NONCE_ACCOUNT",
+                    signature
+                ),
+            )
+            .into());
+        }
+        // the original expired, it can no longer land
+        None => client.get_latest_blockhash()?,
+    };
+    let mut instructions = vec![
+        solana_sdk::compute_budget::ComputeBudgetInstruction::set_compute_unit_limit(
+            sent.unit_limit,
+        ),
+        solana_sdk::compute_budget::ComputeBudgetInstruction::set_compute_unit_price(unit_price),
+    ];
+    if let Some(nonce_account) = sent.nonce_account {
+        instructions.insert(
+            0,
+            system_instruction::advance_nonce_account(&nonce_account, &keypair.pubkey()),
+        );
+    }
+    instructions.extend(sent.instructions.iter().cloned());
+    let txn = build_transaction(
+        keypair,
+        &[],
+        &instructions,
+        &sent.lookup_tables,
+        recent_blockhash,
+    )?;
+    let replacement = txn.signatures[0].to_string();
+    info!(
+        "bump {} -> {}, unit price {} -> {}",
+        signature, replacement, sent.unit_price, unit_price
+    );
+    sent_transactions.insert(
+        replacement,
+        SentTransaction {
+            unit_price,
+            use_jito,
+            recent_blockhash,
+            sent_at: Instant::now(),
+            ..sent.clone()
+        },
+    );
+    let tip_blockhash = match (use_jito, sent.nonce_account) {
+        (true, Some(_)) => client.get_latest_blockhash()?,
+        _ => recent_blockhash,
+    };
+    let signatures = send_transaction(client, keypair, txn, use_jito, tip_blockhash).await?;
+    Ok(BumpOutcome {
+        replaced: signature.to_string(),
+        signatures,
+        previous_unit_price: sent.unit_price,
+        unit_price,
+        jito: use_jito,
+    })
+}
+
+// rent of a 165 bytes token account
+const TOKEN_ACCOUNT_RENT: i128 = 2_039_280;
+
+/// Balance changes of the fee payer in a confirmed transaction
+#[derive(Debug, Clone, Default)]
+pub struct BalanceChanges {
+    /// native sol change with the transaction fee added back
+    pub sol_delta: i128,
+    pub fee: u64,
+    pub token_delta: i128,
+    pub token_account_created: bool,
+    pub token_account_closed: bool,
+}
+
+impl BalanceChanges {
+    /// sol change caused by the trade itself, excluding fees and token account rent
+    pub fn trade_sol_delta(&self) -> i128 {
+        let mut delta = self.sol_delta;
+        if self.token_account_created {
+            delta += TOKEN_ACCOUNT_RENT;
+        }
+        if self.token_account_closed {
+            delta -= TOKEN_ACCOUNT_RENT;
+        }
+        delta
+    }
+}
+
+/// What a landed swap cost in lamports
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TxCosts {
+    /// signature fees, the tip transaction's included
+    pub base_fee: u64,
+    /// compute unit price times the requested compute unit limit
+    pub priority_fee: u64,
+    pub compute_units: Option<u64>,
+    pub jito_tip: u64,
+    pub total: u64,
+}
+
+impl TxCosts {
+    /// Costs from the `fee` and `compute_units` of the swap transaction, and the fee and fee
+    /// payer sol change of the jito tip transaction if any
+    ///
+    /// Swaps are signed by the wallet alone, what the fee holds past one signature is priority fee.
+    pub fn new(fee: u64, compute_units: Option<u64>, tip: Option<(u64, i128)>) -> Self {
+        let base_fee = fee.min(SIGNATURE_FEE);
+        let (tip_fee, jito_tip) = match tip {
+            Some((tip_fee, sol_delta)) => (tip_fee, (-sol_delta - tip_fee as i128).max(0) as u64),
+            None => (0, 0),
+        };
+        TxCosts {
+            base_fee: base_fee + tip_fee,
+            priority_fee: fee - base_fee,
+            compute_units,
+            jito_tip,
+            total: fee + tip_fee + jito_tip,
+        }
+    }
+}
+
+/// Costs of the landed swap `signatures`, the second one is the jito tip transaction if any
+pub async fn get_tx_costs(client: &RpcClient, signatures: &[String]) -> Result<TxCosts> {
+    let signature = signatures
+        .first()
+        .ok_or(anyhow!("no signature to read the costs of"))?;
+    let meta = get_transaction_meta(client, signature).await?;
+    tx_costs(client, &meta, signatures).await
+}
+
+/// Costs of a swap whose transaction `meta` was already read
+pub async fn tx_costs(
+    client: &RpcClient,
+    meta: &UiTransactionStatusMeta,
+    signatures: &[String],
+) -> Result<TxCosts> {
+    let tip = match signatures.get(1) {
+        Some(tip_signature) => {
+            let tip_meta = get_transaction_meta(client, tip_signature).await?;
+            Some((tip_meta.fee, fee_payer_sol_delta(&tip_meta)))
+        }
+        None => None,
+    };
+    let compute_units = match meta.compute_units_consumed {
+        OptionSerializer::Some(units) => Some(units),
+        _ => None,
+    };
+    Ok(TxCosts::new(meta.fee, compute_units, tip))
+}
+
+// post minus pre balance of the fee payer, fee included
+fn fee_payer_sol_delta(meta: &UiTransactionStatusMeta) -> i128 {
+    match (meta.pre_balances.first(), meta.post_balances.first()) {
+        (Some(pre), Some(post)) => *post as i128 - *pre as i128,
+        _ => 0,
+    }
+}
+
+pub(crate) fn owner_token_amount(
+    balances: &OptionSerializer<Vec<UiTransactionTokenBalance>>,
+    owner: &str,
+    mint: &str,
+) -> Option<i128> {
+    let OptionSerializer::Some(balances) = balances else {
+        return None;
+    };
+    balances
+        .iter()
+        .find(|balance| {
+            balance.mint == mint
+                && matches!(&balance.owner, OptionSerializer::Some(o) if o.as_str() == owner)
+        })
+        .and_then(|balance| i128::from_str(&balance.ui_token_amount.amount).ok())
+}
+
+/// Status meta of a confirmed transaction, retried while it is not queryable yet
+pub async fn get_transaction_meta(
+    client: &RpcClient,
+    signature: &str,
+) -> Result<UiTransactionStatusMeta> {
+    let signature = Signature::from_str(signature)?;
+    let config = RpcTransactionConfig {
+        encoding: Some(UiTransactionEncoding::Json),
+        commitment: Some(CommitmentConfig::confirmed()),
+        max_supported_transaction_version: Some(0),
+    };
+    // the transaction can take a moment to be queryable after confirmation
+    let mut retries = 5;
+    let transaction = loop {
+        match client.get_transaction_with_config(&signature, config) {
+            Ok(transaction) => break transaction,
+            Err(err) if retries > 0 => {
+                debug!("get transaction {} err: {}, retrying", signature, err);
+                retries -= 1;
+                sleep(Duration::from_secs(1)).await;
+            }
+            Err(err) => return Err(err.into()),
+        }
+    };
+    transaction
+        .transaction
+        .meta
+        .ok_or(anyhow!("transaction meta not found: {}", signature))
+}
+
+pub async fn get_balance_changes(
+    client: &RpcClient,
+    signature: &str,
+    owner: &Pubkey,
+    mint: &Pubkey,
+) -> Result<BalanceChanges> {
+    let meta = get_transaction_meta(client, signature).await?;
+    Ok(balance_changes(&meta, owner, mint))
+}
+
+/// Balance changes of `owner` in `mint`, sol changes are read from the fee payer
+pub fn balance_changes(
+    meta: &UiTransactionStatusMeta,
+    owner: &Pubkey,
+    mint: &Pubkey,
+) -> BalanceChanges {
+    let sol_delta = fee_payer_sol_delta(meta) + meta.fee as i128;
+    let owner = owner.to_string();
+    let mint = mint.to_string();
+    let pre_amount = owner_token_amount(&meta.pre_token_balances, &owner, &mint);
+    let post_amount = owner_token_amount(&meta.post_token_balances, &owner, &mint);
+
+    BalanceChanges {
+        sol_delta,
+        fee: meta.fee,
+        token_delta: post_amount.unwrap_or_default() - pre_amount.unwrap_or_default(),
+        token_account_created: pre_amount.is_none() && post_amount.is_some(),
+        token_account_closed: pre_amount.is_some() && post_amount.is_none(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use solana_sdk::instruction::AccountMeta;
+
+    use super::*;
+
+    #[test]
+    fn test_unit_limit_with_margin() {
+        assert_eq!(unit_limit_with_margin(100_000, 2000), 120_000);
+        assert_eq!(unit_limit_with_margin(100_000, 0), 100_000);
+        assert_eq!(
+            unit_limit_with_margin(1_300_000, 2000),
+            MAX_COMPUTE_UNIT_LIMIT
+        );
+    }
+
+    #[test]
+    fn test_cu_estimate_key() {
+        let (program, pool) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let on = |data: &[u8], pool: Pubkey| {
+            Instruction::new_with_bytes(program, data, vec![AccountMeta::new(pool, false)])
+        };
+        let create_ata = Instruction::new_with_bytes(Pubkey::new_unique(), &[1], vec![]);
+
+        let key = cu_estimate_key("raydium:buy", &[on(&[1], pool)]);
+        // amounts do not matter
+        assert_eq!(key, cu_estimate_key("raydium:buy", &[on(&[2], pool)]));
+        assert_ne!(key, cu_estimate_key("raydium:sell", &[on(&[1], pool)]));
+        assert_ne!(
+            key,
+            cu_estimate_key("raydium:buy", &[on(&[1], Pubkey::new_unique())])
+        );
+        assert_ne!(
+            key,
+            cu_estimate_key("raydium:buy", &[create_ata, on(&[1], pool)])
+        );
+        assert!(key.starts_with("raydium:buy:"));
+
+        let err = anyhow!("Program log: exceeded CUs meter at BPF instruction");
+        assert!(is_compute_budget_exceeded(&err));
+        assert!(!is_compute_budget_exceeded(&anyhow!("blockhash not found")));
+    }
+
+    #[test]
+    fn test_tx_costs() {
+        // 5000 base fee and 20_000 micro-lamports per unit over 200_000 units
+        let costs = TxCosts::new(9_000, Some(61_354), None);
+        assert_eq!(
+            costs,
+            TxCosts {
+                base_fee: 5_000,
+                priority_fee: 4_000,
+                compute_units: Some(61_354),
+                jito_tip: 0,
+                total: 9_000,
+            }
+        );
+        // the tip transaction paid its fee and a 100_000 lamports tip
+        let costs = TxCosts::new(5_000, Some(61_354), Some((5_000, -105_000)));
+        assert_eq!(costs.base_fee, 10_000);
+        assert_eq!(costs.priority_fee, 0);
+        assert_eq!(costs.jito_tip, 100_000);
+        assert_eq!(costs.total, 110_000);
+    }
+
+    #[test]
+    fn test_bumped_unit_price() {
+        assert_eq!(bumped_unit_price(20_000, None).unwrap(), 40_000);
+        assert_eq!(bumped_unit_price(0, None).unwrap(), 1);
+        assert_eq!(bumped_unit_price(20_000, Some(50_000)).unwrap(), 50_000);
+        let err = bumped_unit_price(20_000, Some(20_000)).unwrap_err();
+        assert_eq!(ApiError::from(&err).code, ErrorCode::InvalidInput);
+    }
+
+    #[test]
+    fn test_check_confirm_mode() {
+        let nonce_account = Some(Pubkey::new_unique());
+        assert!(check_confirm_mode(ConfirmMode::Sync, nonce_account).is_ok());
+        assert!(check_confirm_mode(ConfirmMode::Async, None).is_ok());
+        let err = check_confirm_mode(ConfirmMode::Async, nonce_account).unwrap_err();
+        assert_eq!(ApiError::from(&err).code, ErrorCode::InvalidInput);
+    }
+
+    #[test]
+    fn test_build_transaction_with_lookup_table() {
+        let payer = Keypair::new();
+        let accounts: Vec<Pubkey> = (0..40).map(|_| Pubkey::new_unique()).collect();
+        let instructions = vec![Instruction::new_with_bytes(
+            Pubkey::new_unique(),
+            &[0; 17],
+            accounts
+                .iter()
+                .map(|key| AccountMeta::new(*key, false))
+                .collect(),
+        )];
+
+        // 42 static keys alone are more than a packet
+        let err = build_transaction(&payer, &[], &instructions, &[], Hash::default()).unwrap_err();
+        assert_eq!(ApiError::from(&err).code, ErrorCode::TransactionTooLarge);
+
+        let lookup_table = AddressLookupTableAccount {
+            key: Pubkey::new_unique(),
+            addresses: accounts,
+        };
+        let txn = build_transaction(&payer, &[], &instructions, &[lookup_table], Hash::default())
+            .unwrap();
+        assert!(matches!(txn.message, VersionedMessage::V0(_)));
+        assert!(bincode::serialized_size(&txn).unwrap() as usize <= PACKET_DATA_SIZE);
+        assert!(txn.verify_with_results().iter().all(|ok| *ok));
+    }
+}
@@ -0,0 +1,67 @@
+use std::{env, time::Duration};
+
+use anyhow::Result;
+use serde::Serialize;
+use tracing::{debug, warn};
+
+use crate::{get_client_build, helper::redact_url, swap::SwapDirection};
+
+const WEBHOOK_TIMEOUT: Duration = Duration::from_secs(3);
+const WEBHOOK_RETRIES: u32 = 2;
+
+pub fn get_webhook_url() -> Option<String> {
+    env::var("WEBHOOK_URL").ok().filter(|url| !url.is_empty())
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SwapStatus {
+    Success,
+    Failed,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SwapEvent {
+    pub mint: String,
+    pub direction: SwapDirection,
+    /// amount_in as requested, sol for buys, tokens or a fraction for sells
+    pub amount: f64,
+    pub signature: Option<String>,
+    pub status: SwapStatus,
+    pub error: Option<String>,
+}
+
+/// Post the event in the background, failures are only logged
+pub fn notify(url: String, event: SwapEvent) {
+    tokio::spawn(async move {
+        if let Err(err) = deliver(&url, &event).await {
+            warn!("webhook delivery to {} failed: {}", redact_url(&url), err);
+        }
+    });
+}
+
+async fn deliver(url: &str, event: &SwapEvent) -> Result<()> {
+    let client = get_client_build()?;
+    let mut attempt = 0;
+    loop {
+        let result = client
+            .post(url)
+            .timeout(WEBHOOK_TIMEOUT)
+            .json(event)
+            .send()
+            .await
+            .and_then(|response| response.error_for_status());
+        match result {
+            Ok(_) => {
+                debug!("webhook delivered: {:?}", event);
+                return Ok(());
+            }
+            Err(err) if attempt < WEBHOOK_RETRIES => {
+                attempt += 1;
+                debug!("webhook attempt {} failed: {}", attempt, err);
+                tokio::time::sleep(Duration::from_millis(500 * attempt as u64)).await;
+            }
+            Err(err) => return Err(err.into()),
+        }
+    }
+}